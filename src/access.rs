@@ -0,0 +1,240 @@
+//! Checks the uses of uniform and read-only storage buffers against what their address
+//! space allows, pointing at the access at fault rather than the function naga rejects.
+
+use std::ops::Range;
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Url,
+};
+
+use crate::position::byte_range_to_range;
+use crate::syntax::{Declaration, DeclarationKind, SourceIndex};
+
+/// The atomic builtins that write through their pointer, which is all of them but
+/// `atomicLoad`.
+const ATOMIC_WRITES: &[&str] = &[
+    "atomicStore",
+    "atomicAdd",
+    "atomicSub",
+    "atomicMax",
+    "atomicMin",
+    "atomicAnd",
+    "atomicOr",
+    "atomicXor",
+    "atomicExchange",
+    "atomicCompareExchangeWeak",
+];
+
+/// The address spaces whose variables shaders can't write.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReadOnly {
+    Uniform,
+    /// `var<storage>` or `var<storage, read>`.
+    Storage,
+}
+
+impl ReadOnly {
+    /// The address space of a global `var`, from its template as written, if it can't be
+    /// written.
+    fn of(index: &SourceIndex, text: &str, decl: &Declaration) -> Option<ReadOnly> {
+        let template: Vec<&str> = index
+            .tokens
+            .iter()
+            .filter(|token| decl.full.start <= token.start && token.end <= decl.selection.start)
+            .map(|token| token.text(text))
+            .skip_while(|&token| token != "var")
+            .skip(1)
+            .filter(|&token| token != "<" && token != ">" && token != ",")
+            .collect();
+        match template.as_slice() {
+            ["uniform"] => Some(ReadOnly::Uniform),
+            ["storage"] | ["storage", "read"] => Some(ReadOnly::Storage),
+            _ => None,
+        }
+    }
+
+    fn declared(self) -> &'static str {
+        match self {
+            ReadOnly::Uniform => "var<uniform>",
+            ReadOnly::Storage => "var<storage, read>",
+        }
+    }
+}
+
+/// Reports assignments, increments and atomic writes to uniform and read-only storage
+/// buffers, including to their members and elements, and `arrayLength` of uniform
+/// buffers, as only storage buffers can hold runtime-sized arrays.
+pub fn check(uri: &Url, text: &str, index: &SourceIndex, source: &str) -> Vec<Diagnostic> {
+    let spaces: Vec<Option<ReadOnly>> = index
+        .declarations
+        .iter()
+        .map(|decl| match decl.kind {
+            DeclarationKind::GlobalVar => ReadOnly::of(index, text, decl),
+            _ => None,
+        })
+        .collect();
+    let mut diags = Vec::new();
+    for reference in &index.references {
+        let (decl, space) = match reference.target {
+            Some(target) => match spaces[target] {
+                Some(space) => (&index.declarations[target], space),
+                None => continue,
+            },
+            None => continue,
+        };
+        let first = match index
+            .tokens
+            .iter()
+            .position(|token| token.start == reference.span.start)
+        {
+            Some(first) => first,
+            None => continue,
+        };
+        let text_at = |i: usize| index.tokens.get(i).map_or("", |token| token.text(text));
+
+        // the members and elements accessed, as in `buffer.items[i].count`
+        let mut last = first;
+        loop {
+            match text_at(last + 1) {
+                "." if index.tokens.get(last + 2).is_some() => last += 2,
+                "[" => match index.matching[last + 1] {
+                    Some(close) => last = close,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+        let access = index.tokens[first].start..index.tokens[last].end;
+        let touching = |a: usize, b: usize| {
+            index
+                .tokens
+                .get(a)
+                .zip(index.tokens.get(b))
+                .is_some_and(|(a, b)| a.end == b.start)
+        };
+
+        let (what, atomic) = match (text_at(last + 1), text_at(last + 2)) {
+            ("=", next) if next != "=" => ("assigned to", None),
+            ("+", "+") | ("-", "-")
+                if touching(last + 1, last + 2) && matches!(text_at(last + 3), ";" | ")") =>
+            {
+                ("incremented", None)
+            }
+            ("+", "=")
+            | ("-", "=")
+            | ("*", "=")
+            | ("/", "=")
+            | ("%", "=")
+            | ("&", "=")
+            | ("|", "=")
+            | ("^", "=")
+                if touching(last + 1, last + 2) =>
+            {
+                ("assigned to", None)
+            }
+            ("<", "<") | (">", ">") if text_at(last + 3) == "=" => ("assigned to", None),
+            _ if first > 0 && text_at(first - 1) == "&" => {
+                let callee = index
+                    .enclosing_call(text, index.tokens[first].start)
+                    .filter(|&(_, argument)| argument == 0)
+                    .map(|(callee, _)| text_at(callee));
+                match callee {
+                    Some(callee) if ATOMIC_WRITES.contains(&callee) => ("written", Some(callee)),
+                    Some("arrayLength") if space == ReadOnly::Uniform => {
+                        diags.push(diagnostic(
+                            uri,
+                            text,
+                            source,
+                            access,
+                            format!(
+                                "`arrayLength` takes a storage buffer, and `{}` is a uniform \
+                                 buffer, which can't hold runtime-sized arrays",
+                                decl.name
+                            ),
+                            decl,
+                        ));
+                        continue;
+                    }
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+        let how = match atomic {
+            Some(callee) => format!("`{}` writes to `{}`", callee, &text[access.clone()]),
+            None => format!("`{}` is {}", &text[access.clone()], what),
+        };
+        let fix = match space {
+            ReadOnly::Uniform => "uniform buffers are read-only to shaders",
+            ReadOnly::Storage => "declare it `var<storage, read_write>` to write to it",
+        };
+        let message = format!(
+            "{}, but `{}` is a `{}`: {}",
+            how,
+            decl.name,
+            space.declared(),
+            fix
+        );
+        diags.push(diagnostic(uri, text, source, access, message, decl));
+    }
+    diags
+}
+
+fn diagnostic(
+    uri: &Url,
+    text: &str,
+    source: &str,
+    access: Range<usize>,
+    message: String,
+    decl: &Declaration,
+) -> Diagnostic {
+    Diagnostic {
+        range: byte_range_to_range(text, access),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String("access-mode".to_string())),
+        source: Some(source.to_string()),
+        message,
+        related_information: Some(vec![DiagnosticRelatedInformation {
+            location: Location {
+                uri: uri.clone(),
+                range: byte_range_to_range(text, decl.selection.clone()),
+            },
+            message: format!("`{}` is declared here", decl.name),
+        }]),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn writes(text: &str) -> Vec<Diagnostic> {
+        let uri = Url::parse("file:///test.wgsl").unwrap();
+        let index = SourceIndex::new(text);
+        check(&uri, text, &index, "test")
+    }
+
+    #[test]
+    fn flags_writes_to_read_only_buffers() {
+        let diags = writes(
+            "struct Data {\n    count: u32,\n}\n\
+             @group(0) @binding(0) var<storage, read> data: Data;\n\
+             fn main() {\n    data.count += 1u;\n}\n",
+        );
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert!(diags[0].message.starts_with("`data.count` is assigned to"));
+        assert_eq!(diags[0].range.start, lsp_types::Position::new(5, 4));
+        assert_eq!(diags[0].range.end, lsp_types::Position::new(5, 14));
+    }
+
+    #[test]
+    fn accepts_reads_and_writable_buffers() {
+        let diags = writes(
+            "@group(0) @binding(0) var<uniform> scale: f32;\n\
+             @group(0) @binding(1) var<storage, read_write> total: f32;\n\
+             fn main() {\n    total = total * scale;\n}\n",
+        );
+        assert!(diags.is_empty(), "{:?}", diags);
+    }
+}
@@ -0,0 +1,121 @@
+//! Dry runs of naga's backends, to catch shaders that validate but can't be translated
+//! for a particular target.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use naga::back::{glsl, hlsl, msl, spv};
+use naga::valid::ModuleInfo;
+use naga::Module;
+
+use crate::diagnostics::catch_panic;
+
+/// The backends that can be named in the `backends` setting.
+pub const BACKENDS: &[&str] = &["spv", "glsl", "hlsl", "msl"];
+
+/// Translates the module with each of `backends`, reporting every error as a warning
+/// naming the backend. The backends don't keep track of spans, so the warnings are put at
+/// the start of the document.
+pub fn check(
+    module: &Module,
+    info: &ModuleInfo,
+    backends: &[String],
+    source: &str,
+) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    for backend in backends {
+        let errors = match catch_panic(|| translate(module, info, backend)) {
+            Some(Some(errors)) => errors,
+            Some(None) => {
+                log::warn!(
+                    "unknown backend {}, expected one of {:?}",
+                    backend,
+                    BACKENDS
+                );
+                continue;
+            }
+            // the backends assert on some constructs they don't support
+            None => vec!["unsupported shader".to_string()],
+        };
+        diags.extend(errors.into_iter().map(|error| Diagnostic {
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String("backend-error".to_string())),
+            source: Some(source.to_string()),
+            message: format!("{} backend: {}", backend.to_uppercase(), error),
+            ..Default::default()
+        }));
+    }
+    diags
+}
+
+/// The errors translating the module with `backend` gives, or `None` if there is no such
+/// backend.
+fn translate(module: &Module, info: &ModuleInfo, backend: &str) -> Option<Vec<String>> {
+    let mut errors = Vec::new();
+    match backend {
+        "spv" => {
+            if let Err(err) = spv::write_vec(module, info, &spv::Options::default(), None) {
+                errors.push(err.to_string());
+            }
+        }
+        "glsl" => {
+            // the GLSL backend writes one entry point at a time
+            for entry_point in &module.entry_points {
+                let pipeline_options = glsl::PipelineOptions {
+                    shader_stage: entry_point.stage,
+                    entry_point: entry_point.name.clone(),
+                    multiview: None,
+                };
+                let mut source = String::new();
+                let res = glsl::Writer::new(
+                    &mut source,
+                    module,
+                    info,
+                    &glsl::Options::default(),
+                    &pipeline_options,
+                    naga::proc::BoundsCheckPolicies::default(),
+                )
+                .and_then(|mut writer| writer.write());
+                if let Err(err) = res {
+                    errors.push(format!("entry point `{}`: {}", entry_point.name, err));
+                }
+            }
+        }
+        "hlsl" => {
+            let mut source = String::new();
+            match hlsl::Writer::new(&mut source, &hlsl::Options::default()).write(module, info) {
+                Ok(reflection) => {
+                    errors.extend(entry_point_errors(module, reflection.entry_point_names))
+                }
+                Err(err) => errors.push(err.to_string()),
+            }
+        }
+        "msl" => {
+            let options = msl::Options::default();
+            let pipeline_options = msl::PipelineOptions::default();
+            match msl::write_string(module, info, &options, &pipeline_options) {
+                Ok((_, translation)) => {
+                    errors.extend(entry_point_errors(module, translation.entry_point_names))
+                }
+                Err(err) => errors.push(err.to_string()),
+            }
+        }
+        _ => return None,
+    }
+    Some(errors)
+}
+
+/// The errors of the entry points the backend couldn't translate, as the HLSL and MSL
+/// backends still write the rest.
+fn entry_point_errors<'a, E: std::fmt::Display + 'a>(
+    module: &'a Module,
+    names: Vec<Result<String, E>>,
+) -> impl Iterator<Item = String> + 'a {
+    module
+        .entry_points
+        .iter()
+        .zip(names)
+        .filter_map(|(entry_point, name)| {
+            name.err()
+                .map(|err| format!("entry point `{}`: {}", entry_point.name, err))
+        })
+}
@@ -0,0 +1,73 @@
+//! Checks on the resource bindings of each entry point, which the validator leaves to
+//! pipeline creation.
+
+use std::collections::{HashMap, HashSet};
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, NumberOrString, Url,
+};
+
+use naga::valid::ModuleInfo;
+use naga::{GlobalVariable, Handle, Module, ResourceBinding};
+
+use crate::includes::SourceMap;
+
+/// Reports every pair of distinct globals that share a `@group` and `@binding` while
+/// both being used by the same entry point. Globals reached by different entry points
+/// may share one, as they end up in different pipelines. Each diagnostic comes with the
+/// file it is in, which may be an included one.
+pub fn binding_collisions(
+    uri: &Url,
+    text: &str,
+    map: &SourceMap,
+    module: &Module,
+    info: &ModuleInfo,
+    severity: DiagnosticSeverity,
+    source: &str,
+) -> Vec<(Url, Diagnostic)> {
+    let mut reported = HashSet::new();
+    let mut diags = Vec::new();
+    for (index, entry_point) in module.entry_points.iter().enumerate() {
+        let entry_info = info.get_entry_point(index);
+        let mut bound: HashMap<&ResourceBinding, Handle<naga::GlobalVariable>> = HashMap::new();
+        for (handle, global) in module.global_variables.iter() {
+            let binding = match global.binding {
+                Some(ref binding) if !entry_info[handle].is_empty() => binding,
+                _ => continue,
+            };
+            let first = *bound.entry(binding).or_insert(handle);
+            if first == handle || !reported.insert((first, handle)) {
+                continue;
+            }
+            let name = |handle: Handle<GlobalVariable>| {
+                module.global_variables[handle]
+                    .name
+                    .as_deref()
+                    .unwrap_or("<unnamed>")
+            };
+            let locate = |handle| map.locate(uri, text, module.global_variables.get_span(handle));
+            let location = locate(handle);
+            let diag = Diagnostic {
+                range: location.range,
+                severity: Some(severity),
+                code: Some(NumberOrString::String("binding-collision".to_string())),
+                source: Some(source.to_string()),
+                message: format!(
+                    "`{}` and `{}` are both bound to @group({}) @binding({}) in entry point `{}`",
+                    name(first),
+                    name(handle),
+                    binding.group,
+                    binding.binding,
+                    entry_point.name
+                ),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: locate(first),
+                    message: format!("`{}` is bound here", name(first)),
+                }]),
+                ..Default::default()
+            };
+            diags.push((location.uri, diag));
+        }
+    }
+    diags
+}
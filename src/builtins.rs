@@ -0,0 +1,345 @@
+//! The WGSL built-in functions and attributes, as listed in the WGSL specification.
+//!
+//! Signatures use the spec's shorthand: `T` is a scalar or vector of the types noted in
+//! the doc, `vecN<T>` a vector of any size.
+
+pub struct Builtin {
+    pub name: &'static str,
+    /// Every overload, as `name(params) -> result`.
+    pub signatures: &'static [&'static str],
+    pub doc: &'static str,
+}
+
+pub fn find(name: &str) -> Option<&'static Builtin> {
+    BUILTIN_FUNCTIONS
+        .iter()
+        .find(|builtin| builtin.name == name)
+}
+
+/// Looks up an attribute by its name without the `@`.
+pub fn find_attribute(name: &str) -> Option<&'static Builtin> {
+    ATTRIBUTES.iter().find(|attribute| attribute.name == name)
+}
+
+/// The signatures and documentation of a builtin as Markdown, for hovers.
+pub fn markdown(builtin: &Builtin) -> String {
+    format!(
+        "```wgsl\n{}\n```\n{}",
+        builtin.signatures.join("\n"),
+        builtin.doc
+    )
+}
+
+/// Splits a signature into its parameters, such as `["e1: T", "e2: T"]` for
+/// `max(e1: T, e2: T) -> T`.
+pub fn parameters(signature: &str) -> Vec<&str> {
+    let open = match signature.find('(') {
+        Some(open) => open + 1,
+        None => return Vec::new(),
+    };
+    let mut params = Vec::new();
+    let mut depth = 0;
+    let mut start = open;
+    for (i, c) in signature[open..].char_indices() {
+        let i = open + i;
+        match c {
+            '<' | '(' => depth += 1,
+            '>' if depth > 0 => depth -= 1,
+            ')' if depth == 0 => {
+                if !signature[start..i].trim().is_empty() {
+                    params.push(signature[start..i].trim());
+                }
+                break;
+            }
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(signature[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    params
+}
+
+/// The result type of a signature, such as `T` for `max(e1: T, e2: T) -> T`.
+pub fn return_type(signature: &str) -> Option<&str> {
+    signature.rsplit_once("->").map(|(_, ty)| ty.trim())
+}
+
+macro_rules! builtins {
+    ($($name:literal [$($signature:literal),+ $(,)?] $doc:literal,)*) => {
+        &[$(Builtin { name: $name, signatures: &[$($signature),+], doc: $doc },)*]
+    };
+}
+
+pub const BUILTIN_FUNCTIONS: &[Builtin] = builtins! {
+    // logical
+    "all" ["all(e: vecN<bool>) -> bool", "all(e: bool) -> bool"]
+        "Returns true if each component of `e` is true.",
+    "any" ["any(e: vecN<bool>) -> bool", "any(e: bool) -> bool"]
+        "Returns true if any component of `e` is true.",
+    "select" ["select(f: T, t: T, cond: bool) -> T", "select(f: vecN<T>, t: vecN<T>, cond: vecN<bool>) -> vecN<T>"]
+        "Returns `t` when `cond` is true, and `f` otherwise. The vector form selects component-wise.",
+    // arrays
+    "arrayLength" ["arrayLength(p: ptr<storage, array<E>, A>) -> u32"]
+        "Returns the number of elements in the runtime-sized array.",
+    // numeric
+    "abs" ["abs(e: T) -> T"]
+        "The absolute value of `e`, component-wise for vectors.",
+    "acos" ["acos(e: T) -> T"]
+        "The arc cosine of `e`, in radians.",
+    "acosh" ["acosh(e: T) -> T"]
+        "The inverse hyperbolic cosine of `e`.",
+    "asin" ["asin(e: T) -> T"]
+        "The arc sine of `e`, in radians.",
+    "asinh" ["asinh(e: T) -> T"]
+        "The inverse hyperbolic sine of `e`.",
+    "atan" ["atan(e: T) -> T"]
+        "The arc tangent of `e`, in radians.",
+    "atanh" ["atanh(e: T) -> T"]
+        "The inverse hyperbolic tangent of `e`.",
+    "atan2" ["atan2(y: T, x: T) -> T"]
+        "The arc tangent of `y / x`, using the signs of both to pick the quadrant.",
+    "ceil" ["ceil(e: T) -> T"]
+        "Rounds `e` up to the nearest whole number.",
+    "clamp" ["clamp(e: T, low: T, high: T) -> T"]
+        "Restricts `e` to the range `[low, high]`.",
+    "cos" ["cos(e: T) -> T"]
+        "The cosine of `e`, in radians.",
+    "cosh" ["cosh(e: T) -> T"]
+        "The hyperbolic cosine of `e`.",
+    "countLeadingZeros" ["countLeadingZeros(e: T) -> T"]
+        "The number of consecutive zero bits starting from the most significant bit.",
+    "countOneBits" ["countOneBits(e: T) -> T"]
+        "The number of one bits in `e`.",
+    "countTrailingZeros" ["countTrailingZeros(e: T) -> T"]
+        "The number of consecutive zero bits starting from the least significant bit.",
+    "cross" ["cross(e1: vec3<T>, e2: vec3<T>) -> vec3<T>"]
+        "The cross product of `e1` and `e2`.",
+    "degrees" ["degrees(e: T) -> T"]
+        "Converts radians to degrees.",
+    "determinant" ["determinant(e: matCxC<T>) -> T"]
+        "The determinant of a square matrix.",
+    "distance" ["distance(e1: T, e2: T) -> S"]
+        "The distance between `e1` and `e2`, `length(e1 - e2)`.",
+    "dot" ["dot(e1: vecN<T>, e2: vecN<T>) -> T"]
+        "The dot product of `e1` and `e2`.",
+    "exp" ["exp(e: T) -> T"]
+        "The natural exponentiation of `e`.",
+    "exp2" ["exp2(e: T) -> T"]
+        "2 raised to the power `e`.",
+    "extractBits" ["extractBits(e: T, offset: u32, count: u32) -> T"]
+        "Reads `count` bits of `e` starting at bit `offset`.",
+    "faceForward" ["faceForward(e1: T, e2: T, e3: T) -> T"]
+        "Returns `e1` if `dot(e2, e3)` is negative, and `-e1` otherwise.",
+    "firstLeadingBit" ["firstLeadingBit(e: T) -> T"]
+        "The position of the most significant bit that differs from the sign bit.",
+    "firstTrailingBit" ["firstTrailingBit(e: T) -> T"]
+        "The position of the least significant one bit.",
+    "floor" ["floor(e: T) -> T"]
+        "Rounds `e` down to the nearest whole number.",
+    "fma" ["fma(e1: T, e2: T, e3: T) -> T"]
+        "Computes `e1 * e2 + e3`.",
+    "fract" ["fract(e: T) -> T"]
+        "The fractional part of `e`, `e - floor(e)`.",
+    "frexp" ["frexp(e: T) -> __frexp_result"]
+        "Splits `e` into a significand in `[0.5, 1.0)` and an integral exponent.",
+    "insertBits" ["insertBits(e: T, newbits: T, offset: u32, count: u32) -> T"]
+        "Replaces `count` bits of `e` starting at bit `offset` with the low bits of `newbits`.",
+    "inverseSqrt" ["inverseSqrt(e: T) -> T"]
+        "The reciprocal of `sqrt(e)`.",
+    "ldexp" ["ldexp(e1: T, e2: I) -> T"]
+        "Computes `e1 * 2^e2`.",
+    "length" ["length(e: T) -> S"]
+        "The length of the vector `e`.",
+    "log" ["log(e: T) -> T"]
+        "The natural logarithm of `e`.",
+    "log2" ["log2(e: T) -> T"]
+        "The base-2 logarithm of `e`.",
+    "max" ["max(e1: T, e2: T) -> T"]
+        "The larger of `e1` and `e2`, component-wise for vectors.",
+    "min" ["min(e1: T, e2: T) -> T"]
+        "The smaller of `e1` and `e2`, component-wise for vectors.",
+    "mix" ["mix(e1: T, e2: T, e3: T) -> T", "mix(e1: vecN<T>, e2: vecN<T>, e3: T) -> vecN<T>"]
+        "Linearly blends `e1` and `e2` by `e3`, `e1 * (1 - e3) + e2 * e3`.",
+    "modf" ["modf(e: T) -> __modf_result"]
+        "Splits `e` into its fractional and whole parts.",
+    "normalize" ["normalize(e: vecN<T>) -> vecN<T>"]
+        "A unit vector in the same direction as `e`.",
+    "pow" ["pow(e1: T, e2: T) -> T"]
+        "`e1` raised to the power `e2`.",
+    "quantizeToF16" ["quantizeToF16(e: T) -> T"]
+        "Rounds `e` as though it were stored as an f16 and read back.",
+    "radians" ["radians(e: T) -> T"]
+        "Converts degrees to radians.",
+    "reflect" ["reflect(e1: T, e2: T) -> T"]
+        "Reflects the incident vector `e1` about the surface normal `e2`.",
+    "refract" ["refract(e1: T, e2: T, e3: S) -> T"]
+        "Refracts the incident vector `e1` through the surface normal `e2` with ratio `e3`.",
+    "reverseBits" ["reverseBits(e: T) -> T"]
+        "Reverses the bits of `e`.",
+    "round" ["round(e: T) -> T"]
+        "Rounds `e` to the nearest whole number, ties to even.",
+    "saturate" ["saturate(e: T) -> T"]
+        "Clamps `e` to `[0.0, 1.0]`.",
+    "sign" ["sign(e: T) -> T"]
+        "-1, 0 or 1 depending on the sign of `e`.",
+    "sin" ["sin(e: T) -> T"]
+        "The sine of `e`, in radians.",
+    "sinh" ["sinh(e: T) -> T"]
+        "The hyperbolic sine of `e`.",
+    "smoothstep" ["smoothstep(low: T, high: T, x: T) -> T"]
+        "Smooth Hermite interpolation between 0 and 1 as `x` goes from `low` to `high`.",
+    "sqrt" ["sqrt(e: T) -> T"]
+        "The square root of `e`.",
+    "step" ["step(edge: T, x: T) -> T"]
+        "1.0 if `edge <= x`, and 0.0 otherwise.",
+    "tan" ["tan(e: T) -> T"]
+        "The tangent of `e`, in radians.",
+    "tanh" ["tanh(e: T) -> T"]
+        "The hyperbolic tangent of `e`.",
+    "transpose" ["transpose(e: matRxC<T>) -> matCxR<T>"]
+        "The transpose of the matrix `e`.",
+    "trunc" ["trunc(e: T) -> T"]
+        "Rounds `e` towards zero.",
+    // derivatives
+    "dpdx" ["dpdx(e: T) -> T"]
+        "The partial derivative of `e` with respect to window x. Fragment shaders only.",
+    "dpdxCoarse" ["dpdxCoarse(e: T) -> T"]
+        "A coarse partial derivative of `e` with respect to window x. Fragment shaders only.",
+    "dpdxFine" ["dpdxFine(e: T) -> T"]
+        "A fine partial derivative of `e` with respect to window x. Fragment shaders only.",
+    "dpdy" ["dpdy(e: T) -> T"]
+        "The partial derivative of `e` with respect to window y. Fragment shaders only.",
+    "dpdyCoarse" ["dpdyCoarse(e: T) -> T"]
+        "A coarse partial derivative of `e` with respect to window y. Fragment shaders only.",
+    "dpdyFine" ["dpdyFine(e: T) -> T"]
+        "A fine partial derivative of `e` with respect to window y. Fragment shaders only.",
+    "fwidth" ["fwidth(e: T) -> T"]
+        "`abs(dpdx(e)) + abs(dpdy(e))`. Fragment shaders only.",
+    "fwidthCoarse" ["fwidthCoarse(e: T) -> T"]
+        "`abs(dpdxCoarse(e)) + abs(dpdyCoarse(e))`. Fragment shaders only.",
+    "fwidthFine" ["fwidthFine(e: T) -> T"]
+        "`abs(dpdxFine(e)) + abs(dpdyFine(e))`. Fragment shaders only.",
+    // textures
+    "textureDimensions" ["textureDimensions(t: T) -> vecN<u32>", "textureDimensions(t: T, level: u32) -> vecN<u32>"]
+        "The dimensions of a texture, or of one of its mip levels.",
+    "textureGather" ["textureGather(component: u32, t: texture_2d<T>, s: sampler, coords: vec2<f32>) -> vec4<T>", "textureGather(t: texture_depth_2d, s: sampler, coords: vec2<f32>) -> vec4<f32>"]
+        "Gathers one component from the four texels that would be used for bilinear filtering.",
+    "textureGatherCompare" ["textureGatherCompare(t: texture_depth_2d, s: sampler_comparison, coords: vec2<f32>, depth_ref: f32) -> vec4<f32>"]
+        "Compares `depth_ref` against the four texels that would be used for bilinear filtering.",
+    "textureLoad" ["textureLoad(t: texture_2d<T>, coords: vec2<C>, level: C) -> vec4<T>", "textureLoad(t: texture_storage_2d<F, read>, coords: vec2<C>) -> vec4<T>"]
+        "Reads a single texel without sampling or filtering.",
+    "textureNumLayers" ["textureNumLayers(t: T) -> u32"]
+        "The number of layers of an array texture.",
+    "textureNumLevels" ["textureNumLevels(t: T) -> u32"]
+        "The number of mip levels of a texture.",
+    "textureNumSamples" ["textureNumSamples(t: T) -> u32"]
+        "The number of samples per texel of a multisampled texture.",
+    "textureSample" ["textureSample(t: texture_2d<f32>, s: sampler, coords: vec2<f32>) -> vec4<f32>", "textureSample(t: texture_depth_2d, s: sampler, coords: vec2<f32>) -> f32"]
+        "Samples a texture. Fragment shaders only, as it relies on implicit derivatives.",
+    "textureSampleBias" ["textureSampleBias(t: texture_2d<f32>, s: sampler, coords: vec2<f32>, bias: f32) -> vec4<f32>"]
+        "Samples a texture with a bias added to the mip level. Fragment shaders only.",
+    "textureSampleCompare" ["textureSampleCompare(t: texture_depth_2d, s: sampler_comparison, coords: vec2<f32>, depth_ref: f32) -> f32"]
+        "Samples a depth texture and compares the result against `depth_ref`. Fragment shaders only.",
+    "textureSampleCompareLevel" ["textureSampleCompareLevel(t: texture_depth_2d, s: sampler_comparison, coords: vec2<f32>, depth_ref: f32) -> f32"]
+        "Like `textureSampleCompare`, but always uses mip level 0. Usable in any stage.",
+    "textureSampleGrad" ["textureSampleGrad(t: texture_2d<f32>, s: sampler, coords: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32>"]
+        "Samples a texture using explicit gradients.",
+    "textureSampleLevel" ["textureSampleLevel(t: texture_2d<f32>, s: sampler, coords: vec2<f32>, level: f32) -> vec4<f32>", "textureSampleLevel(t: texture_depth_2d, s: sampler, coords: vec2<f32>, level: i32) -> f32"]
+        "Samples a texture at an explicit mip level. Usable in any stage.",
+    "textureSampleBaseClampToEdge" ["textureSampleBaseClampToEdge(t: texture_2d<f32>, s: sampler, coords: vec2<f32>) -> vec4<f32>"]
+        "Samples mip level 0 with coordinates clamped to the edge of the texture.",
+    "textureStore" ["textureStore(t: texture_storage_2d<F, write>, coords: vec2<C>, value: vec4<T>)"]
+        "Writes a single texel to a storage texture.",
+    // atomics
+    "atomicLoad" ["atomicLoad(atomic_ptr: ptr<AS, atomic<T>, read_write>) -> T"]
+        "Atomically reads the value.",
+    "atomicStore" ["atomicStore(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T)"]
+        "Atomically writes `v`.",
+    "atomicAdd" ["atomicAdd(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T) -> T"]
+        "Atomically adds `v`, returning the original value.",
+    "atomicSub" ["atomicSub(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T) -> T"]
+        "Atomically subtracts `v`, returning the original value.",
+    "atomicMax" ["atomicMax(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T) -> T"]
+        "Atomically stores the maximum with `v`, returning the original value.",
+    "atomicMin" ["atomicMin(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T) -> T"]
+        "Atomically stores the minimum with `v`, returning the original value.",
+    "atomicAnd" ["atomicAnd(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T) -> T"]
+        "Atomic bitwise and with `v`, returning the original value.",
+    "atomicOr" ["atomicOr(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T) -> T"]
+        "Atomic bitwise or with `v`, returning the original value.",
+    "atomicXor" ["atomicXor(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T) -> T"]
+        "Atomic bitwise xor with `v`, returning the original value.",
+    "atomicExchange" ["atomicExchange(atomic_ptr: ptr<AS, atomic<T>, read_write>, v: T) -> T"]
+        "Atomically replaces the value with `v`, returning the original value.",
+    "atomicCompareExchangeWeak" ["atomicCompareExchangeWeak(atomic_ptr: ptr<AS, atomic<T>, read_write>, cmp: T, v: T) -> __atomic_compare_exchange_result<T>"]
+        "Stores `v` if the value equals `cmp`. May spuriously fail.",
+    // packing
+    "pack4x8snorm" ["pack4x8snorm(e: vec4<f32>) -> u32"]
+        "Packs four normalized floats into 8-bit signed integers.",
+    "pack4x8unorm" ["pack4x8unorm(e: vec4<f32>) -> u32"]
+        "Packs four normalized floats into 8-bit unsigned integers.",
+    "pack2x16snorm" ["pack2x16snorm(e: vec2<f32>) -> u32"]
+        "Packs two normalized floats into 16-bit signed integers.",
+    "pack2x16unorm" ["pack2x16unorm(e: vec2<f32>) -> u32"]
+        "Packs two normalized floats into 16-bit unsigned integers.",
+    "pack2x16float" ["pack2x16float(e: vec2<f32>) -> u32"]
+        "Packs two floats as half-precision floats.",
+    "unpack4x8snorm" ["unpack4x8snorm(e: u32) -> vec4<f32>"]
+        "Unpacks four 8-bit signed integers into normalized floats.",
+    "unpack4x8unorm" ["unpack4x8unorm(e: u32) -> vec4<f32>"]
+        "Unpacks four 8-bit unsigned integers into normalized floats.",
+    "unpack2x16snorm" ["unpack2x16snorm(e: u32) -> vec2<f32>"]
+        "Unpacks two 16-bit signed integers into normalized floats.",
+    "unpack2x16unorm" ["unpack2x16unorm(e: u32) -> vec2<f32>"]
+        "Unpacks two 16-bit unsigned integers into normalized floats.",
+    "unpack2x16float" ["unpack2x16float(e: u32) -> vec2<f32>"]
+        "Unpacks two half-precision floats.",
+    // synchronization
+    "storageBarrier" ["storageBarrier()"]
+        "Orders memory accesses to storage buffers within the workgroup. Compute shaders only.",
+    "workgroupBarrier" ["workgroupBarrier()"]
+        "Waits for every invocation in the workgroup and orders workgroup memory accesses. Compute shaders only.",
+    "workgroupUniformLoad" ["workgroupUniformLoad(p: ptr<workgroup, T>) -> T"]
+        "Loads a workgroup variable as a uniform value for every invocation. Compute shaders only.",
+    // bit reinterpretation
+    "bitcast" ["bitcast<T>(e: S) -> T"]
+        "Reinterprets the bits of `e` as type `T`.",
+};
+
+pub const ATTRIBUTES: &[Builtin] = builtins! {
+    "align" ["@align(n: u32)"]
+        "Aligns the struct member to a multiple of `n` bytes, which must be a power of two.",
+    "binding" ["@binding(n: u32)"]
+        "The binding number of a resource within its bind group.",
+    "builtin" ["@builtin(name)"]
+        "Passes a built-in value such as `position`, `vertex_index` or `global_invocation_id` in or out of an entry point.",
+    "compute" ["@compute"]
+        "Declares a compute shader entry point. It also needs a `@workgroup_size`.",
+    "const" ["@const"]
+        "Marks a built-in function as usable in constant expressions. Only for the standard library.",
+    "diagnostic" ["@diagnostic(severity, rule)"]
+        "Sets the severity of a diagnostic rule, such as `derivative_uniformity`, within the function.",
+    "fragment" ["@fragment"]
+        "Declares a fragment shader entry point.",
+    "group" ["@group(n: u32)"]
+        "The bind group a resource belongs to.",
+    "id" ["@id(n: u32)"]
+        "The numeric id of a pipeline-overridable constant.",
+    "interpolate" ["@interpolate(type)", "@interpolate(type, sampling)"]
+        "How a user-defined input is interpolated: `perspective`, `linear` or `flat`, sampled at the `center`, `centroid` or `sample`.",
+    "invariant" ["@invariant"]
+        "Makes the `position` output computed identically across shaders and pipelines doing the same computation.",
+    "location" ["@location(n: u32)"]
+        "The location of a user-defined input or output of an entry point.",
+    "must_use" ["@must_use"]
+        "Makes calling the function without using its result an error.",
+    "size" ["@size(n: u32)"]
+        "Reserves `n` bytes for the struct member, which must be at least the size of its type.",
+    "vertex" ["@vertex"]
+        "Declares a vertex shader entry point. It must return the `position` builtin.",
+    "workgroup_size" ["@workgroup_size(x: u32)", "@workgroup_size(x: u32, y: u32)", "@workgroup_size(x: u32, y: u32, z: u32)"]
+        "The size of the workgroups a compute shader runs in. Omitted dimensions are 1.",
+};
@@ -0,0 +1,126 @@
+//! The calls between the functions of a document, as written in the source.
+
+use lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Position, SymbolKind,
+    Url,
+};
+
+use crate::document::Document;
+use crate::position::{byte_range_to_range, position_to_byte_offset};
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// The function under the cursor, at its declaration or at a call.
+pub fn prepare(uri: &Url, document: &Document, position: Position) -> Vec<CallHierarchyItem> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    index
+        .declaration_at(offset)
+        .filter(|&decl| index.declarations[decl].kind == DeclarationKind::Function)
+        .map(|decl| hierarchy_item(uri, text, &index, decl))
+        .into_iter()
+        .collect()
+}
+
+/// The functions calling the function of `item`, each with the ranges of its calls.
+pub fn incoming_calls(
+    document: &Document,
+    item: &CallHierarchyItem,
+) -> Vec<CallHierarchyIncomingCall> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let callee = match find_function(&index, &item.name) {
+        Some(callee) => callee,
+        None => return Vec::new(),
+    };
+    let mut calls: Vec<CallHierarchyIncomingCall> = Vec::new();
+    for reference in &index.references {
+        if reference.target != Some(callee) {
+            continue;
+        }
+        let caller = match enclosing_function(&index, reference.span.start) {
+            Some(caller) => caller,
+            None => continue,
+        };
+        let range = byte_range_to_range(text, reference.span.clone());
+        let from = hierarchy_item(&item.uri, text, &index, caller);
+        match calls.iter_mut().find(|call| call.from.name == from.name) {
+            Some(call) => call.from_ranges.push(range),
+            None => calls.push(CallHierarchyIncomingCall {
+                from,
+                from_ranges: vec![range],
+            }),
+        }
+    }
+    calls
+}
+
+/// The functions the function of `item` calls, each with the ranges of the calls within
+/// it. Builtins aren't included, as they have nowhere to go.
+pub fn outgoing_calls(
+    document: &Document,
+    item: &CallHierarchyItem,
+) -> Vec<CallHierarchyOutgoingCall> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let body = match find_function(&index, &item.name)
+        .and_then(|caller| index.declarations[caller].body.clone())
+    {
+        Some(body) => body,
+        None => return Vec::new(),
+    };
+    let mut calls: Vec<CallHierarchyOutgoingCall> = Vec::new();
+    for reference in &index.references {
+        let callee = match reference.target {
+            Some(target)
+                if body.contains(&reference.span.start)
+                    && index.declarations[target].kind == DeclarationKind::Function =>
+            {
+                target
+            }
+            _ => continue,
+        };
+        let range = byte_range_to_range(text, reference.span.clone());
+        let to = hierarchy_item(&item.uri, text, &index, callee);
+        match calls.iter_mut().find(|call| call.to.name == to.name) {
+            Some(call) => call.from_ranges.push(range),
+            None => calls.push(CallHierarchyOutgoingCall {
+                to,
+                from_ranges: vec![range],
+            }),
+        }
+    }
+    calls
+}
+
+fn find_function(index: &SourceIndex, name: &str) -> Option<usize> {
+    index
+        .declarations
+        .iter()
+        .position(|decl| decl.kind == DeclarationKind::Function && decl.name == name)
+}
+
+/// The function whose body contains `offset`.
+fn enclosing_function(index: &SourceIndex, offset: usize) -> Option<usize> {
+    index.declarations.iter().position(|decl| {
+        decl.kind == DeclarationKind::Function
+            && decl
+                .body
+                .as_ref()
+                .is_some_and(|body| body.contains(&offset))
+    })
+}
+
+fn hierarchy_item(uri: &Url, text: &str, index: &SourceIndex, decl: usize) -> CallHierarchyItem {
+    let declaration = &index.declarations[decl];
+    CallHierarchyItem {
+        name: declaration.name.clone(),
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: declaration.ty.clone(),
+        uri: uri.clone(),
+        range: byte_range_to_range(text, declaration.full.clone()),
+        selection_range: byte_range_to_range(text, declaration.selection.clone()),
+        data: None,
+    }
+}
@@ -0,0 +1,57 @@
+//! Command-line arguments.
+
+pub const USAGE: &str = "\
+usage: naga-lsp [--stdio | --socket <port> | --listen <port>]
+
+    --stdio           talk to the client over stdin and stdout (the default)
+    --socket <port>   connect to a client listening on 127.0.0.1:<port>
+    --listen <port>   wait for a client to connect to 127.0.0.1:<port>
+    -h, --help        print this message";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    Stdio,
+    Connect(u16),
+    Listen(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Run(Transport),
+    Help,
+}
+
+/// Parses the arguments, without the program name. Flags taking a value accept it both
+/// as the next argument and after `=`.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut transport = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let parsed = match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--stdio" => Transport::Stdio,
+            "--socket" | "--listen" => {
+                let value = value
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{} expects a port", flag))?;
+                let port = value
+                    .parse()
+                    .map_err(|_| format!("invalid port {} for {}", value, flag))?;
+                if flag == "--socket" {
+                    Transport::Connect(port)
+                } else {
+                    Transport::Listen(port)
+                }
+            }
+            _ => return Err(format!("unknown argument {}", flag)),
+        };
+        if transport.replace(parsed).is_some() {
+            return Err("only one transport can be given".to_string());
+        }
+    }
+    Ok(Command::Run(transport.unwrap_or(Transport::Stdio)))
+}
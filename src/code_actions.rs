@@ -0,0 +1,347 @@
+//! Quick fixes for diagnostics, matched on the error message, and refactorings of the
+//! selection.
+//!
+//! New fixes are added to `FIXERS`.
+
+use std::collections::HashMap;
+
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, Range, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::builtins::BUILTIN_FUNCTIONS;
+use crate::document::Document;
+use crate::position::{byte_offset_to_position, position_to_byte_offset};
+use crate::syntax::{DeclarationKind, SourceIndex, TokenKind};
+
+struct Fixer {
+    /// A fragment of the messages this fixer handles.
+    message: &'static str,
+    fix: fn(&Context, &Diagnostic) -> Option<Fix>,
+}
+
+struct Fix {
+    title: String,
+    edit: TextEdit,
+}
+
+struct Context<'a> {
+    text: &'a str,
+    index: SourceIndex,
+}
+
+const FIXERS: &[Fixer] = &[
+    Fixer {
+        message: "expected ';'",
+        fix: insert_semicolon,
+    },
+    Fixer {
+        message: "unknown type",
+        fix: correct_type,
+    },
+    Fixer {
+        message: "no definition in scope for identifier",
+        fix: correct_identifier,
+    },
+];
+
+pub fn code_actions(
+    uri: &Url,
+    document: &Document,
+    range: Range,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeActionOrCommand> {
+    let context = Context {
+        text: &document.text,
+        index: SourceIndex::new(&document.text),
+    };
+    let fixes = diagnostics.iter().flat_map(|diagnostic| {
+        let context = &context;
+        FIXERS
+            .iter()
+            .filter(move |fixer| diagnostic.message.contains(fixer.message))
+            .filter_map(move |fixer| (fixer.fix)(context, diagnostic))
+            .map(move |fix| {
+                let mut changes = HashMap::new();
+                changes.insert(uri.clone(), vec![fix.edit]);
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title: fix.title,
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![diagnostic.clone()]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(changes),
+                        ..Default::default()
+                    }),
+                    is_preferred: Some(true),
+                    ..Default::default()
+                })
+            })
+    });
+    let mut actions: Vec<CodeActionOrCommand> = fixes.collect();
+    actions.extend(extract_let(uri, &context, range));
+    actions
+}
+
+/// Binds the selected expression to a `let` right before the statement it is in, and
+/// uses that instead.
+///
+/// Nothing is offered for selections that aren't a whole expression, that are in a loop
+/// header or an `else if` condition, which run more than once or not at all, or that use
+/// a local declared after where the `let` would go.
+fn extract_let(uri: &Url, context: &Context, range: Range) -> Option<CodeActionOrCommand> {
+    let text = context.text;
+    let index = &context.index;
+    let start = position_to_byte_offset(text, range.start);
+    let end = position_to_byte_offset(text, range.end);
+    let first = index.tokens.iter().position(|token| token.end > start)?;
+    let last = index.tokens.iter().rposition(|token| token.start < end)?;
+    // the selection has to cover whole tokens, give or take whitespace
+    let (expression_start, expression_end) =
+        (index.tokens.get(first)?.start, index.tokens[last].end);
+    if first > last || start > expression_start || expression_end > end {
+        return None;
+    }
+    if !text[start..expression_start].trim().is_empty()
+        || !text[expression_end..end].trim().is_empty()
+    {
+        return None;
+    }
+    let selected = &index.tokens[first..=last];
+    // brackets have to close within the selection, and statements can't be in it
+    let balanced = (first..=last).all(|i| match index.tokens[i].text(text) {
+        "(" | "[" => index.matching[i].is_some_and(|close| close <= last),
+        ")" | "]" => index.matching[i].is_some_and(|open| open >= first),
+        // `=` on its own assigns, unlike in `==`, `!=`, `<=` and `>=`
+        "=" => {
+            let touching = |other: usize, texts: &[&str]| {
+                index.tokens.get(other).is_some_and(|other_token| {
+                    let token = index.tokens[i];
+                    (other_token.end == token.start || other_token.start == token.end)
+                        && texts.contains(&other_token.text(text))
+                })
+            };
+            (i > 0 && touching(i - 1, &["=", "!", "<", ">"])) || touching(i + 1, &["="])
+        }
+        ";" | "{" | "}" | "let" | "var" | "const" => false,
+        _ => true,
+    });
+    if !balanced
+        || selected
+            .iter()
+            .all(|token| token.kind != TokenKind::Ident && token.kind != TokenKind::Number)
+    {
+        return None;
+    }
+    let function = index.declarations.iter().find(|decl| {
+        decl.kind == DeclarationKind::Function
+            && decl
+                .body
+                .as_ref()
+                .is_some_and(|body| body.start < expression_start && expression_end < body.end)
+    })?;
+
+    // the start of the statement, skipping over brackets and out of those the selection
+    // is in, such as the arguments of a call
+    let mut statement = first;
+    let mut inside = 0;
+    while statement > 0 {
+        let previous = statement - 1;
+        match index.tokens[previous].text(text) {
+            ")" | "]" => statement = index.matching[previous]?,
+            "(" | "[" => {
+                inside += 1;
+                statement = previous;
+            }
+            ";" if inside > 0 => statement = previous,
+            ";" | "{" | "}" => break,
+            _ => statement = previous,
+        }
+    }
+    let statement_token = index.tokens[statement];
+    if matches!(statement_token.text(text), "for" | "while" | "else") {
+        return None;
+    }
+    let insert_at = statement_token.start;
+    if insert_at <= function.body.as_ref()?.start {
+        return None;
+    }
+    // every local used has to be declared by then
+    let in_scope = index
+        .references
+        .iter()
+        .filter(|reference| {
+            expression_start <= reference.span.start && reference.span.end <= expression_end
+        })
+        .filter_map(|reference| reference.target)
+        .all(|target| {
+            let decl = &index.declarations[target];
+            !decl.kind.is_local() || decl.scope.start <= insert_at
+        });
+    if !in_scope {
+        return None;
+    }
+
+    // a name nothing in the document uses, so that it shadows nothing
+    let taken = |name: &str| {
+        index
+            .tokens
+            .iter()
+            .any(|token| token.kind == TokenKind::Ident && token.text(text) == name)
+    };
+    let name = std::iter::once("value".to_string())
+        .chain((1..).map(|n| format!("value{}", n)))
+        .find(|name| !taken(name))?;
+
+    let expression = &text[expression_start..expression_end];
+    let line_start = text[..insert_at]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let indent = &text[line_start..insert_at];
+    let (at, binding) = if indent.trim().is_empty() {
+        (
+            line_start,
+            format!("{}let {} = {};\n", indent, name, expression),
+        )
+    } else {
+        (insert_at, format!("let {} = {}; ", name, expression))
+    };
+    let at = byte_offset_to_position(text, at);
+    let edits = vec![
+        TextEdit {
+            range: Range { start: at, end: at },
+            new_text: binding,
+        },
+        TextEdit {
+            range: Range {
+                start: byte_offset_to_position(text, expression_start),
+                end: byte_offset_to_position(text, expression_end),
+            },
+            new_text: name.clone(),
+        },
+    ];
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), edits);
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Extract into `let {}`", name),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Inserts the missing `;` right after the token preceding the unexpected one.
+fn insert_semicolon(context: &Context, diagnostic: &Diagnostic) -> Option<Fix> {
+    let offset = position_to_byte_offset(context.text, diagnostic.range.start);
+    let previous = context.index.tokens[context.index.token_before(offset)?];
+    let position = byte_offset_to_position(context.text, previous.end);
+    Some(Fix {
+        title: "Insert `;`".to_string(),
+        edit: TextEdit {
+            range: Range {
+                start: position,
+                end: position,
+            },
+            new_text: ";".to_string(),
+        },
+    })
+}
+
+/// Replaces a misspelled type with the closest predeclared or user-defined type.
+fn correct_type(context: &Context, diagnostic: &Diagnostic) -> Option<Fix> {
+    let mut candidates = predeclared_types();
+    candidates.extend(
+        context
+            .index
+            .declarations
+            .iter()
+            .filter(|decl| matches!(decl.kind, DeclarationKind::Struct | DeclarationKind::Alias))
+            .map(|decl| decl.name.clone()),
+    );
+    replace_with_closest(context, diagnostic, candidates)
+}
+
+/// Replaces a misspelled name with the closest declaration in scope or builtin function.
+fn correct_identifier(context: &Context, diagnostic: &Diagnostic) -> Option<Fix> {
+    let offset = position_to_byte_offset(context.text, diagnostic.range.start);
+    let index = &context.index;
+    let mut candidates: Vec<String> = index
+        .declarations
+        .iter()
+        .filter(|decl| decl.kind != DeclarationKind::Member)
+        .filter(|decl| index.resolve(&decl.name, offset).is_some())
+        .map(|decl| decl.name.clone())
+        .collect();
+    candidates.extend(
+        BUILTIN_FUNCTIONS
+            .iter()
+            .map(|builtin| builtin.name.to_string()),
+    );
+    replace_with_closest(context, diagnostic, candidates)
+}
+
+fn replace_with_closest(
+    context: &Context,
+    diagnostic: &Diagnostic,
+    candidates: Vec<String>,
+) -> Option<Fix> {
+    let offset = position_to_byte_offset(context.text, diagnostic.range.start);
+    let token = context.index.ident_at(offset)?;
+    let name = token.text(context.text);
+    // allow roughly one typo per three characters, at most two
+    let max_distance = (name.len() / 3).clamp(1, 2);
+    let closest = candidates
+        .into_iter()
+        .filter(|candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, &candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)?
+        .1;
+    Some(Fix {
+        title: format!("Change to `{}`", closest),
+        edit: TextEdit {
+            range: Range {
+                start: byte_offset_to_position(context.text, token.start),
+                end: byte_offset_to_position(context.text, token.end),
+            },
+            new_text: closest,
+        },
+    })
+}
+
+/// The predeclared types worth suggesting, without the texture types.
+fn predeclared_types() -> Vec<String> {
+    let mut types: Vec<String> = ["bool", "i32", "u32", "f32", "f16", "array", "atomic", "ptr"]
+        .iter()
+        .map(|ty| ty.to_string())
+        .collect();
+    for n in 2..=4 {
+        types.push(format!("vec{}", n));
+        for suffix in &["i", "u", "f", "h"] {
+            types.push(format!("vec{}{}", n, suffix));
+        }
+        for m in 2..=4 {
+            types.push(format!("mat{}x{}", n, m));
+            types.push(format!("mat{}x{}f", n, m));
+        }
+    }
+    types
+}
+
+/// The Levenshtein distance between two names.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,48 @@
+use lsp_types::{CodeLens, Command, Url};
+
+use crate::commands::{stage_name, EXPORT_GLSL};
+use crate::document::Document;
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// A lens above every entry point showing its stage, which exports it to GLSL when
+/// clicked.
+pub fn code_lenses(uri: &Url, document: &Document) -> Vec<CodeLens> {
+    let module = match document.module {
+        Some(ref module) => module,
+        None => return Vec::new(),
+    };
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+
+    module
+        .entry_points
+        .iter()
+        .filter_map(|entry_point| {
+            let declaration = index.declarations.iter().find(|decl| {
+                decl.kind == DeclarationKind::Function && decl.name == entry_point.name
+            })?;
+            let stage = stage_name(entry_point.stage);
+            let mut title = format!("@{}", stage);
+            if entry_point.stage == naga::ShaderStage::Compute {
+                let [x, y, z] = entry_point.workgroup_size;
+                title.push_str(&format!(" @workgroup_size({}, {}, {})", x, y, z));
+            }
+            title.push_str(" \u{2014} export GLSL");
+
+            Some(CodeLens {
+                range: byte_range_to_range(text, declaration.selection.clone()),
+                command: Some(Command {
+                    title,
+                    command: EXPORT_GLSL.to_string(),
+                    arguments: Some(vec![
+                        serde_json::json!(uri),
+                        serde_json::json!(entry_point.name),
+                        serde_json::json!(stage),
+                    ]),
+                }),
+                data: None,
+            })
+        })
+        .collect()
+}
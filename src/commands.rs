@@ -0,0 +1,623 @@
+//! Commands run through `workspace/executeCommand`.
+
+use lsp_types::{ExecuteCommandParams, Location, Url};
+use serde_json::Value;
+
+use naga::valid::{Capabilities, ModuleInfo, ValidationFlags, Validator};
+use naga::ShaderStage;
+
+use crate::config::{Config, FormatStyle};
+use crate::diagnostics::{catch_panic, error_chain};
+use crate::document::{Document, DocumentStore, Language};
+use crate::formatting;
+use crate::overrides;
+use crate::position::byte_range_to_range;
+use crate::progress::Progress;
+use crate::reflection;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+pub const EXPORT_SPIRV: &str = "naga-lsp.exportSpirv";
+pub const DISASSEMBLE_SPIRV: &str = "naga-lsp.disassembleSpirv";
+pub const EXPORT_GLSL: &str = "naga-lsp.exportGlsl";
+pub const EXPORT_HLSL: &str = "naga-lsp.exportHlsl";
+pub const EXPORT_MSL: &str = "naga-lsp.exportMsl";
+pub const REFLECT: &str = "naga-lsp.reflect";
+pub const DUMP_IR: &str = "naga-lsp.dumpIr";
+pub const FORMAT: &str = "naga-lsp.format";
+pub const GOTO_ENTRY_POINT: &str = "naga-lsp.gotoEntryPoint";
+pub const OVERRIDES: &str = "naga-lsp.overrides";
+pub const STATS: &str = "naga-lsp.stats";
+pub const ROUNDTRIP: &str = "naga-lsp.roundtrip";
+
+/// Every command we advertise in `execute_command_provider`.
+pub const COMMANDS: &[&str] = &[
+    EXPORT_SPIRV,
+    DISASSEMBLE_SPIRV,
+    EXPORT_GLSL,
+    EXPORT_HLSL,
+    EXPORT_MSL,
+    REFLECT,
+    DUMP_IR,
+    FORMAT,
+    GOTO_ENTRY_POINT,
+    OVERRIDES,
+    STATS,
+    ROUNDTRIP,
+];
+
+/// The title of the progress shown while `command` runs.
+pub fn title(command: &str) -> &'static str {
+    match command {
+        EXPORT_SPIRV => "Exporting SPIR-V",
+        DISASSEMBLE_SPIRV => "Disassembling SPIR-V",
+        EXPORT_GLSL => "Exporting GLSL",
+        EXPORT_HLSL => "Exporting HLSL",
+        EXPORT_MSL => "Exporting MSL",
+        REFLECT => "Reflecting",
+        DUMP_IR => "Dumping IR",
+        FORMAT => "Formatting",
+        GOTO_ENTRY_POINT => "Finding entry points",
+        OVERRIDES => "Listing overrides",
+        STATS => "Collecting statistics",
+        ROUNDTRIP => "Checking the WGSL round trip",
+        _ => "Running command",
+    }
+}
+
+/// Runs a command, returning an error message to show the user if it fails.
+pub fn execute(
+    documents: &DocumentStore,
+    config: &Config,
+    params: ExecuteCommandParams,
+    progress: &Progress,
+) -> Result<Value, String> {
+    let mut arguments = params.arguments.into_iter();
+    let uri: Url = match arguments.next() {
+        Some(uri) => serde_json::from_value(uri).map_err(|err| format!("invalid uri: {}", err))?,
+        None => return Err(format!("{} expects a document uri", params.command)),
+    };
+    let document = documents
+        .get(&uri)
+        .ok_or_else(|| format!("{} is not open", uri))?;
+
+    progress.report(&format!(
+        "compiling {}",
+        uri.path().rsplit('/').next().unwrap_or("")
+    ));
+    match params.command.as_str() {
+        EXPORT_SPIRV => export_spirv(&uri, document, progress),
+        DISASSEMBLE_SPIRV => disassemble_spirv(document),
+        EXPORT_GLSL => export_glsl(document, arguments.collect()),
+        EXPORT_HLSL => export_hlsl(document, arguments.collect()),
+        EXPORT_MSL => export_msl(document, arguments.collect()),
+        DUMP_IR => dump_ir(document, arguments.collect()),
+        FORMAT => format(document, config, arguments.next()),
+        GOTO_ENTRY_POINT => entry_points(&uri, document),
+        // overrides are read from the source, as naga doesn't keep them apart from
+        // other constants
+        OVERRIDES => Ok(overrides::list(&document.text)),
+        STATS => Ok(stats(document)),
+        ROUNDTRIP => roundtrip(document),
+        REFLECT => {
+            let (module, info) = validated(document)?;
+            Ok(reflection::reflect(module, info))
+        }
+        command => Err(format!("unknown command {}", command)),
+    }
+}
+
+/// The parsed module of `document` along with its validation info, as every backend
+/// needs both.
+///
+/// Both are cached from the last parse, which validated against the configured target
+/// capabilities.
+pub fn validated(document: &Document) -> Result<(&naga::Module, &ModuleInfo), String> {
+    let module = document
+        .module
+        .as_ref()
+        .ok_or_else(|| "the document has parse errors".to_string())?;
+    let info = document
+        .info
+        .as_ref()
+        .ok_or_else(|| "the document failed to validate or validation is disabled".to_string())?;
+    Ok((module, info))
+}
+
+/// The locations of the entry points of the document, for the client to pick one to go
+/// to, along with their names in the same order.
+fn entry_points(uri: &Url, document: &Document) -> Result<Value, String> {
+    let module = document
+        .module
+        .as_ref()
+        .ok_or_else(|| "the document has parse errors".to_string())?;
+    let index = SourceIndex::new(&document.text);
+    let (names, locations): (Vec<&str>, Vec<Location>) = module
+        .entry_points
+        .iter()
+        .filter_map(|entry_point| {
+            let declaration = index.declarations.iter().find(|decl| {
+                decl.kind == DeclarationKind::Function && decl.name == entry_point.name
+            })?;
+            let location = Location {
+                uri: uri.clone(),
+                range: byte_range_to_range(&document.text, declaration.selection.clone()),
+            };
+            Some((entry_point.name.as_str(), location))
+        })
+        .unzip();
+    Ok(serde_json::json!({
+        "locations": locations,
+        "names": names,
+    }))
+}
+
+/// How long the last parse and validation of the document took in milliseconds, with
+/// `null` for what didn't run or wasn't timed, and the size of its module.
+fn stats(document: &Document) -> Value {
+    let millis = |time: Option<std::time::Duration>| time.map(|time| time.as_secs_f64() * 1000.0);
+    let timings = document.timings;
+    let module = document.module.as_ref().map(|module| {
+        let functions = module.functions.iter().map(|(_, function)| function);
+        let bodies = functions.chain(module.entry_points.iter().map(|ep| &ep.function));
+        serde_json::json!({
+            "functions": module.functions.len(),
+            "entryPoints": module.entry_points.len(),
+            "types": module.types.len(),
+            "constants": module.constants.len(),
+            "globals": module.global_variables.len(),
+            "expressions": bodies.map(|function| function.expressions.len()).sum::<usize>(),
+        })
+    });
+    serde_json::json!({
+        "parseMs": millis(timings.parse),
+        "validationMs": millis(timings.validation),
+        "validationReused": timings.validation_reused,
+        "functionsValidated": timings.validated_functions,
+        "textBytes": document.text.len(),
+        "module": module,
+    })
+}
+
+/// Formats the document in the style given as the argument after the uri, or else the
+/// configured one, returning the `TextEdit` replacing the whole document.
+fn format(document: &Document, config: &Config, style: Option<Value>) -> Result<Value, String> {
+    let style: FormatStyle = match style {
+        Some(style) => {
+            serde_json::from_value(style).map_err(|err| format!("invalid style: {}", err))?
+        }
+        None => config.format_style.clone().unwrap_or_default(),
+    };
+    let edit = formatting::format_with_style(document, &style, None)
+        .ok_or_else(|| "only WGSL documents can be formatted".to_string())?;
+    serde_json::to_value(edit).map_err(|err| err.to_string())
+}
+
+/// Compiles the document to SPIR-V and writes it to a `.spv` file next to the source.
+fn export_spirv(uri: &Url, document: &Document, progress: &Progress) -> Result<Value, String> {
+    use naga::back::spv;
+
+    let (module, info) = validated(document)?;
+    // a module naga validated can still trip an assertion in the backend
+    let words = catch_panic(|| spv::write_vec(module, info, &spv::Options::default(), None))
+        .ok_or_else(|| "SPIR-V backend error: unsupported shader".to_string())?
+        .map_err(|err| format!("SPIR-V backend error: {}", err))?;
+
+    let path = uri
+        .to_file_path()
+        .map_err(|()| format!("{} is not a file", uri))?
+        .with_extension("spv");
+    progress.report(&format!("writing {}", path.display()));
+    let bytes = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect::<Vec<u8>>();
+    std::fs::write(&path, bytes)
+        .map_err(|err| format!("failed to write {}: {}", path.display(), err))?;
+
+    Ok(serde_json::json!({
+        "path": path.display().to_string(),
+        "words": words.len(),
+    }))
+}
+
+/// Compiles the document to SPIR-V and returns it disassembled, to show what the source
+/// lowers to.
+fn disassemble_spirv(document: &Document) -> Result<Value, String> {
+    use naga::back::spv;
+    use rspirv::binary::Disassemble;
+
+    let (module, info) = validated(document)?;
+    let words = catch_panic(|| spv::write_vec(module, info, &spv::Options::default(), None))
+        .ok_or_else(|| "SPIR-V backend error: unsupported shader".to_string())?
+        .map_err(|err| format!("SPIR-V backend error: {}", err))?;
+    let binary = rspirv::dr::load_words(&words)
+        .map_err(|err| format!("failed to read back the SPIR-V: {:?}", err))?;
+    Ok(Value::String(binary.disassemble()))
+}
+
+/// The most lines of a round trip's diff to return.
+const MAX_DIFF_LINES: usize = 50;
+
+/// Writes the module back out as WGSL, parses and validates that, and writes it out
+/// again, reporting whether both writes agree. Any difference is naga losing or changing
+/// something on the way, which the formatter would too.
+fn roundtrip(document: &Document) -> Result<Value, String> {
+    use naga::back::wgsl;
+
+    if document.language != Language::Wgsl {
+        return Err("only WGSL documents can be round-tripped".to_string());
+    }
+    let (module, info) = validated(document)?;
+    let write = |module: &naga::Module, info: &ModuleInfo| {
+        wgsl::write_string(module, info, wgsl::WriterFlags::empty())
+            .map_err(|err| format!("WGSL backend error: {}", err))
+    };
+    let first = write(module, info)?;
+    let failed = |error: String| {
+        Ok::<_, String>(serde_json::json!({
+            "equivalent": false,
+            "error": error,
+            "written": first,
+        }))
+    };
+    let reparsed = match catch_panic(|| naga::front::wgsl::parse_str(&first)) {
+        Some(Ok(reparsed)) => reparsed,
+        Some(Err(err)) => {
+            let error = format!(
+                "the written WGSL doesn't parse: {}",
+                err.emit_to_string(&first)
+            );
+            return failed(error);
+        }
+        None => return failed("naga panicked parsing the written WGSL".to_string()),
+    };
+    // the writer drops enable directives, so the reparsed module may need any capability
+    let mut validator = Validator::new(ValidationFlags::all(), Capabilities::all());
+    let reparsed_info = match catch_panic(|| validator.validate(&reparsed)) {
+        Some(Ok(info)) => info,
+        Some(Err(err)) => {
+            let error = format!(
+                "the written WGSL doesn't validate: {}",
+                error_chain(err.as_inner())
+            );
+            return failed(error);
+        }
+        None => return failed("naga panicked validating the written WGSL".to_string()),
+    };
+    let second = write(&reparsed, &reparsed_info)?;
+
+    let counts = |module: &naga::Module| {
+        serde_json::json!({
+            "types": module.types.len(),
+            "constants": module.constants.len(),
+            "globals": module.global_variables.len(),
+            "functions": module.functions.len(),
+            "entryPoints": module.entry_points.len(),
+        })
+    };
+    let diff = line_diff(&first, &second);
+    let truncated = diff.len() > MAX_DIFF_LINES;
+    Ok(serde_json::json!({
+        "equivalent": diff.is_empty(),
+        "counts": { "original": counts(module), "reparsed": counts(&reparsed) },
+        "diff": diff.into_iter().take(MAX_DIFF_LINES).collect::<Vec<_>>(),
+        "diffTruncated": truncated,
+    }))
+}
+
+/// The most lines either side of a diff may have left once their common start and end
+/// are set aside, as comparing them takes memory by the product of the two counts.
+const MAX_COMPARED_LINES: usize = 1000;
+
+/// The lines only `old` or only `new` has, prefixed with `-` or `+` like a diff and
+/// with their line number, by the longest common subsequence of lines. Past
+/// `MAX_COMPARED_LINES`, only the ranges of lines that differ are given.
+fn line_diff(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+    if old.len().max(new.len()) > MAX_COMPARED_LINES {
+        return vec![format!(
+            "-{}..{} +{}..{}: too many lines differ to compare",
+            prefix + 1,
+            prefix + old.len(),
+            prefix + 1,
+            prefix + new.len()
+        )];
+    }
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            diff.push(format!("-{}: {}", prefix + i + 1, old[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+{}: {}", prefix + j + 1, new[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+/// Compiles one entry point to GLSL, taking the entry point name and optionally its
+/// stage as arguments, and returns the source.
+fn export_glsl(document: &Document, arguments: Vec<Value>) -> Result<Value, String> {
+    use naga::back::glsl;
+
+    let (module, info) = validated(document)?;
+    let name = arguments.first().and_then(Value::as_str);
+    let stage = match arguments.get(1).and_then(Value::as_str) {
+        Some(stage) => Some(parse_stage(stage)?),
+        None => None,
+    };
+    let entry_point = module
+        .entry_points
+        .iter()
+        .find(|ep| Some(ep.name.as_str()) == name && stage.is_none_or(|s| s == ep.stage))
+        .ok_or_else(|| {
+            let available = module
+                .entry_points
+                .iter()
+                .map(|ep| format!("{} ({})", ep.name, stage_name(ep.stage)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            match name {
+                Some(name) => format!("no entry point {}, available: {}", name, available),
+                None => format!("expected an entry point name, available: {}", available),
+            }
+        })?;
+
+    let pipeline_options = glsl::PipelineOptions {
+        shader_stage: entry_point.stage,
+        entry_point: entry_point.name.clone(),
+        multiview: None,
+    };
+    let options = glsl::Options::default();
+    let mut source = String::new();
+    // like the MSL backend, the GLSL one asserts on some constructs it can't translate
+    catch_panic(|| {
+        glsl::Writer::new(
+            &mut source,
+            module,
+            info,
+            &options,
+            &pipeline_options,
+            naga::proc::BoundsCheckPolicies::default(),
+        )?
+        .write()
+    })
+    .ok_or_else(|| "GLSL backend error: unsupported shader".to_string())?
+    .map_err(|err| format!("GLSL backend error: {}", err))?;
+
+    Ok(Value::String(source))
+}
+
+/// Compiles the document to HLSL, taking the shader model such as `"6_0"` as an
+/// optional argument, and returns the source along with the HLSL name of each entry
+/// point.
+fn export_hlsl(document: &Document, arguments: Vec<Value>) -> Result<Value, String> {
+    use naga::back::hlsl;
+
+    let (module, info) = validated(document)?;
+    let mut options = hlsl::Options::default();
+    if let Some(shader_model) = arguments.first().and_then(Value::as_str) {
+        options.shader_model = parse_shader_model(shader_model)?;
+    }
+
+    let mut source = String::new();
+    let reflection = catch_panic(|| hlsl::Writer::new(&mut source, &options).write(module, info))
+        .ok_or_else(|| "HLSL backend error: unsupported shader".to_string())?
+        .map_err(|err| format!("HLSL backend error: {}", err))?;
+
+    // the bindings, types and stage inputs and outputs of each entry point, for wiring
+    // up the pipeline, along with the name the HLSL gives it
+    let entry_points = reflection
+        .entry_point_names
+        .into_iter()
+        .enumerate()
+        .map(|(index, hlsl_name)| {
+            let mut reflected = reflection::entry_point(module, info, index);
+            match hlsl_name {
+                Ok(hlsl_name) => reflected["hlslName"] = Value::String(hlsl_name),
+                Err(err) => reflected["error"] = Value::String(err.to_string()),
+            }
+            reflected
+        })
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::json!({
+        "source": source,
+        "entryPoints": entry_points,
+    }))
+}
+
+/// Compiles the document to Metal Shading Language. The optional arguments are the
+/// language version, such as `"2.4"`, and the resource bindings of each entry point in
+/// the form of naga's `msl::EntryPointResourceMap`.
+fn export_msl(document: &Document, arguments: Vec<Value>) -> Result<Value, String> {
+    use naga::back::msl;
+
+    let (module, info) = validated(document)?;
+    let mut options = msl::Options::default();
+    if let Some(version) = arguments.first().and_then(Value::as_str) {
+        options.lang_version = parse_metal_version(version)?;
+    }
+    if let Some(bindings) = arguments.get(1) {
+        options.per_entry_point_map = serde_json::from_value(bindings.clone())
+            .map_err(|err| format!("invalid binding map: {}", err))?;
+    }
+
+    let pipeline_options = msl::PipelineOptions::default();
+    // the backend asserts on some unsupported constructs rather than returning an error
+    let (source, translation) =
+        catch_panic(|| msl::write_string(module, info, &options, &pipeline_options))
+            .ok_or_else(|| "MSL backend error: unsupported shader".to_string())?
+            .map_err(|err| format!("MSL backend error: {}", err))?;
+
+    let entry_points = module
+        .entry_points
+        .iter()
+        .zip(translation.entry_point_names)
+        .map(|(entry_point, msl_name)| {
+            let mut translated = serde_json::json!({
+                "name": entry_point.name,
+                "stage": stage_name(entry_point.stage),
+            });
+            match msl_name {
+                Ok(msl_name) => translated["mslName"] = Value::String(msl_name),
+                Err(err) => translated["error"] = Value::String(err.to_string()),
+            }
+            translated
+        })
+        .collect::<Vec<_>>();
+
+    Ok(serde_json::json!({
+        "source": source,
+        "entryPoints": entry_points,
+    }))
+}
+
+/// Pretty prints naga's IR of the document. The optional arguments select what to print,
+/// out of `functions`, `entryPoints`, `types`, `constants` and `globals`, and the name of
+/// a single item in there.
+fn dump_ir(document: &Document, arguments: Vec<Value>) -> Result<Value, String> {
+    let module = document
+        .module
+        .as_ref()
+        .ok_or_else(|| "the document has parse errors".to_string())?;
+    let section = arguments.first().and_then(Value::as_str);
+    let name = arguments.get(1).and_then(Value::as_str);
+    let selected = |item_name: Option<&str>| name.is_none_or(|name| item_name == Some(name));
+
+    let dump = match section {
+        None => format!("{:#?}", module),
+        Some("functions") => dump_items(
+            module
+                .functions
+                .iter()
+                .filter(|(_, function)| selected(function.name.as_deref())),
+        ),
+        Some("entryPoints") => dump_items(
+            module
+                .entry_points
+                .iter()
+                .filter(|entry_point| selected(Some(&entry_point.name))),
+        ),
+        Some("types") => dump_items(
+            module
+                .types
+                .iter()
+                .filter(|(_, ty)| selected(ty.name.as_deref())),
+        ),
+        Some("constants") => dump_items(
+            module
+                .constants
+                .iter()
+                .filter(|(_, constant)| selected(constant.name.as_deref())),
+        ),
+        Some("globals") => dump_items(
+            module
+                .global_variables
+                .iter()
+                .filter(|(_, global)| selected(global.name.as_deref())),
+        ),
+        Some(section) => {
+            return Err(format!(
+                "unknown section {}, expected functions, entryPoints, types, constants or globals",
+                section
+            ))
+        }
+    };
+    Ok(Value::String(dump))
+}
+
+fn dump_items<T: std::fmt::Debug>(items: impl Iterator<Item = T>) -> String {
+    items
+        .map(|item| format!("{:#?}", item))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parses a Metal language version such as `"2.4"`.
+fn parse_metal_version(version: &str) -> Result<(u8, u8), String> {
+    let invalid = || format!("invalid Metal version {}, expected one like 2.4", version);
+    let (major, minor) = version.split_once('.').ok_or_else(invalid)?;
+    Ok((
+        major.parse().map_err(|_| invalid())?,
+        minor.parse().map_err(|_| invalid())?,
+    ))
+}
+
+fn parse_shader_model(shader_model: &str) -> Result<naga::back::hlsl::ShaderModel, String> {
+    use naga::back::hlsl::ShaderModel;
+
+    match shader_model.trim_start_matches(['s', 'm', '_']) {
+        "5_0" | "5.0" => Ok(ShaderModel::V5_0),
+        "5_1" | "5.1" => Ok(ShaderModel::V5_1),
+        "6_0" | "6.0" => Ok(ShaderModel::V6_0),
+        _ => Err(format!(
+            "unknown shader model {}, expected 5_0, 5_1 or 6_0",
+            shader_model
+        )),
+    }
+}
+
+pub fn parse_stage(stage: &str) -> Result<ShaderStage, String> {
+    match stage {
+        "vertex" => Ok(ShaderStage::Vertex),
+        "fragment" => Ok(ShaderStage::Fragment),
+        "compute" => Ok(ShaderStage::Compute),
+        _ => Err(format!("unknown shader stage {}", stage)),
+    }
+}
+
+pub fn stage_name(stage: ShaderStage) -> &'static str {
+    match stage {
+        ShaderStage::Vertex => "vertex",
+        ShaderStage::Fragment => "fragment",
+        ShaderStage::Compute => "compute",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_number_the_lines_around_the_common_start_and_end() {
+        let diff = line_diff("a\nb\nc\nd\n", "a\nc\nx\nd\n");
+        assert_eq!(diff, ["-2: b", "+3: x"]);
+        assert!(line_diff("a\nb\n", "a\nb\n").is_empty());
+    }
+
+    #[test]
+    fn long_diffs_only_give_the_lines_that_differ() {
+        let old = "x\n".repeat(MAX_COMPARED_LINES + 1);
+        let new = "y\n".repeat(MAX_COMPARED_LINES + 1);
+        let diff = line_diff(&format!("a\n{}z\n", old), &format!("a\n{}z\n", new));
+        assert_eq!(diff.len(), 1);
+        assert!(diff[0].starts_with("-2..1002 +2..1002:"), "{}", diff[0]);
+    }
+}
@@ -0,0 +1,312 @@
+use lsp_types::{
+    ClientCapabilities, CompletionItem, CompletionItemKind, Documentation, InsertTextFormat,
+    Position,
+};
+use naga::TypeInner;
+
+use crate::builtins::{self, BUILTIN_FUNCTIONS};
+use crate::document::Document;
+use crate::position::position_to_byte_offset;
+use crate::resolve::{expected_type, expression_type, type_of};
+use crate::snippets::SNIPPETS;
+use crate::syntax::{DeclarationKind, SourceIndex, TokenKind};
+use crate::types::canonical_type_name;
+
+/// The completions at `position`. With `resolve`, builtins only come with their name,
+/// and `completionItem/resolve` fills in the signature and documentation of the one the
+/// user highlights. With `snippets`, the shader scaffolds are offered at the top level.
+pub fn completion(
+    document: &Document,
+    position: Position,
+    resolve: bool,
+    snippets: bool,
+) -> Vec<CompletionItem> {
+    let text = &document.text;
+    let offset = position_to_byte_offset(text, position);
+    let index = SourceIndex::new(text);
+
+    // the `.` right before the cursor, or before the partially typed member name
+    let dot = index.token_before(offset).and_then(|last| {
+        let token = index.tokens[last];
+        match token.text(text) {
+            "." => Some(last),
+            _ if token.kind == TokenKind::Ident
+                && last > 0
+                && index.tokens[last - 1].text(text) == "." =>
+            {
+                Some(last - 1)
+            }
+            _ => None,
+        }
+    });
+    if let Some(dot) = dot {
+        return member_completion(document, &index, dot);
+    }
+
+    // the `:` or `->` right before the cursor, or before the partially typed type name
+    let annotated = index.token_before(offset).is_some_and(|last| {
+        let annotation = |token: usize| match index.tokens[token].text(text) {
+            ":" => true,
+            ">" => token > 0 && index.tokens[token - 1].text(text) == "-",
+            _ => false,
+        };
+        annotation(last)
+            || (index.tokens[last].kind == TokenKind::Ident && last > 0 && annotation(last - 1))
+    });
+    if annotated {
+        return type_completion(document, &index);
+    }
+
+    // with an expected type, whatever produces it goes first
+    let expected = expected_type(document, &index, offset);
+    let sort_text = |label: &str, ty: Option<&str>| {
+        let expected = expected.as_deref()?;
+        let rank = if ty.is_some_and(|ty| type_matches(ty, expected)) {
+            0
+        } else {
+            1
+        };
+        Some(format!("{}{}", rank, label))
+    };
+
+    let declarations = index
+        .declarations
+        .iter()
+        .enumerate()
+        // only what is in scope here and not shadowed
+        .filter(|&(decl, declaration)| index.resolve(&declaration.name, offset) == Some(decl))
+        .filter_map(|(decl, declaration)| {
+            let kind = match declaration.kind {
+                DeclarationKind::Function => CompletionItemKind::FUNCTION,
+                DeclarationKind::Struct | DeclarationKind::Alias => CompletionItemKind::STRUCT,
+                DeclarationKind::Const
+                | DeclarationKind::Override
+                | DeclarationKind::LocalConst => CompletionItemKind::CONSTANT,
+                DeclarationKind::GlobalVar
+                | DeclarationKind::Param
+                | DeclarationKind::Let
+                | DeclarationKind::Var => CompletionItemKind::VARIABLE,
+                DeclarationKind::Member => return None,
+            };
+            // the type a use of this name evaluates to
+            let ty = match declaration.kind {
+                DeclarationKind::Function => declaration.ty.clone(),
+                DeclarationKind::Struct | DeclarationKind::Alias => None,
+                _ => type_of(document, &index, decl),
+            };
+            Some(CompletionItem {
+                label: declaration.name.clone(),
+                kind: Some(kind),
+                sort_text: sort_text(&declaration.name, ty.as_deref()),
+                detail: ty,
+                ..Default::default()
+            })
+        });
+
+    let builtins = BUILTIN_FUNCTIONS.iter().map(|builtin| {
+        let returns_expected = builtin
+            .signatures
+            .iter()
+            .filter_map(|signature| builtins::return_type(signature))
+            .find(|ty| {
+                expected
+                    .as_deref()
+                    .is_some_and(|expected| type_matches(ty, expected))
+            });
+        let item = CompletionItem {
+            label: builtin.name.to_string(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            sort_text: sort_text(builtin.name, returns_expected),
+            data: Some(serde_json::json!({ "builtin": builtin.name })),
+            ..Default::default()
+        };
+        if resolve {
+            item
+        } else {
+            self::resolve(item)
+        }
+    });
+
+    // outside of any function or struct, where a new one can start
+    let top_level = !index.declarations.iter().any(|decl| match decl.kind {
+        DeclarationKind::Function => decl
+            .body
+            .as_ref()
+            .is_some_and(|body| body.contains(&offset)),
+        DeclarationKind::Struct => decl.full.start < offset && offset < decl.full.end,
+        _ => false,
+    });
+    let scaffolds = SNIPPETS
+        .iter()
+        .filter(|_| snippets && top_level)
+        .map(|snippet| CompletionItem {
+            label: snippet.label.to_string(),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: Some(snippet.detail.to_string()),
+            insert_text: Some(snippet.body.to_string()),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        });
+
+    declarations.chain(builtins).chain(scaffolds).collect()
+}
+
+/// Whether a value of type `ty` fits where `expected` is needed. Builtin signatures use
+/// uppercase placeholders such as `T` or `vecN<T>`, which match any type.
+fn type_matches(ty: &str, expected: &str) -> bool {
+    fn glob(pattern: &[u8], text: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some((c, rest)) if c.is_ascii_uppercase() => {
+                (1..=text.len()).any(|taken| glob(rest, &text[taken..]))
+            }
+            Some((c, rest)) => text.first() == Some(c) && glob(rest, &text[1..]),
+        }
+    }
+    let ty = canonical_type_name(ty);
+    let expected = canonical_type_name(expected);
+    glob(ty.as_bytes(), expected.as_bytes())
+}
+
+/// Whether the client can ask for the documentation of a completion item once it is
+/// selected, rather than getting it up front for every item.
+pub fn resolves_documentation(client: &ClientCapabilities) -> bool {
+    client
+        .text_document
+        .as_ref()
+        .and_then(|caps| caps.completion.as_ref())
+        .and_then(|caps| caps.completion_item.as_ref())
+        .and_then(|caps| caps.resolve_support.as_ref())
+        .is_some_and(|support| {
+            support
+                .properties
+                .iter()
+                .any(|property| property == "documentation")
+        })
+}
+
+/// Whether the client expands snippets, with their tab stops, in completions.
+pub fn supports_snippets(client: &ClientCapabilities) -> bool {
+    client
+        .text_document
+        .as_ref()
+        .and_then(|caps| caps.completion.as_ref())
+        .and_then(|caps| caps.completion_item.as_ref())
+        .and_then(|caps| caps.snippet_support)
+        .unwrap_or(false)
+}
+
+/// Fills in the signature and documentation of a builtin's completion item, which its
+/// `data` names.
+pub fn resolve(mut item: CompletionItem) -> CompletionItem {
+    let builtin = item
+        .data
+        .as_ref()
+        .and_then(|data| data.get("builtin"))
+        .and_then(|name| name.as_str())
+        .and_then(builtins::find);
+    if let Some(builtin) = builtin {
+        item.detail = Some(builtin.signatures[0].to_string());
+        item.documentation = Some(Documentation::String(builtin.doc.to_string()));
+    }
+    item
+}
+
+/// The types a declaration can be annotated with: the structs and aliases of the
+/// document, those naga knows of besides, and the predeclared types.
+fn type_completion(document: &Document, index: &SourceIndex) -> Vec<CompletionItem> {
+    let mut names: Vec<String> = index
+        .declarations
+        .iter()
+        .filter(|decl| matches!(decl.kind, DeclarationKind::Struct | DeclarationKind::Alias))
+        .map(|decl| decl.name.clone())
+        .collect();
+    if let Some(ref module) = document.module {
+        for (_, ty) in module.types.iter() {
+            if let (Some(name), TypeInner::Struct { .. }) = (&ty.name, &ty.inner) {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+    }
+    let user = names.into_iter().map(|name| CompletionItem {
+        label: name,
+        kind: Some(CompletionItemKind::STRUCT),
+        ..Default::default()
+    });
+    let predeclared = predeclared_types().into_iter().map(|name| CompletionItem {
+        label: name,
+        kind: Some(CompletionItemKind::CLASS),
+        ..Default::default()
+    });
+    user.chain(predeclared).collect()
+}
+
+/// The types and type generators WGSL predeclares.
+fn predeclared_types() -> Vec<String> {
+    const TYPES: &[&str] = &[
+        "bool",
+        "i32",
+        "u32",
+        "f32",
+        "f16",
+        "array",
+        "atomic",
+        "ptr",
+        "sampler",
+        "sampler_comparison",
+        "texture_1d",
+        "texture_2d",
+        "texture_2d_array",
+        "texture_3d",
+        "texture_cube",
+        "texture_cube_array",
+        "texture_multisampled_2d",
+        "texture_depth_2d",
+        "texture_depth_2d_array",
+        "texture_depth_cube",
+        "texture_depth_cube_array",
+        "texture_depth_multisampled_2d",
+        "texture_storage_1d",
+        "texture_storage_2d",
+        "texture_storage_2d_array",
+        "texture_storage_3d",
+        "texture_external",
+    ];
+    let mut types: Vec<String> = TYPES.iter().map(|ty| ty.to_string()).collect();
+    for n in 2..=4 {
+        types.push(format!("vec{}", n));
+        for suffix in &["i", "u", "f", "h"] {
+            types.push(format!("vec{}{}", n, suffix));
+        }
+        for m in 2..=4 {
+            types.push(format!("mat{}x{}", n, m));
+            for suffix in &["f", "h"] {
+                types.push(format!("mat{}x{}{}", n, m, suffix));
+            }
+        }
+    }
+    types
+}
+
+/// The fields of the struct that the expression before `dot` evaluates to.
+fn member_completion(document: &Document, index: &SourceIndex, dot: usize) -> Vec<CompletionItem> {
+    let structure = dot
+        .checked_sub(1)
+        .and_then(|base| expression_type(document, index, base))
+        .and_then(|ty| index.find_struct(&ty));
+    let structure = match structure {
+        Some(structure) => structure,
+        None => return Vec::new(),
+    };
+    index
+        .members(structure)
+        .map(|member| CompletionItem {
+            label: member.name.clone(),
+            kind: Some(CompletionItemKind::FIELD),
+            detail: member.ty.clone(),
+            ..Default::default()
+        })
+        .collect()
+}
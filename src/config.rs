@@ -0,0 +1,248 @@
+//! User settings, sent as `initializationOptions` and updated through
+//! `workspace/didChangeConfiguration`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use lsp_types::DiagnosticSeverity;
+use naga::valid::Capabilities;
+use naga::ShaderStage;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::commands::parse_stage;
+use crate::diagnostics::CODES;
+
+/// The section clients may nest our settings under.
+const SECTION: &str = "naga-lsp";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    /// Whether to run the validator on top of parsing.
+    pub validation: bool,
+    /// The features the target hardware supports, named after naga's
+    /// `valid::Capabilities` flags in any case, such as `push_constant`, `float64`,
+    /// `primitive_index`, `clip_distance`, `cull_distance`, `multiview`,
+    /// `early_depth_test`, `multisampled_shading` or `ray_query`. Everything is allowed
+    /// if unset, and shaders using anything else fail validation.
+    pub capabilities: Option<Vec<String>>,
+    /// The extensions shaders may turn on with `enable`, such as `f16`. Any extension
+    /// WGSL defines is allowed if unset. The capabilities an extension needs are granted
+    /// to the shaders enabling it on top of `capabilities`.
+    pub allowed_enables: Option<Vec<String>>,
+    /// Whether to warn about unused functions and variables.
+    pub lints: bool,
+    /// How long the text has to stay unchanged before we reparse it.
+    pub debounce_ms: u64,
+    /// The level to log at, such as `"debug"`, unless `NAGA_LSP_LOG` is set.
+    pub log_level: Option<String>,
+    /// The backends to translate every shader with after it validates, out of `spv`,
+    /// `glsl`, `hlsl` and `msl`, warning about any that fail.
+    pub backends: Vec<String>,
+    /// Whether to only translate with `backends` when a document is saved, rather than
+    /// after every edit.
+    pub backends_on_save: bool,
+    /// The start of lines that include another WGSL file, such as `//!include`, followed
+    /// by the path of the file in quotes relative to the including one. Nothing is
+    /// included if unset.
+    pub include_directive: Option<String>,
+    /// How to report two resources sharing a group and binding in one entry point,
+    /// `"warning"` or `"error"`.
+    pub binding_collisions: Severity,
+    /// How to report barriers in control flow that may differ between invocations,
+    /// `"warning"` or `"error"`. The rest of a module is still validated when it is only
+    /// warned about. naga doesn't check derivatives and texture sampling for uniformity.
+    pub uniformity: Severity,
+    /// The largest workgroups compute shaders may use, by default WebGPU's.
+    pub workgroup_limits: WorkgroupLimits,
+    /// The most diagnostics to publish for one document, not counting the note saying
+    /// how many more there are.
+    pub max_diagnostics: usize,
+    /// The severity to report diagnostics with by their code, such as
+    /// `{"unused-variable": "error"}`, or `"off"` not to report them at all.
+    pub severity_overrides: HashMap<String, SeverityOverride>,
+    /// The style to format documents in, keeping their comments. Documents are written
+    /// back out through naga instead if unset.
+    pub format_style: Option<FormatStyle>,
+    /// Whether to format documents as they are saved, for clients asking to, unless their
+    /// text doesn't parse.
+    pub format_on_save: bool,
+    /// Whether to index the WGSL files of the workspace that aren't open, for workspace
+    /// symbols and references. Off by default, as big workspaces take a while.
+    pub workspace_index: bool,
+    /// The files to index, relative to each workspace folder.
+    pub workspace_glob: String,
+    /// Whether to show the type abstract literals such as `1` take where it isn't the
+    /// `i32` or `f32` they'd be on their own, as inlay hints. Off by default, as there
+    /// can be many.
+    pub conversion_hints: bool,
+    /// The size in bytes of the largest document to analyze. Bigger ones, which are more
+    /// likely mislabeled data than shaders, only get a note saying so.
+    pub max_file_size: usize,
+    /// The stage to assume for documents that don't say, `"vertex"`, `"fragment"` or
+    /// `"compute"`: GLSL without a stage extension, which is otherwise guessed from its
+    /// contents, and WGSL without entry points, whose functions are otherwise checked
+    /// for no stage in particular. A document picks its own with a comment at the top
+    /// such as `// naga-lsp: stage=fragment`.
+    pub default_stage: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn to_lsp(self) -> DiagnosticSeverity {
+        match self {
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Error => DiagnosticSeverity::ERROR,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeverityOverride {
+    Off,
+    Hint,
+    Information,
+    Warning,
+    Error,
+}
+
+impl SeverityOverride {
+    /// The severity to report with, or `None` to drop the diagnostic.
+    pub fn to_lsp(self) -> Option<DiagnosticSeverity> {
+        match self {
+            SeverityOverride::Off => None,
+            SeverityOverride::Hint => Some(DiagnosticSeverity::HINT),
+            SeverityOverride::Information => Some(DiagnosticSeverity::INFORMATION),
+            SeverityOverride::Warning => Some(DiagnosticSeverity::WARNING),
+            SeverityOverride::Error => Some(DiagnosticSeverity::ERROR),
+        }
+    }
+}
+
+/// How to lay out a shader when formatting it from its tokens.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FormatStyle {
+    /// The spaces to indent each block by, going by the client's options if unset.
+    pub indent_width: Option<u32>,
+    /// Whether the `{` opening a block ends the line before it or goes on its own.
+    pub brace_style: BraceStyle,
+    /// The longest lines can get before their arguments are put on lines of their own.
+    pub max_line_length: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BraceStyle {
+    #[default]
+    SameLine,
+    NextLine,
+}
+
+/// The largest workgroup size along each dimension, and the most invocations overall.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorkgroupLimits {
+    pub max_x: u32,
+    pub max_y: u32,
+    pub max_z: u32,
+    pub max_invocations: u32,
+}
+
+impl Default for WorkgroupLimits {
+    fn default() -> Self {
+        WorkgroupLimits {
+            max_x: 256,
+            max_y: 256,
+            max_z: 64,
+            max_invocations: 256,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            validation: true,
+            capabilities: None,
+            allowed_enables: None,
+            lints: true,
+            debounce_ms: 200,
+            max_diagnostics: 100,
+            backends: Vec::new(),
+            backends_on_save: false,
+            include_directive: None,
+            binding_collisions: Severity::Warning,
+            uniformity: Severity::Error,
+            workgroup_limits: WorkgroupLimits::default(),
+            log_level: None,
+            severity_overrides: HashMap::new(),
+            format_style: None,
+            format_on_save: true,
+            workspace_index: false,
+            workspace_glob: "**/*.wgsl".to_string(),
+            conversion_hints: false,
+            max_file_size: 1 << 20,
+            default_stage: None,
+        }
+    }
+}
+
+impl Config {
+    /// Reads the settings, falling back to the defaults if they don't deserialize.
+    pub fn from_value(value: Option<Value>) -> Self {
+        let value = match value {
+            Some(Value::Object(mut settings)) if settings.contains_key(SECTION) => {
+                settings.remove(SECTION).unwrap_or_default()
+            }
+            Some(value) => value,
+            None => return Config::default(),
+        };
+        let config: Config = serde_json::from_value(value).unwrap_or_else(|err| {
+            log::warn!("invalid configuration: {}", err);
+            Config::default()
+        });
+        for code in config.severity_overrides.keys() {
+            if !CODES.contains(&code.as_str()) {
+                log::warn!("unknown diagnostic code {} in severityOverrides", code);
+            }
+        }
+        config
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        let names = match self.capabilities {
+            Some(ref names) => names,
+            None => return Capabilities::all(),
+        };
+        names
+            .iter()
+            .filter_map(|name| {
+                let capability = Capabilities::from_name(&name.to_ascii_uppercase());
+                if capability.is_none() {
+                    log::warn!("unknown capability {}", name);
+                }
+                capability
+            })
+            .fold(Capabilities::empty(), |all, capability| all | capability)
+    }
+
+    pub fn default_stage(&self) -> Option<ShaderStage> {
+        let name = self.default_stage.as_deref()?;
+        parse_stage(name)
+            .map_err(|err| log::warn!("{} in defaultStage", err))
+            .ok()
+    }
+}
@@ -0,0 +1,1015 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::hash_map::{DefaultHasher, HashMap};
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DocumentDiagnosticReport,
+    DocumentDiagnosticReportKind, DocumentDiagnosticReportResult, FullDocumentDiagnosticReport,
+    NumberOrString, RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport,
+    UnchangedDocumentDiagnosticReport, Url,
+};
+
+use naga::front::{glsl, spv, wgsl};
+use naga::valid::{Capabilities, ModuleInfo, ValidationError, ValidationFlags, Validator};
+use naga::WithSpan;
+
+use crate::access;
+use crate::backends;
+use crate::bindings;
+use crate::config::{Config, Severity};
+use crate::document::{Document, Language, Timings};
+use crate::enables;
+use crate::includes::{self, SourceMap};
+use crate::incremental::{self, Fingerprints};
+use crate::lints;
+use crate::locations;
+use crate::overflow;
+use crate::overrides;
+use crate::position::byte_range_to_range;
+use crate::recursion;
+use crate::returns;
+use crate::samplers;
+use crate::stages;
+use crate::swizzles;
+use crate::syntax::{tokenize, SourceIndex, TokenKind};
+use crate::undeclared;
+use crate::uniformity;
+use crate::workgroups;
+
+/// The `source` of every diagnostic we publish.
+const SOURCE: &str = "naga";
+
+/// Parses and validates the current text of `document`, caching the resulting module and
+/// its validation info for the other features and returning any errors as diagnostics.
+///
+/// The diagnostics are grouped by file to publish them for, starting with the document
+/// itself and followed by the files it includes, including an empty group for each file
+/// whose errors have since been fixed.
+///
+/// The checks too slow to run on every edit are only run if the document was just
+/// `saved`, their diagnostics being kept until the next save.
+pub fn parse_document(
+    uri: &Url,
+    document: &mut Document,
+    config: &Config,
+    saved: bool,
+) -> Vec<(Url, Vec<Diagnostic>)> {
+    if saved {
+        document.saved_diagnostics.clear();
+    }
+    let mut files = vec![(uri.clone(), Vec::new())];
+    let mut diags = diagnose(uri, document, config, saved);
+    if config.backends_on_save {
+        let kept = document.saved_diagnostics.iter().cloned();
+        diags.extend(kept.map(|diag| (uri.clone(), diag)));
+    }
+    for (file, diag) in diags {
+        match files.iter_mut().find(|(other, _)| *other == file) {
+            Some((_, diags)) => diags.push(diag),
+            None => files.push((file, vec![diag])),
+        }
+    }
+    for file in document.diagnosed_includes.drain(..) {
+        if !files.iter().any(|(other, _)| *other == file) {
+            files.push((file, Vec::new()));
+        }
+    }
+    document.diagnosed_includes = files[1..]
+        .iter()
+        .filter(|(_, diags)| !diags.is_empty())
+        .map(|(file, _)| file.clone())
+        .collect();
+    for (_, diags) in &mut files {
+        let overridden = override_severities(std::mem::take(diags), config);
+        *diags = limit(overridden, config.max_diagnostics);
+    }
+    files
+}
+
+/// Diagnoses `text` on its own, without a client that opened it, for embedders such as
+/// the WASM build. Only the diagnostics of `text` itself are returned, as there are no
+/// other files to publish them for.
+pub fn check_source(text: &str, language: Language, config: &Config) -> Vec<Diagnostic> {
+    let uri = Url::parse("untitled:shader").expect("the placeholder uri is valid");
+    let mut document = Document::new(text.to_string(), 0, language);
+    // there is no later save to wait for, so the slow checks run now
+    let mut files = parse_document(&uri, &mut document, config, true);
+    files.swap_remove(0).1
+}
+
+/// Every code our diagnostics may have, which `severityOverrides` may name.
+pub const CODES: &[&str] = &[
+    "access-mode",
+    "backend-error",
+    "binding-collision",
+    "constant-overflow",
+    "disallowed-enable",
+    "glsl-error",
+    "include-error",
+    "internal",
+    "invalid-constant",
+    "invalid-entry-point",
+    "invalid-function",
+    "invalid-global",
+    "invalid-layout",
+    "invalid-swizzle",
+    "invalid-type",
+    "location-collision",
+    "missing-return",
+    "non-uniform-control-flow",
+    "not-analyzed",
+    "override-id-collision",
+    "parse-error",
+    "recursion",
+    "redefinition",
+    "texture-sampler-mismatch",
+    "spirv-error",
+    "stage-mismatch",
+    "too-many-errors",
+    "unassignable-override",
+    "unknown-enable",
+    "unexpected-token",
+    "unknown-attribute",
+    "unknown-identifier",
+    "unknown-type",
+    "unused-function",
+    "unused-variable",
+    "use-before-declaration",
+    "validation-error",
+    "workgroup-size",
+];
+
+/// Applies the user's `severityOverrides`, dropping the diagnostics turned off.
+fn override_severities(diags: Vec<Diagnostic>, config: &Config) -> Vec<Diagnostic> {
+    if config.severity_overrides.is_empty() {
+        return diags;
+    }
+    diags
+        .into_iter()
+        .filter_map(|mut diag| {
+            let severity = match diag.code {
+                Some(NumberOrString::String(ref code)) => config.severity_overrides.get(code),
+                _ => None,
+            };
+            if let Some(severity) = severity {
+                diag.severity = Some(severity.to_lsp()?);
+            }
+            Some(diag)
+        })
+        .collect()
+}
+
+/// The diagnostics of `document` along with the file each is in.
+///
+/// A panic inside naga is reported as a single diagnostic instead of taking the server
+/// down, and leaves the document without a module.
+fn diagnose(
+    uri: &Url,
+    document: &mut Document,
+    config: &Config,
+    saved: bool,
+) -> Vec<(Url, Diagnostic)> {
+    // validation results only depend on the code, so they carry over edits to comments
+    // and whitespace in WGSL; line breaks end GLSL directives, and a SPIR-V binary may
+    // have changed on disk without any edit
+    let fingerprint = code_fingerprint(&document.text);
+    let info = document.info.take();
+    let previous = info.clone().filter(|_| {
+        document.language == Language::Wgsl && document.validated_code == Some(fingerprint)
+    });
+    // edits to other code leave the info of the functions they didn't change
+    let earlier = info.zip(document.validated_functions.take());
+    document.module = None;
+    document.validated_code = None;
+    document.source_map = None;
+    let text = &document.text;
+
+    // a SPIR-V document's text isn't what gets parsed
+    if document.language != Language::Spirv {
+        let skipped = if text.len() > config.max_file_size {
+            Some(format!(
+                "file too large to analyze, at {} bytes, over the maxFileSize of {}",
+                text.len(),
+                config.max_file_size
+            ))
+        } else if text.contains('\0') {
+            Some("file looks like binary data rather than shader source, not analyzing".to_string())
+        } else {
+            None
+        };
+        if let Some(message) = skipped {
+            let diag = Diagnostic {
+                severity: Some(lsp_types::DiagnosticSeverity::INFORMATION),
+                code: Some(NumberOrString::String("not-analyzed".to_string())),
+                source: Some(SOURCE.to_string()),
+                message,
+                ..Default::default()
+            };
+            return vec![(uri.clone(), diag)];
+        }
+    }
+
+    let (source, map) = match config.include_directive {
+        Some(ref directive) if document.language == Language::Wgsl => {
+            match includes::expand(uri, text, directive) {
+                Ok((source, map)) => (Cow::Owned(source), map),
+                Err(err) => {
+                    let diag = Diagnostic {
+                        range: byte_range_to_range(text, err.range),
+                        severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+                        code: Some(NumberOrString::String("include-error".to_string())),
+                        source: Some(SOURCE.to_string()),
+                        message: err.message,
+                        ..Default::default()
+                    };
+                    return vec![(uri.clone(), diag)];
+                }
+            }
+        }
+        _ => (
+            Cow::Borrowed(text.as_str()),
+            SourceMap::identity(text.len()),
+        ),
+    };
+    // the included files may have changed without the document changing
+    let previous = previous.filter(|_| !map.includes_files());
+    // naga doesn't know about enable directives, so we take them out and grant the
+    // validator what they enable ourselves
+    let (source, enabled) = if document.language == Language::Wgsl {
+        let enabled = enables::capabilities(&source, config.allowed_enables.as_deref());
+        (enables::strip(source), enabled)
+    } else {
+        (source, Capabilities::empty())
+    };
+
+    let language = document.language;
+    let (res, parse_time) = timed(|| match language {
+        Language::Wgsl => catch_panic(|| parse_wgsl(uri, text, &source, &map)),
+        Language::Glsl(stage) => {
+            let stage = stage.or_else(|| stages::assumed(text, config));
+            catch_panic(|| parse_glsl(uri, text, stage))
+        }
+        Language::Spirv => catch_panic(|| parse_spirv(uri)),
+    });
+    document.timings = Timings {
+        parse: parse_time,
+        ..Timings::default()
+    };
+    let res = match res {
+        Some(res) => res,
+        None => return vec![(uri.clone(), internal_error())],
+    };
+    let mut diags = Vec::new();
+    let own = |found: Vec<Diagnostic>| found.into_iter().map(move |diag| (uri.clone(), diag));
+
+    match res {
+        Ok(module) if !config.validation => document.module = Some(Arc::new(module)),
+        Ok(module) => {
+            document.timings.validation_reused = previous.is_some();
+            let capabilities = config.capabilities() | enabled;
+            let fingerprints;
+            let mut validation = match previous {
+                Some(info) => {
+                    fingerprints = earlier.map(|(_, fingerprints)| fingerprints);
+                    Ok(info)
+                }
+                None => {
+                    fingerprints = Fingerprints::of(&module, ValidationFlags::all(), capabilities);
+                    let (validation, time) = timed(|| {
+                        validate_changes(&module, capabilities, fingerprints.as_ref(), earlier)
+                    });
+                    document.timings.validation = time;
+                    match validation {
+                        Some((validation, validated)) => {
+                            document.timings.validated_functions = validated;
+                            validation
+                        }
+                        None => return vec![(uri.clone(), internal_error())],
+                    }
+                }
+            };
+            // uniformity that is only warned about mustn't keep the rest of the module
+            // from validating, so it is validated again without the analysis; the info
+            // can't be reused then, as that would lose the warning
+            let warning = match validation {
+                Err(ref err) if matches!(config.uniformity, Severity::Warning) => {
+                    uniformity::explain(err.as_inner()).map(|explanation| {
+                        let spans = err
+                            .spans()
+                            .map(|(span, label)| (close_parens(&source, *span), label.as_str()));
+                        error_diagnostic(
+                            uri,
+                            &document.text,
+                            &map,
+                            explanation,
+                            "non-uniform-control-flow",
+                            spans,
+                        )
+                    })
+                }
+                _ => None,
+            };
+            let reusable = warning.is_none();
+            if let Some((file, mut diag)) = warning {
+                diag.severity = Some(lsp_types::DiagnosticSeverity::WARNING);
+                diags.push((file, diag));
+                let flags = ValidationFlags::all() - ValidationFlags::CONTROL_FLOW_UNIFORMITY;
+                validation = match validate(&module, flags, capabilities) {
+                    Some(validation) => validation,
+                    None => return vec![(uri.clone(), internal_error())],
+                };
+            }
+            match validation {
+                Ok(info) => {
+                    diags.extend(bindings::binding_collisions(
+                        uri,
+                        &document.text,
+                        &map,
+                        &module,
+                        &info,
+                        config.binding_collisions.to_lsp(),
+                        SOURCE,
+                    ));
+                    if !config.backends_on_save {
+                        diags.extend(own(backends::check(
+                            &module,
+                            &info,
+                            &config.backends,
+                            SOURCE,
+                        )));
+                    } else if saved {
+                        document.saved_diagnostics =
+                            backends::check(&module, &info, &config.backends, SOURCE);
+                    }
+                    document.info = Some(info);
+                    if reusable {
+                        document.validated_code = Some(fingerprint);
+                        document.validated_functions = fingerprints;
+                    }
+                }
+                Err(err) => {
+                    log::debug!("validation error: {:?}", err);
+                    let spans = err
+                        .spans()
+                        .map(|(span, label)| (close_parens(&source, *span), label.as_str()));
+                    let (message, code) = match uniformity::explain(err.as_inner()) {
+                        Some(explanation) => (explanation, "non-uniform-control-flow"),
+                        None => (error_chain(err.as_inner()), validation_code(err.as_inner())),
+                    };
+                    diags.push(error_diagnostic(
+                        uri,
+                        &document.text,
+                        &map,
+                        message,
+                        code,
+                        spans,
+                    ));
+                }
+            }
+            if diags.is_empty() && document.language == Language::Spirv {
+                // a binary has nothing to underline, so confirm it is fine instead
+                let diag = Diagnostic {
+                    severity: Some(lsp_types::DiagnosticSeverity::INFORMATION),
+                    source: Some(SOURCE.to_string()),
+                    message: format!(
+                        "SPIR-V module is valid, with {} entry points",
+                        module.entry_points.len()
+                    ),
+                    ..Default::default()
+                };
+                diags.push((uri.clone(), diag));
+            }
+            document.module = Some(Arc::new(module));
+        }
+        Err(errors) => diags = errors,
+    }
+    if let Some(ref module) = document.module {
+        diags.extend(samplers::check(
+            uri,
+            &document.text,
+            &map,
+            module,
+            document.info.as_deref(),
+            SOURCE,
+        ));
+    }
+    if map.includes_files() {
+        document.source_map = Some(map);
+    }
+    // the source-level checks all look the text up in the same index
+    let index = SourceIndex::new(&document.text);
+    // a module that failed validation may not have a workgroup size worth checking
+    if let Some(ref module) = document.module {
+        if document.info.is_some() || !config.validation {
+            diags.extend(own(workgroups::check(
+                &document.text,
+                &index,
+                module,
+                &config.workgroup_limits,
+                SOURCE,
+            )));
+        }
+    }
+    if document.language == Language::Wgsl {
+        diags.extend(own(enables::check(
+            &document.text,
+            config.allowed_enables.as_deref(),
+            SOURCE,
+        )));
+        diags.extend(own(overrides::check(uri, &document.text, &index, SOURCE)));
+        diags.extend(own(overflow::check(&document.text, &index, SOURCE)));
+        // naga rejects recursion itself when it stops the parse, so it is only reported
+        // once
+        let recursion = NumberOrString::String("recursion".to_string());
+        if !diags
+            .iter()
+            .any(|(_, diag)| diag.code.as_ref() == Some(&recursion))
+        {
+            diags.extend(own(recursion::check(uri, &document.text, &index, SOURCE)));
+        }
+        diags.extend(own(swizzles::check(document, &index, SOURCE)));
+        diags.extend(own(undeclared::check(uri, &document.text, &index, SOURCE)));
+        diags.extend(own(access::check(uri, &document.text, &index, SOURCE)));
+    }
+    if let (Language::Wgsl, Some(module), Some(info)) =
+        (document.language, &document.module, &document.info)
+    {
+        if let Some(stage) = stages::assumed(&document.text, config) {
+            diags.extend(own(stages::check(
+                &document.text,
+                &index,
+                module,
+                info,
+                stage,
+                SOURCE,
+            )));
+        }
+    }
+    if let (Language::Wgsl, Some(module)) = (document.language, &document.module) {
+        diags.extend(own(returns::missing_returns(
+            &document.text,
+            &index,
+            module,
+            SOURCE,
+        )));
+        diags.extend(own(locations::collisions(
+            uri,
+            &document.text,
+            &index,
+            module,
+            SOURCE,
+        )));
+    }
+    // only lint code that parses, as half typed code is full of unused names
+    if config.lints && document.language == Language::Wgsl && document.module.is_some() {
+        diags.extend(own(lints::lint(&document.text, &index, SOURCE)));
+    }
+    diags
+}
+
+/// The diagnostics of a document for clients that pull them: unchanged if the client
+/// already has those of `previous_result_id`, and otherwise in full, along with those of
+/// the files it includes.
+pub fn report(
+    document: Option<&Document>,
+    previous_result_id: Option<&str>,
+) -> DocumentDiagnosticReportResult {
+    let document = match document {
+        Some(document) => document,
+        None => {
+            return DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+                Default::default(),
+            ))
+        }
+    };
+    let result_id = document.diagnostics_id.to_string();
+    if previous_result_id == Some(result_id.as_str()) {
+        return DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(
+            RelatedUnchangedDocumentDiagnosticReport {
+                related_documents: None,
+                unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                    result_id,
+                },
+            },
+        ));
+    }
+
+    let mut files = document.diagnostics.iter();
+    let items = files
+        .next()
+        .map_or_else(Vec::new, |(_, diags)| diags.clone());
+    let related_documents = files
+        .map(|(file, diags)| {
+            let report = FullDocumentDiagnosticReport {
+                result_id: None,
+                items: diags.clone(),
+            };
+            (file.clone(), DocumentDiagnosticReportKind::Full(report))
+        })
+        .collect::<HashMap<_, _>>();
+    DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+        RelatedFullDocumentDiagnosticReport {
+            related_documents: Some(related_documents).filter(|related| !related.is_empty()),
+            full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                result_id: Some(result_id),
+                items,
+            },
+        },
+    ))
+}
+
+/// A hash of the tokens of `text` other than comments, which stays the same while only
+/// comments or whitespace change.
+fn code_fingerprint(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for token in tokenize(text) {
+        if token.kind != TokenKind::Comment {
+            token.text(text).hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+/// Parses `source`, which is `text` with its includes expanded as `map` describes.
+fn parse_wgsl(
+    uri: &Url,
+    text: &str,
+    source: &str,
+    map: &SourceMap,
+) -> Result<naga::Module, Vec<(Url, Diagnostic)>> {
+    wgsl::parse_str(source).map_err(|err| {
+        log::debug!("compile error: {:?}", err);
+        let message = err.to_string();
+        let code = parse_code(&message);
+        vec![error_diagnostic(
+            uri,
+            text,
+            map,
+            message,
+            code,
+            err.labels(),
+        )]
+    })
+}
+
+/// Parses GLSL as the given stage, or as the stage its contents suggest if neither the
+/// file extension nor the document or settings said.
+fn parse_glsl(
+    uri: &Url,
+    text: &str,
+    stage: Option<naga::ShaderStage>,
+) -> Result<naga::Module, Vec<(Url, Diagnostic)>> {
+    let stage = stage.unwrap_or_else(|| guess_glsl_stage(text));
+    glsl::Frontend::default()
+        .parse(&glsl::Options::from(stage), text)
+        .map_err(|errors| {
+            log::debug!("compile errors: {:?}", errors);
+            errors
+                .iter()
+                .map(|err| {
+                    let labels = std::iter::once((err.meta, ""));
+                    let map = SourceMap::identity(text.len());
+                    let message = err.kind.to_string();
+                    error_diagnostic(uri, text, &map, message, "glsl-error", labels)
+                })
+                .collect()
+        })
+}
+
+/// Parses the SPIR-V binary the uri points to.
+fn parse_spirv(uri: &Url) -> Result<naga::Module, Vec<(Url, Diagnostic)>> {
+    let error = |message: String| {
+        let diag = Diagnostic {
+            severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String("spirv-error".to_string())),
+            source: Some(SOURCE.to_string()),
+            message,
+            ..Default::default()
+        };
+        vec![(uri.clone(), diag)]
+    };
+    let path = uri
+        .to_file_path()
+        .map_err(|()| error(format!("{} is not a file", uri)))?;
+    let bytes = std::fs::read(&path)
+        .map_err(|err| error(format!("failed to read {}: {}", path.display(), err)))?;
+    spv::parse_u8_slice(&bytes, &spv::Options::default()).map_err(|err| {
+        log::debug!("compile error: {:?}", err);
+        error(format!("invalid SPIR-V: {}", err))
+    })
+}
+
+fn guess_glsl_stage(text: &str) -> naga::ShaderStage {
+    if text.contains("local_size_x") {
+        naga::ShaderStage::Compute
+    } else if text.contains("gl_Position") {
+        naga::ShaderStage::Vertex
+    } else {
+        naga::ShaderStage::Fragment
+    }
+}
+
+/// Drops diagnostics repeating an earlier one's range and message, and replaces those
+/// past `max` with a note saying how many were left out.
+fn limit(diags: Vec<Diagnostic>, max: usize) -> Vec<Diagnostic> {
+    let mut unique: Vec<Diagnostic> = Vec::with_capacity(diags.len());
+    for diag in diags {
+        if !unique
+            .iter()
+            .any(|other| other.range == diag.range && other.message == diag.message)
+        {
+            unique.push(diag);
+        }
+    }
+    if unique.len() <= max {
+        return unique;
+    }
+
+    let suppressed = unique.len() - max;
+    unique.truncate(max);
+    let range = unique
+        .last()
+        .map_or_else(lsp_types::Range::default, |last| last.range);
+    unique.push(Diagnostic {
+        range,
+        severity: Some(lsp_types::DiagnosticSeverity::INFORMATION),
+        code: Some(NumberOrString::String("too-many-errors".to_string())),
+        source: Some(SOURCE.to_string()),
+        message: format!("{} more diagnostics not shown", suppressed),
+        ..Default::default()
+    });
+    unique
+}
+
+thread_local! {
+    /// The validator of each thread, kept between validations so that its scratch buffers
+    /// are reused, along with the flags and capabilities it was made for. Each worker has
+    /// its own so that documents validate in parallel.
+    static VALIDATOR: RefCell<Option<(ValidationFlags, Capabilities, Validator)>> =
+        const { RefCell::new(None) };
+}
+
+/// The info of a module that validated, or why it didn't.
+type Validation = Result<Arc<ModuleInfo>, WithSpan<ValidationError>>;
+
+/// Validates `module` with this thread's validator, replacing it first if it was made
+/// for other flags or capabilities. `None` if naga panicked.
+fn validate(
+    module: &naga::Module,
+    flags: ValidationFlags,
+    capabilities: Capabilities,
+) -> Option<Validation> {
+    VALIDATOR.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        if cached.as_ref().is_some_and(|(made_with, made_for, _)| {
+            *made_with != flags || *made_for != capabilities
+        }) {
+            *cached = None;
+        }
+        let (_, _, validator) = cached
+            .get_or_insert_with(|| (flags, capabilities, Validator::new(flags, capabilities)));
+        let validation = catch_panic(|| validator.validate(module).map(Arc::new));
+        if validation.is_none() {
+            // whatever state the panic left it in isn't worth keeping
+            *cached = None;
+        }
+        validation
+    })
+}
+
+/// Validates `module` like `validate` does, but if `earlier` has the info and fingerprints
+/// of its last validation, only validates again the functions an edit could have changed,
+/// along with how many that was.
+fn validate_changes(
+    module: &naga::Module,
+    capabilities: Capabilities,
+    current: Option<&Fingerprints>,
+    earlier: Option<(Arc<ModuleInfo>, Fingerprints)>,
+) -> Option<(Validation, Option<usize>)> {
+    let flags = ValidationFlags::all();
+    if let (Some(current), Some((info, previous))) = (current, earlier) {
+        if let Some(reduced) = incremental::reduce(module, current, &previous) {
+            let validated = Some(reduced.validated());
+            match validate(&reduced.module, flags, capabilities)? {
+                Ok(fresh) => {
+                    if let Some(spliced) = reduced.splice(&fresh, &info) {
+                        return Some((Ok(Arc::new(spliced)), validated));
+                    }
+                }
+                Err(err) => return Some((Err(reduced.restore_handles(err)), validated)),
+            }
+        }
+    }
+    validate(module, flags, capabilities).map(|validation| (validation, None))
+}
+
+/// Runs `f`, along with how long it took unless built for the browser, which has no
+/// clock `Instant` can read.
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Option<Duration>) {
+    #[cfg(not(target_arch = "wasm32"))]
+    let start = Instant::now();
+    let value = f();
+    #[cfg(not(target_arch = "wasm32"))]
+    let elapsed = Some(start.elapsed());
+    #[cfg(target_arch = "wasm32")]
+    let elapsed = None;
+    (value, elapsed)
+}
+
+/// Runs `f`, catching and logging any panic.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => Some(value),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            log::error!("naga panicked: {}", message);
+            None
+        }
+    }
+}
+
+fn internal_error() -> Diagnostic {
+    Diagnostic {
+        range: lsp_types::Range::default(),
+        severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String("internal".to_string())),
+        source: Some(SOURCE.to_string()),
+        message: "internal parser error".to_string(),
+        ..Default::default()
+    }
+}
+
+/// An error's message followed by those of the errors that caused it, as naga's
+/// validation errors only say which item is invalid at the top level.
+pub fn error_chain(err: &dyn Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+/// The diagnostic code of a parse error. The WGSL front end only exposes the message, so
+/// this goes by its wording.
+fn parse_code(message: &str) -> &'static str {
+    const CODES: &[(&str, &str)] = &[
+        ("expected", "unexpected-token"),
+        ("unknown type", "unknown-type"),
+        ("unknown attribute", "unknown-attribute"),
+        ("no definition in scope", "unknown-identifier"),
+        ("redefinition", "redefinition"),
+        ("is recursive", "recursion"),
+        ("is cyclic", "recursion"),
+    ];
+    CODES
+        .iter()
+        .find(|(fragment, _)| message.contains(fragment))
+        .map_or("parse-error", |&(_, code)| code)
+}
+
+/// Extends `span` over the closing parentheses it leaves out: naga's spans of calls end
+/// at their last argument, which for a call over several lines leaves the line with the
+/// `)` out of the range.
+fn close_parens(source: &str, span: naga::Span) -> naga::Span {
+    let range = match span.to_range() {
+        Some(range) if range.end <= source.len() => range,
+        _ => return span,
+    };
+    let bytes = source.as_bytes();
+    let mut open = bytes[range.clone()]
+        .iter()
+        .fold(0i32, |open, &byte| match byte {
+            b'(' => open + 1,
+            b')' => open - 1,
+            _ => open,
+        });
+    let mut end = range.end;
+    while open > 0 && end < bytes.len() {
+        match bytes[end] {
+            b'(' => open += 1,
+            b')' => open -= 1,
+            _ => {}
+        }
+        end += 1;
+    }
+    if open == 0 {
+        naga::Span::new(range.start as u32, end as u32)
+    } else {
+        span
+    }
+}
+
+/// What a label naming a handle, such as `naga::Expression [2]`, points at, or `None`
+/// if the label is text of its own.
+fn handle_label(label: &str) -> Option<&'static str> {
+    let kind = label.strip_prefix("naga::")?.split(' ').next()?;
+    Some(match kind {
+        "Function" => "in this function",
+        "Expression" => "this expression",
+        "Type" => "this type",
+        "Constant" => "this constant",
+        "GlobalVariable" => "this global",
+        "LocalVariable" => "this local",
+        _ => "here",
+    })
+}
+
+/// The diagnostic code of a validation error, by the kind of item that is invalid.
+fn validation_code(err: &ValidationError) -> &'static str {
+    match *err {
+        ValidationError::Layouter(_) => "invalid-layout",
+        ValidationError::Type { .. } => "invalid-type",
+        ValidationError::Constant { .. } => "invalid-constant",
+        ValidationError::GlobalVariable { .. } => "invalid-global",
+        ValidationError::Function { .. } => "invalid-function",
+        ValidationError::EntryPoint { .. } => "invalid-entry-point",
+        _ => "validation-error",
+    }
+}
+
+/// Builds an error diagnostic from naga's labeled spans, along with the file it is in;
+/// the first label naga wrote words for, or else the innermost, is the primary location,
+/// whose text is added to the message, and the rest point at related code. The spans
+/// are mapped back to the files they came from through `map`.
+fn error_diagnostic<'a>(
+    uri: &Url,
+    text: &str,
+    map: &SourceMap,
+    message: String,
+    code: &str,
+    labels: impl Iterator<Item = (naga::Span, &'a str)>,
+) -> (Url, Diagnostic) {
+    let mut labels: Vec<_> = labels
+        .map(|(span, label)| (map.locate(uri, text, span), label))
+        .collect();
+    // the validator labels the function and every handle involved by their names; the
+    // first label saying something of its own is where the error is
+    let primary = labels
+        .iter()
+        .position(|&(_, label)| handle_label(label).is_none())
+        .or_else(|| labels.len().checked_sub(1));
+    let (file, range, message) = match primary.map(|primary| labels.remove(primary)) {
+        Some((location, label))
+            if !label.is_empty() && handle_label(label).is_none() && !message.contains(label) =>
+        {
+            (
+                location.uri,
+                location.range,
+                format!("{}: {}", message, label),
+            )
+        }
+        Some((location, _)) => (location.uri, location.range, message),
+        None => (uri.clone(), lsp_types::Range::default(), message),
+    };
+    let related_information = labels
+        .into_iter()
+        .map(|(location, label)| DiagnosticRelatedInformation {
+            location,
+            message: handle_label(label).unwrap_or(label).to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    let diag = Diagnostic {
+        range,
+        severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(code.to_string())),
+        code_description: None,
+        source: Some(SOURCE.to_string()),
+        message,
+        related_information: Some(related_information),
+        tags: None,
+        data: None,
+    };
+    (file, diag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnose_wgsl(text: &str) -> Vec<Diagnostic> {
+        check_source(text, Language::Wgsl, &Config::default())
+    }
+
+    #[test]
+    fn parse_error_after_emoji_counts_utf16_units() {
+        let diags = diagnose_wgsl("// 🦀 crab\nfn main() { let x = ; }");
+        let diag = diags.first().expect("a parse error");
+        assert_eq!(diag.range.start, lsp_types::Position::new(1, 20));
+
+        let diags = diagnose_wgsl("/* 🦀 */ fn main() { let x = ; }");
+        let diag = diags.first().expect("a parse error");
+        // the crab takes two UTF-16 units, not the four bytes it does in UTF-8
+        assert_eq!(diag.range.start, lsp_types::Position::new(0, 29));
+    }
+
+    #[test]
+    fn multi_line_call_range_covers_the_call() {
+        let diags = diagnose_wgsl(
+            "fn f(a: f32, b: f32) -> f32 { return a + b; }\n\
+             fn main() {\n    let x = f(\n        1.0,\n        2u,\n    );\n}\n",
+        );
+        let diag = diags
+            .iter()
+            .find(|diag| diag.code == Some(NumberOrString::String("invalid-function".into())))
+            .expect("a validation error");
+        assert_eq!(diag.range.start, lsp_types::Position::new(2, 12));
+        assert_eq!(diag.range.end, lsp_types::Position::new(5, 5));
+    }
+
+    /// Diagnoses `text` as the file `main.wgsl` in a directory of its own, next to the
+    /// file `common.wgsl` it includes.
+    fn diagnose_with_include(test: &str, text: &str, common: &str) -> Vec<(Url, Vec<Diagnostic>)> {
+        let dir = std::env::temp_dir().join(format!("naga-lsp-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("common.wgsl"), common).unwrap();
+        let uri = Url::from_file_path(dir.join("main.wgsl")).unwrap();
+        let config = Config {
+            include_directive: Some("//!include".to_string()),
+            ..Config::default()
+        };
+        let mut document = Document::new(text.to_string(), 0, Language::Wgsl);
+        let files = parse_document(&uri, &mut document, &config, true);
+        std::fs::remove_dir_all(&dir).unwrap();
+        files
+    }
+
+    #[test]
+    fn errors_after_an_include_map_to_the_document() {
+        let files = diagnose_with_include(
+            "document",
+            "//!include \"common.wgsl\"\nfn main() {\n    let x: u32 = helper();\n}\n",
+            "fn helper() -> f32 {\n    return 1.0;\n}\n",
+        );
+        let (file, diags) = &files[0];
+        assert!(file.path().ends_with("/main.wgsl"));
+        let diag = diags.first().expect("an error in the document");
+        assert_eq!(diag.range.start.line, 2);
+        assert!(files[1..].iter().all(|(_, diags)| diags.is_empty()));
+    }
+
+    #[test]
+    fn errors_in_an_include_map_to_the_included_file() {
+        let files = diagnose_with_include(
+            "include",
+            "//!include \"common.wgsl\"\nfn main() {\n    let x = helper();\n}\n",
+            "fn helper() -> f32 {\n    return 1.0 +;\n}\n",
+        );
+        assert!(files[0].1.is_empty());
+        let (_, diags) = files
+            .iter()
+            .find(|(file, _)| file.path().ends_with("/common.wgsl"))
+            .expect("diagnostics for the included file");
+        let diag = diags.first().expect("an error in the included file");
+        assert_eq!(diag.range.start, lsp_types::Position::new(1, 16));
+    }
+
+    #[test]
+    fn recursion_is_reported_once() {
+        let diags = diagnose_wgsl("fn f() { g(); }\nfn g() { f(); }\n");
+        assert_eq!(diags.len(), 1, "{:#?}", diags);
+        let recursion = NumberOrString::String("recursion".to_string());
+        assert_eq!(diags[0].code, Some(recursion));
+    }
+
+    #[test]
+    fn edits_only_validate_the_functions_they_change() {
+        let text = "fn helper() -> f32 { return 2.0; }\n\
+                    fn other() -> f32 { return 1.0; }\n\
+                    @fragment fn main() -> @location(0) vec4<f32> { return vec4(helper()); }\n";
+        let uri = Url::parse("untitled:shader").unwrap();
+        // `other` is never called
+        let config = Config {
+            lints: false,
+            ..Config::default()
+        };
+        let mut document = Document::new(text.to_string(), 0, Language::Wgsl);
+        parse_document(&uri, &mut document, &config, false);
+        assert_eq!(document.timings.validated_functions, None);
+
+        document.text = text.replace("2.0", "3.0");
+        let files = parse_document(&uri, &mut document, &config, false);
+        assert!(files[0].1.is_empty(), "{:#?}", files);
+        // `helper` and `main`, but not `other`
+        assert_eq!(document.timings.validated_functions, Some(2));
+
+        document.text = text.replace("2.0", "2u");
+        let files = parse_document(&uri, &mut document, &config, false);
+        assert_eq!(document.timings.validated_functions, Some(2));
+        assert!(files[0].1[0].message.contains("'helper'"), "{:#?}", files);
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use lsp_types::{TextDocumentContentChangeEvent, Url};
+
+use crate::includes::SourceMap;
+use crate::incremental::Fingerprints;
+use crate::position::position_to_byte_offset;
+
+/// The shading language of a document, which decides the frontend that parses it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    Wgsl,
+    /// GLSL, with the stage known from the file extension, such as `.frag`.
+    Glsl(Option<naga::ShaderStage>),
+    /// A SPIR-V binary. The client's text is meaningless, so it is read from disk.
+    Spirv,
+}
+
+impl Language {
+    /// Guesses the language from the uri's extension, falling back to the client's
+    /// language id.
+    pub fn detect(uri: &Url, language_id: Option<&str>) -> Self {
+        use naga::ShaderStage;
+
+        let extension = extension(uri);
+        match extension.as_deref() {
+            Some("vert") => Language::Glsl(Some(ShaderStage::Vertex)),
+            Some("frag") => Language::Glsl(Some(ShaderStage::Fragment)),
+            Some("comp") => Language::Glsl(Some(ShaderStage::Compute)),
+            Some("glsl") => Language::Glsl(None),
+            Some("wgsl") => Language::Wgsl,
+            Some("spv") => Language::Spirv,
+            _ if language_id == Some("glsl") => Language::Glsl(None),
+            _ => Language::Wgsl,
+        }
+    }
+
+    /// Whether the uri's extension or the client's language id is that of a shader we
+    /// can parse, as documents of other languages aren't worth analyzing.
+    pub fn is_shader(uri: &Url, language_id: Option<&str>) -> bool {
+        let extension = extension(uri);
+        let known_extension = matches!(
+            extension.as_deref(),
+            Some("vert") | Some("frag") | Some("comp") | Some("glsl") | Some("wgsl") | Some("spv")
+        );
+        known_extension || matches!(language_id, Some("wgsl") | Some("glsl"))
+    }
+}
+
+/// The extension of the uri's file name, in lowercase.
+fn extension(uri: &Url) -> Option<String> {
+    uri.path()
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_ascii_lowercase())
+}
+
+/// An open text document, as last synced from the client.
+/// Cloning one is cheap, as the module and its info are shared, which lets requests be
+/// answered from a snapshot of the documents.
+#[derive(Clone)]
+pub struct Document {
+    pub text: String,
+    pub version: i32,
+    pub language: Language,
+    /// The module from the last successful parse of `text`, if any, by the frontend for
+    /// `language`. Like `info`, it is kept while the changed text waits to be reparsed,
+    /// so that the requests in between are answered from it rather than not at all.
+    pub module: Option<Arc<naga::Module>>,
+    /// The validation info for `module`, if it validated. It is kept while the changed
+    /// text waits to be reparsed, so that it can be reused if only comments and
+    /// whitespace changed.
+    pub info: Option<Arc<naga::valid::ModuleInfo>>,
+    /// The fingerprint of the code `info` was computed for, from `code_fingerprint`.
+    pub validated_code: Option<u64>,
+    /// The fingerprints of the module `info` was computed for, to only validate the
+    /// functions an edit changed again.
+    pub validated_functions: Option<Fingerprints>,
+    /// How the source naga parsed maps back to `text` and the files it includes, if it
+    /// included any.
+    pub source_map: Option<SourceMap>,
+    /// The included files we last published diagnostics for, to clear them once fixed.
+    pub diagnosed_includes: Vec<Url>,
+    /// The diagnostics of the last parse by file, kept for clients that pull them.
+    pub diagnostics: Vec<(Url, Vec<lsp_types::Diagnostic>)>,
+    /// Counts the changes to `diagnostics`, to tell clients pulling them whether they
+    /// changed since they last did.
+    pub diagnostics_id: u32,
+    /// The diagnostics of the checks run when the document was last saved.
+    pub saved_diagnostics: Vec<lsp_types::Diagnostic>,
+    /// When to reparse `text` and publish diagnostics, if it changed since the last parse.
+    pub reparse_at: Option<Instant>,
+    /// How long the last parse took.
+    pub timings: Timings,
+}
+
+/// How long the last parse and validation of a document took, where the clock could be
+/// read, for `naga-lsp.stats`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Timings {
+    pub parse: Option<Duration>,
+    /// `None` as well if the validation info of the previous parse was reused.
+    pub validation: Option<Duration>,
+    pub validation_reused: bool,
+    /// How many functions and entry points were validated, if only those an edit could
+    /// have changed were.
+    pub validated_functions: Option<usize>,
+}
+
+impl Document {
+    pub fn new(text: String, version: i32, language: Language) -> Self {
+        Document {
+            text,
+            version,
+            language,
+            module: None,
+            info: None,
+            validated_code: None,
+            validated_functions: None,
+            source_map: None,
+            diagnosed_includes: Vec::new(),
+            diagnostics: Vec::new(),
+            diagnostics_id: 0,
+            saved_diagnostics: Vec::new(),
+            reparse_at: None,
+            timings: Timings::default(),
+        }
+    }
+
+    /// Replaces the range of text the change covers, or all of it if it has no range.
+    fn apply_change(&mut self, change: TextDocumentContentChangeEvent) {
+        match change.range {
+            Some(range) => {
+                let start = position_to_byte_offset(&self.text, range.start);
+                let end = position_to_byte_offset(&self.text, range.end).max(start);
+                self.text.replace_range(start..end, &change.text);
+            }
+            None => self.text = change.text,
+        }
+    }
+}
+
+/// All documents the client currently has open, keyed by uri.
+///
+/// Cloning the store takes a snapshot of it that later changes leave alone, with each
+/// document only copied once one of them changes it.
+#[derive(Clone, Default)]
+pub struct DocumentStore {
+    documents: HashMap<Url, Arc<Document>>,
+}
+
+impl DocumentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn open(
+        &mut self,
+        uri: Url,
+        text: String,
+        version: i32,
+        language: Language,
+    ) -> &mut Document {
+        let document = Arc::new(Document::new(text, version, language));
+        let document = match self.documents.entry(uri) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(document);
+                entry.into_mut()
+            }
+            Entry::Vacant(entry) => entry.insert(document),
+        };
+        Arc::make_mut(document)
+    }
+
+    /// Applies edits to the text of a document, opening it if the client never sent
+    /// `didOpen` and it looks like a shader. The edits are applied in order, as the range
+    /// of each is relative to the text the previous ones left.
+    pub fn change(
+        &mut self,
+        uri: Url,
+        changes: Vec<TextDocumentContentChangeEvent>,
+        version: i32,
+    ) -> Option<&mut Document> {
+        let document = match self.documents.entry(uri) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) if !Language::is_shader(entry.key(), None) => return None,
+            Entry::Vacant(entry) => {
+                let language = Language::detect(entry.key(), None);
+                entry.insert(Arc::new(Document::new(String::new(), version, language)))
+            }
+        };
+        let document = Arc::make_mut(document);
+        for change in changes {
+            document.apply_change(change);
+        }
+        document.version = version;
+        Some(document)
+    }
+
+    pub fn close(&mut self, uri: &Url) -> Option<Arc<Document>> {
+        self.documents.remove(uri)
+    }
+
+    pub fn get(&self, uri: &Url) -> Option<&Document> {
+        self.documents.get(uri).map(|document| &**document)
+    }
+
+    pub fn get_mut(&mut self, uri: &Url) -> Option<&mut Document> {
+        self.documents.get_mut(uri).map(Arc::make_mut)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Url, &Document)> {
+        self.documents
+            .iter()
+            .map(|(uri, document)| (uri, &**document))
+    }
+
+    /// The earliest time any document is due to be reparsed.
+    pub fn next_reparse(&self) -> Option<Instant> {
+        self.documents
+            .values()
+            .filter_map(|document| document.reparse_at)
+            .min()
+    }
+
+    /// Schedules every document to be reparsed and validated from scratch at `at`, as
+    /// needed after the settings changed.
+    pub fn reparse_all(&mut self, at: Instant) {
+        for document in self.documents.values_mut().map(Arc::make_mut) {
+            document.validated_code = None;
+            document.validated_functions = None;
+            document.reparse_at = Some(at);
+        }
+    }
+
+    /// The documents due to be reparsed by `now`.
+    pub fn due_for_reparse(&self, now: Instant) -> Vec<Url> {
+        self.documents
+            .iter()
+            .filter(|(_, document)| document.reparse_at.is_some_and(|due| due <= now))
+            .map(|(uri, _)| uri.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn changes_apply_to_the_text_the_previous_ones_left() {
+        let uri = Url::parse("file:///shader.wgsl").unwrap();
+        let mut store = DocumentStore::new();
+        store.open(
+            uri.clone(),
+            "fn main() {\n}\n".to_string(),
+            1,
+            Language::Wgsl,
+        );
+        let changes = vec![
+            // the second edit's range is only right after the first added its line
+            edit((1, 0), (1, 0), "    let x = 1;\n"),
+            edit((2, 0), (2, 1), "} // main"),
+        ];
+        let document = store.change(uri, changes, 2).expect("an open document");
+        assert_eq!(document.text, "fn main() {\n    let x = 1;\n} // main\n");
+        assert_eq!(document.version, 2);
+    }
+
+    #[test]
+    fn changes_keep_the_module_until_the_reparse() {
+        let uri = Url::parse("file:///shader.wgsl").unwrap();
+        let mut store = DocumentStore::new();
+        let document = store.open(uri.clone(), "fn main() {}\n".to_string(), 1, Language::Wgsl);
+        document.module = Some(Arc::new(naga::Module::default()));
+        let changes = vec![edit((0, 12), (0, 12), " // main")];
+        let document = store.change(uri, changes, 2).expect("an open document");
+        assert!(document.module.is_some());
+    }
+}
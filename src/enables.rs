@@ -0,0 +1,157 @@
+//! `enable` directives, which turn on the WGSL extensions a shader uses, and which naga
+//! leaves to us.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use naga::valid::Capabilities;
+
+use crate::position::byte_range_to_range;
+use crate::syntax::{tokenize, TokenKind};
+
+/// The extensions WGSL defines, along with the names of the validator capabilities each
+/// needs, which our version of naga may not have.
+const EXTENSIONS: &[(&str, &[&str])] = &[
+    ("f16", &["SHADER_FLOAT16"]),
+    ("clip_distances", &["CLIP_DISTANCE"]),
+    ("dual_source_blending", &["DUAL_SOURCE_BLENDING"]),
+    ("primitive_index", &["PRIMITIVE_INDEX"]),
+    ("subgroups", &["SUBGROUP"]),
+];
+
+/// An extension named by an `enable` directive, with the span of its name.
+type Named<'a> = (&'a str, Range<usize>);
+
+/// The extensions named by the `enable` directives of `text`, along with the span of
+/// each whole directive.
+fn directives(text: &str) -> (Vec<Named<'_>>, Vec<Range<usize>>) {
+    let tokens: Vec<_> = tokenize(text)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Comment)
+        .collect();
+    let mut names = Vec::new();
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i].text(text) != "enable" {
+            i += 1;
+            continue;
+        }
+        let start = tokens[i].start;
+        i += 1;
+        while i < tokens.len() && tokens[i].text(text) != ";" {
+            if tokens[i].kind == TokenKind::Ident {
+                names.push((tokens[i].text(text), tokens[i].span()));
+            }
+            i += 1;
+        }
+        let end = tokens.get(i).map_or(text.len(), |semicolon| semicolon.end);
+        spans.push(start..end);
+    }
+    (names, spans)
+}
+
+/// Blanks out the `enable` directives of `source`, keeping every offset, as naga can't
+/// parse them.
+pub fn strip(source: Cow<str>) -> Cow<str> {
+    let (_, spans) = directives(&source);
+    if spans.is_empty() {
+        return source;
+    }
+    let mut bytes = source.into_owned().into_bytes();
+    for span in spans {
+        for byte in &mut bytes[span] {
+            // line breaks stay so that positions keep their line
+            if *byte != b'\n' && *byte != b'\r' {
+                *byte = b' ';
+            }
+        }
+    }
+    // only whole characters were replaced, by ASCII
+    Cow::Owned(String::from_utf8(bytes).unwrap_or_default())
+}
+
+/// The validator capabilities needed by the extensions `source` enables, out of those
+/// `allowed` if set.
+pub fn capabilities(source: &str, allowed: Option<&[String]>) -> Capabilities {
+    let (names, _) = directives(source);
+    names
+        .iter()
+        .filter(|(name, _)| allowed.is_none_or(|allowed| allowed.iter().any(|a| a == name)))
+        .filter_map(|(name, _)| EXTENSIONS.iter().find(|(known, _)| known == name))
+        .flat_map(|(_, capabilities)| capabilities.iter())
+        .filter_map(|capability| {
+            let found = Capabilities::from_name(capability);
+            if found.is_none() {
+                log::debug!("naga has no {} capability", capability);
+            }
+            found
+        })
+        .fold(Capabilities::empty(), |all, capability| all | capability)
+}
+
+/// Flags the extensions `text` enables that WGSL doesn't define, or that aren't in the
+/// `allowed` list if there is one.
+pub fn check(text: &str, allowed: Option<&[String]>, source: &str) -> Vec<Diagnostic> {
+    let (names, _) = directives(text);
+    names
+        .into_iter()
+        .filter_map(|(name, span)| {
+            let (severity, code, message) = if !EXTENSIONS.iter().any(|(known, _)| *known == name) {
+                (
+                    DiagnosticSeverity::WARNING,
+                    "unknown-enable",
+                    format!("unknown extension `{}`", name),
+                )
+            } else if allowed.is_some_and(|allowed| !allowed.iter().any(|a| a == name)) {
+                (
+                    DiagnosticSeverity::ERROR,
+                    "disallowed-enable",
+                    format!("extension `{}` is not in the allowedEnables setting", name),
+                )
+            } else {
+                return None;
+            };
+            Some(Diagnostic {
+                range: byte_range_to_range(text, span),
+                severity: Some(severity),
+                code: Some(NumberOrString::String(code.to_string())),
+                source: Some(source.to_string()),
+                message,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(text: &str, allowed: Option<&[String]>) -> Vec<NumberOrString> {
+        check(text, allowed, "test")
+            .into_iter()
+            .filter_map(|diag| diag.code)
+            .collect()
+    }
+
+    #[test]
+    fn flags_unknown_and_disallowed_extensions() {
+        let allowed = ["f16".to_string()];
+        let codes = codes("enable f16, subgroups;\nenable wave_ops;\n", Some(&allowed));
+        assert_eq!(
+            codes,
+            [
+                NumberOrString::String("disallowed-enable".to_string()),
+                NumberOrString::String("unknown-enable".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_known_extensions() {
+        assert!(codes("enable f16;\nfn main() {}\n", None).is_empty());
+    }
+}
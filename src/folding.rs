@@ -0,0 +1,67 @@
+use lsp_types::{FoldingRange, FoldingRangeKind};
+
+use crate::document::Document;
+use crate::position::byte_offset_to_position;
+use crate::syntax::{tokenize, SourceIndex, TokenKind};
+
+/// Folds every brace pair spanning several lines, which covers function bodies, structs
+/// and the blocks inside functions, as well as block comments and runs of line comments.
+pub fn folding_ranges(document: &Document) -> Vec<FoldingRange> {
+    let text = &document.text;
+    let line = |offset| byte_offset_to_position(text, offset).line;
+    let mut ranges = Vec::new();
+
+    let index = SourceIndex::new(text);
+    for (open, token) in index.tokens.iter().enumerate() {
+        if token.text(text) != "{" {
+            continue;
+        }
+        if let Some(close) = index.matching[open] {
+            // keep the closing brace visible
+            let start_line = line(token.start);
+            let end_line = line(index.tokens[close].start).saturating_sub(1);
+            if end_line > start_line {
+                ranges.push(folding_range(
+                    start_line,
+                    end_line,
+                    FoldingRangeKind::Region,
+                ));
+            }
+        }
+    }
+
+    let mut comments = tokenize(text)
+        .into_iter()
+        .filter(|token| token.kind == TokenKind::Comment)
+        .map(|token| (line(token.start), line(token.end)))
+        .peekable();
+    while let Some((start_line, mut end_line)) = comments.next() {
+        // merge comments on consecutive lines
+        while let Some(&(next_start, next_end)) = comments.peek() {
+            if next_start != end_line + 1 {
+                break;
+            }
+            end_line = next_end;
+            comments.next();
+        }
+        if end_line > start_line {
+            ranges.push(folding_range(
+                start_line,
+                end_line,
+                FoldingRangeKind::Comment,
+            ));
+        }
+    }
+    ranges
+}
+
+fn folding_range(start_line: u32, end_line: u32, kind: FoldingRangeKind) -> FoldingRange {
+    FoldingRange {
+        start_line,
+        start_character: None,
+        end_line,
+        end_character: None,
+        kind: Some(kind),
+        collapsed_text: None,
+    }
+}
@@ -0,0 +1,466 @@
+use lsp_types::{FormattingOptions, Position, Range, TextEdit};
+
+use naga::back::wgsl;
+
+use crate::commands::validated;
+use crate::config::{BraceStyle, FormatStyle};
+use crate::document::{Document, Language};
+use crate::position::{byte_offset_to_position, byte_range_to_range, position_to_byte_offset};
+use crate::syntax::{tokenize, SourceIndex, Token, TokenKind};
+
+/// Reformats the document in `style`, or else by writing its module back out, as
+/// `textDocument/formatting` does.
+pub fn format_document(
+    document: &Document,
+    style: Option<&FormatStyle>,
+    options: Option<&FormattingOptions>,
+) -> Vec<TextEdit> {
+    match style {
+        Some(style) => format_with_style(document, style, options)
+            .into_iter()
+            .collect(),
+        None => format(document),
+    }
+}
+
+/// Formats the document as it is about to be saved the way `format_document` does, but
+/// only if its current text parses, so that code being written isn't mangled. The
+/// pending reparse of the document is done before, so its module is of that text.
+pub fn on_save(document: &Document, style: Option<&FormatStyle>) -> Vec<TextEdit> {
+    if document.module.is_none() {
+        return Vec::new();
+    }
+    format_document(document, style, None)
+}
+
+/// Reformats the document by writing its module back out as WGSL.
+///
+/// The round trip through naga loses comments and would inline included files, so
+/// documents with either are left alone, as are documents that don't parse and GLSL
+/// documents.
+pub fn format(document: &Document) -> Vec<TextEdit> {
+    let text = &document.text;
+    if document.language != Language::Wgsl || document.source_map.is_some() {
+        return Vec::new();
+    }
+    if tokenize(text)
+        .iter()
+        .any(|token| token.kind == TokenKind::Comment)
+    {
+        return Vec::new();
+    }
+    let (module, info) = match validated(document) {
+        Ok(validated) => validated,
+        Err(_) => return Vec::new(),
+    };
+    let formatted = match wgsl::write_string(module, info, wgsl::WriterFlags::empty()) {
+        Ok(formatted) => formatted,
+        Err(err) => {
+            log::warn!("WGSL backend error: {}", err);
+            return Vec::new();
+        }
+    };
+    if formatted == *text {
+        return Vec::new();
+    }
+    vec![TextEdit {
+        range: Range {
+            start: Position::new(0, 0),
+            end: byte_offset_to_position(text, text.len()),
+        },
+        new_text: formatted,
+    }]
+}
+
+/// Reformats the whole document from its tokens alone in `style`, which unlike `format`
+/// keeps comments and works on documents that don't parse. Lines are laid out as
+/// `format_range` describes, braces are moved to match the style, and lines too long
+/// are broken up. `options` gives the indentation if the style doesn't.
+pub fn format_with_style(
+    document: &Document,
+    style: &FormatStyle,
+    options: Option<&FormattingOptions>,
+) -> Option<TextEdit> {
+    let text = &document.text;
+    if document.language != Language::Wgsl {
+        return None;
+    }
+    let tokens = tokenize(text);
+    let unit = match (style.indent_width, options) {
+        (Some(width), _) => " ".repeat(width as usize),
+        (None, Some(options)) => indent_unit(options),
+        (None, None) => " ".repeat(4),
+    };
+
+    // the tokens of each line to write, or the line as it was if it is in a comment
+    let mut lines: Vec<(Vec<Token>, Option<&str>)> = Vec::new();
+    for line in source_lines(text, &tokens) {
+        let mut on_line = tokens[line.tokens].to_vec();
+        if line.verbatim {
+            lines.push((on_line, Some(line.original)));
+            continue;
+        }
+        let opens_block = |tokens: &[Token]| tokens.last().map(|last| last.text(text)) == Some("{");
+        match style.brace_style {
+            BraceStyle::NextLine if on_line.len() > 1 && opens_block(&on_line) => {
+                let open = on_line.split_off(on_line.len() - 1);
+                lines.push((on_line, None));
+                lines.push((open, None));
+            }
+            BraceStyle::SameLine if on_line.len() == 1 && opens_block(&on_line) => {
+                match lines.last_mut() {
+                    Some((previous, None))
+                        if previous
+                            .last()
+                            .is_some_and(|last| last.kind != TokenKind::Comment) =>
+                    {
+                        previous.extend(on_line)
+                    }
+                    _ => lines.push((on_line, None)),
+                }
+            }
+            _ => lines.push((on_line, None)),
+        }
+    }
+
+    let mut formatted = String::new();
+    let mut depth = 0usize;
+    for (on_line, verbatim) in &lines {
+        match (verbatim, on_line.first()) {
+            (Some(original), _) => formatted.push_str(original),
+            (None, Some(first)) => {
+                let closing = first.text(text) == "}";
+                let depth = depth.saturating_sub(closing as usize);
+                write_line(
+                    text,
+                    on_line,
+                    depth,
+                    &unit,
+                    style.max_line_length,
+                    &mut formatted,
+                );
+            }
+            (None, None) => {}
+        }
+        formatted.push('\n');
+        for token in on_line {
+            match token.text(text) {
+                "{" => depth += 1,
+                "}" => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+    // every line got a line break, while the last one had none
+    formatted.pop();
+
+    Some(TextEdit {
+        range: Range {
+            start: Position::new(0, 0),
+            end: byte_offset_to_position(text, text.len()),
+        },
+        new_text: formatted,
+    })
+}
+
+/// Writes a line of tokens indented `depth` levels. A line longer than `max` is broken
+/// after the opening bracket of its first bracketed list, with each item on a line one
+/// level deeper and the closing bracket starting the last line, each of which is broken
+/// up the same way if still too long.
+fn write_line(
+    text: &str,
+    tokens: &[Token],
+    depth: usize,
+    unit: &str,
+    max: Option<usize>,
+    out: &mut String,
+) {
+    let line = format!("{}{}", unit.repeat(depth), join_tokens(text, tokens));
+    let too_long = max.is_some_and(|max| line.chars().count() > max);
+    let (open, close, commas) = match first_list(text, tokens) {
+        Some(list) if too_long => list,
+        _ => {
+            out.push_str(&line);
+            return;
+        }
+    };
+    write_line(text, &tokens[..=open], depth, unit, max, out);
+    let mut start = open + 1;
+    for end in commas.into_iter().map(|comma| comma + 1).chain(Some(close)) {
+        if start < end {
+            out.push('\n');
+            write_line(text, &tokens[start..end], depth + 1, unit, max, out);
+        }
+        start = end;
+    }
+    out.push('\n');
+    write_line(text, &tokens[close..], depth, unit, max, out);
+}
+
+/// The first pair of parentheses or square brackets on a line that holds anything,
+/// along with the commas separating its items.
+fn first_list(text: &str, tokens: &[Token]) -> Option<(usize, usize, Vec<usize>)> {
+    let mut open = Vec::new();
+    let mut commas = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        match token.text(text) {
+            "(" | "[" => open.push(i),
+            ")" | "]" => {
+                let start = open.pop()?;
+                if open.is_empty() {
+                    if i > start + 1 {
+                        return Some((start, i, commas));
+                    }
+                    commas.clear();
+                }
+            }
+            "," if open.len() == 1 => commas.push(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// A line of the source along with the tokens starting on it.
+struct SourceLine<'a> {
+    start: usize,
+    /// The line without its line break.
+    original: &'a str,
+    tokens: std::ops::Range<usize>,
+    /// Whether the line is part of a comment spanning several lines, and so to be kept
+    /// as it is.
+    verbatim: bool,
+}
+
+fn source_lines<'a>(text: &'a str, tokens: &[Token]) -> Vec<SourceLine<'a>> {
+    let mut lines = Vec::new();
+    let mut next = 0;
+    let mut line_start = 0;
+    for content in text.split('\n') {
+        let line_end = line_start + content.len();
+        let inside_comment = next > 0 && tokens[next - 1].end > line_start;
+        let first = next;
+        while next < tokens.len() && tokens[next].start < line_end {
+            next += 1;
+        }
+        let starts_comment = next > first && tokens[next - 1].end > line_end;
+        lines.push(SourceLine {
+            start: line_start,
+            original: content.trim_end_matches('\r'),
+            tokens: first..next,
+            verbatim: inside_comment || starts_comment,
+        });
+        line_start = line_end + 1;
+    }
+    lines
+}
+
+/// Formats the lines overlapping `range` from their tokens alone, leaving the rest of
+/// the document untouched: lines are indented by how many blocks they are in and runs of
+/// whitespace between tokens are collapsed to a single space, with none inside brackets
+/// or before separators. Comments are kept, and lines inside a block comment are left as
+/// they are.
+pub fn format_range(
+    document: &Document,
+    range: Range,
+    options: &FormattingOptions,
+) -> Vec<TextEdit> {
+    let text = &document.text;
+    if document.language != Language::Wgsl {
+        return Vec::new();
+    }
+    // a selection of whole lines ends at the start of the next one
+    let last_line = if range.end.character == 0 && range.end.line > range.start.line {
+        range.end.line - 1
+    } else {
+        range.end.line
+    };
+    let tokens = tokenize(text);
+    let unit = indent_unit(options);
+    let mut edits = Vec::new();
+    let mut depth = 0usize;
+    for (line, source) in source_lines(text, &tokens).into_iter().enumerate() {
+        let on_line = &tokens[source.tokens];
+        let line = line as u32;
+        if range.start.line <= line && line <= last_line && !source.verbatim {
+            let original = source.original;
+            let formatted = match on_line.first() {
+                Some(first) => {
+                    let closing = first.text(text) == "}";
+                    let depth = depth.saturating_sub(closing as usize);
+                    format!("{}{}", unit.repeat(depth), join_tokens(text, on_line))
+                }
+                None => String::new(),
+            };
+            if formatted != original {
+                let start = source.start;
+                edits.push(TextEdit {
+                    range: byte_range_to_range(text, start..start + original.len()),
+                    new_text: formatted,
+                });
+            }
+        }
+        for token in on_line {
+            match token.text(text) {
+                "{" => depth += 1,
+                "}" => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+    edits
+}
+
+/// The tokens of a line separated as `format_range` describes.
+fn join_tokens(text: &str, tokens: &[Token]) -> String {
+    let mut line = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let word = token.text(text);
+        if i > 0 {
+            let previous = &tokens[i - 1];
+            let spaced = match (previous.text(text), word) {
+                (_, "," | ";" | ")" | "]" | ".") | ("(" | "[" | "." | "@", _) => false,
+                (",", _) => true,
+                _ => previous.end < token.start,
+            };
+            if spaced {
+                line.push(' ');
+            }
+        }
+        line.push_str(word);
+    }
+    line
+}
+
+fn indent_unit(options: &FormattingOptions) -> String {
+    if options.insert_spaces {
+        " ".repeat(options.tab_size as usize)
+    } else {
+        "\t".to_string()
+    }
+}
+
+/// Re-indents the line being typed on: a `}` that starts a line is lined up with the line
+/// holding its `{`, and a new line is indented one level deeper than its enclosing block.
+pub fn on_type(
+    document: &Document,
+    position: Position,
+    ch: &str,
+    options: &FormattingOptions,
+) -> Vec<TextEdit> {
+    let text = &document.text;
+    if document.language == Language::Spirv {
+        return Vec::new();
+    }
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let line_start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+    let indent_end = line_start + indentation(text, line_start).len();
+    let tokens = &index.tokens;
+
+    let indent = match ch {
+        "}" => {
+            let close = match tokens
+                .iter()
+                .position(|token| token.end == offset && token.text(text) == "}")
+            {
+                Some(close) if tokens[close].start == indent_end => close,
+                _ => return Vec::new(),
+            };
+            match index.matching[close] {
+                Some(open) => indentation(text, tokens[open].start).to_string(),
+                None => return Vec::new(),
+            }
+        }
+        "\n" => {
+            // the innermost block still open at the cursor
+            let open = (0..tokens.len()).rev().find(|&open| {
+                tokens[open].start < offset
+                    && tokens[open].text(text) == "{"
+                    && index.matching[open].is_none_or(|close| tokens[close].start >= offset)
+            });
+            let open = match open {
+                Some(open) => open,
+                None => return Vec::new(),
+            };
+            let mut indent = indentation(text, tokens[open].start).to_string();
+            if !text[indent_end..].starts_with('}') {
+                indent.push_str(&indent_unit(options));
+            }
+            indent
+        }
+        _ => return Vec::new(),
+    };
+    if text[line_start..indent_end] == indent {
+        return Vec::new();
+    }
+    vec![TextEdit {
+        range: byte_range_to_range(text, line_start..indent_end),
+        new_text: indent,
+    }]
+}
+
+/// The leading whitespace of the line `offset` is on.
+fn indentation(text: &str, offset: usize) -> &str {
+    let line_start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+    let line = &text[line_start..];
+    let end = line
+        .find(|c: char| c != ' ' && c != '\t')
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(text: &str, style: FormatStyle) -> String {
+        let document = Document::new(text.to_string(), 0, Language::Wgsl);
+        format_with_style(&document, &style, None)
+            .expect("WGSL is formatted")
+            .new_text
+    }
+
+    #[test]
+    fn indent_width_sets_the_spaces_per_block() {
+        let style = FormatStyle {
+            indent_width: Some(2),
+            ..FormatStyle::default()
+        };
+        assert_eq!(
+            formatted("fn main() {\nif true {\nlet x = 1;\n}\n}\n", style),
+            "fn main() {\n  if true {\n    let x = 1;\n  }\n}\n"
+        );
+    }
+
+    #[test]
+    fn brace_style_moves_opening_braces() {
+        let next_line = FormatStyle {
+            brace_style: BraceStyle::NextLine,
+            ..FormatStyle::default()
+        };
+        assert_eq!(
+            formatted("fn main() {\n    return;\n}\n", next_line),
+            "fn main()\n{\n    return;\n}\n"
+        );
+        assert_eq!(
+            formatted("fn main()\n{\n    return;\n}\n", FormatStyle::default()),
+            "fn main() {\n    return;\n}\n"
+        );
+    }
+
+    #[test]
+    fn max_line_length_breaks_up_arguments() {
+        let style = FormatStyle {
+            max_line_length: Some(30),
+            ..FormatStyle::default()
+        };
+        assert_eq!(
+            formatted(
+                "fn main() {\n    let v = vec3<f32>(1.0, 2.0, 3.0);\n}\n",
+                style
+            ),
+            "fn main() {\n    let v = vec3<f32>(\n        1.0,\n        2.0,\n        3.0\n    );\n}\n"
+        );
+    }
+}
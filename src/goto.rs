@@ -0,0 +1,72 @@
+use lsp_types::request::GotoDeclarationResponse;
+use lsp_types::{GotoDefinitionResponse, Location, Position, Url};
+
+use crate::document::Document;
+use crate::position::{byte_range_to_range, position_to_byte_offset};
+use crate::resolve::{member_at, type_of};
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// Finds the declaration of the function, type, variable or struct member under the
+/// cursor. Builtins and unknown names have no declaration to go to.
+pub fn definition(uri: &Url, document: &Document, position: Position) -> GotoDefinitionResponse {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let locations = index
+        .declaration_at(offset)
+        .or_else(|| member_at(document, &index, offset))
+        .map(|decl| Location {
+            uri: uri.clone(),
+            range: byte_range_to_range(text, index.declarations[decl].selection.clone()),
+        })
+        .into_iter()
+        .collect();
+    GotoDefinitionResponse::Array(locations)
+}
+
+/// Finds the declaration of the name under the cursor. WGSL declares and defines every
+/// name in one place, used before or after it alike, so this goes where `definition`
+/// does; it is still answered on its own for clients that tell the two apart.
+pub fn declaration(uri: &Url, document: &Document, position: Position) -> GotoDeclarationResponse {
+    definition(uri, document, position)
+}
+
+/// Finds the declaration of the struct or alias that is the type of the variable, member
+/// or function under the cursor. Predeclared types have no declaration to go to.
+pub fn type_definition(
+    uri: &Url,
+    document: &Document,
+    position: Position,
+) -> GotoDefinitionResponse {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let ty = index
+        .declaration_at(offset)
+        .or_else(|| member_at(document, &index, offset))
+        .and_then(|decl| {
+            // prefer the type as written, which may name an alias naga has resolved away
+            index.declarations[decl]
+                .ty
+                .clone()
+                .or_else(|| type_of(document, &index, decl))
+        });
+    let locations = ty
+        .and_then(|ty| {
+            // the named type may be wrapped, as in `array<Light, 4>`
+            ty.split(|c: char| !c.is_alphanumeric() && c != '_')
+                .find_map(|word| {
+                    index.declarations.iter().find(|decl| {
+                        matches!(decl.kind, DeclarationKind::Struct | DeclarationKind::Alias)
+                            && decl.name == word
+                    })
+                })
+                .map(|decl| Location {
+                    uri: uri.clone(),
+                    range: byte_range_to_range(text, decl.selection.clone()),
+                })
+        })
+        .into_iter()
+        .collect();
+    GotoDefinitionResponse::Array(locations)
+}
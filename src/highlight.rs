@@ -0,0 +1,97 @@
+use lsp_types::{DocumentHighlight, DocumentHighlightKind, LinkedEditingRanges, Position};
+
+use crate::document::Document;
+use crate::position::{byte_range_to_range, position_to_byte_offset};
+use crate::references::occurrences;
+use crate::syntax::SourceIndex;
+
+/// Every occurrence of the declaration under the cursor, marking the declaration itself
+/// and assignments as writes.
+pub fn document_highlights(document: &Document, position: Position) -> Vec<DocumentHighlight> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let decl = match index.declaration_at(offset) {
+        Some(decl) => decl,
+        None => return Vec::new(),
+    };
+    let selection = index.declarations[decl].selection.clone();
+
+    occurrences(&index, decl, true)
+        .into_iter()
+        .map(|span| {
+            let kind = if span == selection || is_assigned(&index, text, span.start) {
+                DocumentHighlightKind::WRITE
+            } else {
+                DocumentHighlightKind::READ
+            };
+            DocumentHighlight {
+                range: byte_range_to_range(text, span),
+                kind: Some(kind),
+            }
+        })
+        .collect()
+}
+
+/// What a WGSL identifier looks like, for clients to stop editing the occurrences
+/// together once an edit makes them something else.
+const WORD_PATTERN: &str = "[a-zA-Z_][a-zA-Z0-9_]*";
+
+/// Every occurrence of the declaration under the cursor, for clients to edit as one.
+pub fn linked_editing_ranges(
+    document: &Document,
+    position: Position,
+) -> Option<LinkedEditingRanges> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let decl = index.declaration_at(offset)?;
+    let ranges = occurrences(&index, decl, true)
+        .into_iter()
+        .map(|span| byte_range_to_range(text, span))
+        .collect();
+    Some(LinkedEditingRanges {
+        ranges,
+        word_pattern: Some(WORD_PATTERN.to_string()),
+    })
+}
+
+/// Whether the identifier starting at `start`, or a member or element of it, is the left
+/// hand side of an assignment or increment.
+pub fn is_assigned(index: &SourceIndex, text: &str, start: usize) -> bool {
+    let mut i = match index.tokens.iter().position(|token| token.start == start) {
+        Some(i) => i + 1,
+        None => return false,
+    };
+    loop {
+        match index.tokens.get(i).map(|token| token.text(text)) {
+            Some(".") => i += 2,
+            Some("[") => match index.matching[i] {
+                Some(close) => i = close + 1,
+                None => return false,
+            },
+            _ => break,
+        }
+    }
+
+    // operators are split into single characters, so look at the adjacent ones
+    let mut operator = String::new();
+    let mut end = None;
+    for token in &index.tokens[i.min(index.tokens.len())..] {
+        if end.is_some_and(|end| end != token.start) || operator.len() == 3 {
+            break;
+        }
+        let token_text = token.text(text);
+        if !"=+-*/%&|^<>!".contains(token_text) {
+            break;
+        }
+        operator.push_str(token_text);
+        end = Some(token.end);
+    }
+    match operator.as_str() {
+        "=" | "++" | "--" | "+=" | "-=" | "*=" | "/=" | "%=" | "&=" | "|=" | "^=" | "<<="
+        | ">>=" => true,
+        // `a = -b` or `a = !b`
+        _ => operator.starts_with('=') && !operator.starts_with("=="),
+    }
+}
@@ -0,0 +1,104 @@
+use lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position};
+
+use naga::{Module, ShaderStage};
+
+use crate::builtins;
+use crate::commands::stage_name;
+use crate::document::Document;
+use crate::position::{byte_range_to_range, position_to_byte_offset};
+use crate::resolve::{find_function, type_of, FunctionRef};
+use crate::syntax::{DeclarationKind, SourceIndex, Token};
+use crate::types::type_name;
+
+pub fn hover(document: &Document, position: Position) -> Option<Hover> {
+    let text = &document.text;
+    let offset = position_to_byte_offset(text, position);
+    let index = SourceIndex::new(text);
+    let token = index.ident_at(offset)?;
+    let decl = match index.declaration_at(offset) {
+        Some(decl) => decl,
+        None => return builtin_hover(&index, text, token),
+    };
+    let declaration = &index.declarations[decl];
+    let signature = if declaration.kind == DeclarationKind::Function {
+        function_signature(document.module.as_ref()?, &declaration.name)?
+    } else if declaration.kind.is_local() || declaration.kind == DeclarationKind::Member {
+        let signature = match type_of(document, &index, decl) {
+            Some(ty) => format!("{}: {}", declaration.name, ty),
+            None => declaration.name.clone(),
+        };
+        match declaration.kind.keyword() {
+            "" => signature,
+            keyword => format!("{} {}", keyword, signature),
+        }
+    } else {
+        return None;
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("```wgsl\n{}\n```", signature),
+        }),
+        range: Some(byte_range_to_range(text, token.span())),
+    })
+}
+
+/// Documents the builtin function or attribute the token names, as it has no declaration.
+fn builtin_hover(index: &SourceIndex, text: &str, token: Token) -> Option<Hover> {
+    let name = token.text(text);
+    let position = index
+        .tokens
+        .iter()
+        .position(|other| other.start == token.start)?;
+    let at = position
+        .checked_sub(1)
+        .filter(|&before| index.tokens[before].text(text) == "@");
+    let (builtin, span) = match at {
+        Some(at) => (
+            builtins::find_attribute(name)?,
+            index.tokens[at].start..token.end,
+        ),
+        None => (builtins::find(name)?, token.span()),
+    };
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: builtins::markdown(builtin),
+        }),
+        range: Some(byte_range_to_range(text, span)),
+    })
+}
+
+/// The signature of a function as resolved by naga, preceded by its stage and workgroup
+/// size if it is an entry point.
+fn function_signature(module: &Module, name: &str) -> Option<String> {
+    let (function, body) = find_function(module, name)?;
+    let mut signature = String::new();
+    if let FunctionRef::EntryPoint(index) = function {
+        let entry_point = &module.entry_points[index];
+        signature.push_str(&format!("@{}", stage_name(entry_point.stage)));
+        if entry_point.stage == ShaderStage::Compute {
+            let [x, y, z] = entry_point.workgroup_size;
+            signature.push_str(&format!(" @workgroup_size({}, {}, {})", x, y, z));
+        }
+        signature.push('\n');
+    }
+    let arguments = body
+        .arguments
+        .iter()
+        .map(|argument| {
+            format!(
+                "{}: {}",
+                argument.name.as_deref().unwrap_or("_"),
+                type_name(module, argument.ty)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    signature.push_str(&format!("fn {}({})", name, arguments));
+    if let Some(ref result) = body.result {
+        signature.push_str(&format!(" -> {}", type_name(module, result.ty)));
+    }
+    Some(signature)
+}
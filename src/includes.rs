@@ -0,0 +1,231 @@
+//! Expansion of include directives such as `//!include "common.wgsl"`, which some
+//! projects use to share code between WGSL files, along with the map from the combined
+//! source back to the files it was put together from.
+
+use std::ops::Range;
+
+use lsp_types::{DocumentLink, Location, Url};
+
+use crate::position::byte_range_to_range;
+
+/// Where each part of an expanded source came from.
+#[derive(Clone, Debug)]
+pub struct SourceMap {
+    /// The uri and text of every included file.
+    files: Vec<(Url, String)>,
+    /// Consecutive parts of the combined source, in order.
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+struct Segment {
+    /// The range of the combined source.
+    combined: Range<usize>,
+    /// The included file it was copied from, or `None` for the document itself.
+    file: Option<usize>,
+    /// Where in that file it was copied from.
+    offset: usize,
+}
+
+impl SourceMap {
+    /// The map of a source that includes nothing.
+    pub fn identity(len: usize) -> Self {
+        SourceMap {
+            files: Vec::new(),
+            segments: vec![Segment {
+                combined: 0..len,
+                file: None,
+                offset: 0,
+            }],
+        }
+    }
+
+    /// Whether any file was included.
+    pub fn includes_files(&self) -> bool {
+        !self.files.is_empty()
+    }
+
+    /// Maps a span of the combined source back to the file and range it came from, `text`
+    /// being the document's own text. Spans naga couldn't attribute to any code map to
+    /// the start of the document.
+    pub fn locate(&self, uri: &Url, text: &str, span: naga::Span) -> Location {
+        let span = span.to_range().unwrap_or(0..0);
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| span.start < segment.combined.end)
+            .or_else(|| self.segments.last());
+        let segment = match segment {
+            Some(segment) => segment,
+            None => {
+                return Location {
+                    uri: uri.clone(),
+                    range: lsp_types::Range::default(),
+                }
+            }
+        };
+        // a span running past the segment is cut off at its end
+        let start = span
+            .start
+            .clamp(segment.combined.start, segment.combined.end);
+        let end = span.end.clamp(start, segment.combined.end);
+        let range = segment.offset + start - segment.combined.start
+            ..segment.offset + end - segment.combined.start;
+        match segment.file {
+            None => Location {
+                uri: uri.clone(),
+                range: byte_range_to_range(text, range),
+            },
+            Some(file) => {
+                let (ref file_uri, ref file_text) = self.files[file];
+                Location {
+                    uri: file_uri.clone(),
+                    range: byte_range_to_range(file_text, range),
+                }
+            }
+        }
+    }
+}
+
+/// An include that couldn't be expanded, with the range of the document's directive that
+/// led to it.
+pub struct IncludeError {
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+/// Replaces every line of `text` starting with `directive` and followed by a quoted path
+/// relative to the including file with the contents of that file, recursively. Each file
+/// is included at most once, and a file including itself is an error.
+pub fn expand(uri: &Url, text: &str, directive: &str) -> Result<(String, SourceMap), IncludeError> {
+    let mut expander = Expander {
+        directive,
+        combined: String::with_capacity(text.len()),
+        map: SourceMap {
+            files: Vec::new(),
+            segments: Vec::new(),
+        },
+        stack: vec![uri.clone()],
+    };
+    expander.expand(uri, text, None, None)?;
+    Ok((expander.combined, expander.map))
+}
+
+/// Links the quoted path of every include directive in `text` to the file it includes,
+/// whether or not that file exists.
+pub fn document_links(uri: &Url, text: &str, directive: &str) -> Vec<DocumentLink> {
+    let mut links = Vec::new();
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let start = line_start;
+        line_start += line.len();
+        let trimmed = line.trim_start();
+        let rest = match trimmed.strip_prefix(directive) {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let rest_start = start + (line.len() - trimmed.len()) + directive.len();
+        let quoted = rest.trim_start();
+        let path_start = rest_start + (rest.len() - quoted.len()) + 1;
+        let path = match quoted
+            .strip_prefix('"')
+            .and_then(|quoted| quoted.split_once('"'))
+        {
+            Some((path, _)) => path,
+            None => continue,
+        };
+        if let Ok(target) = uri.join(path) {
+            links.push(DocumentLink {
+                range: byte_range_to_range(text, path_start..path_start + path.len()),
+                target: Some(target),
+                tooltip: None,
+                data: None,
+            });
+        }
+    }
+    links
+}
+
+struct Expander<'a> {
+    directive: &'a str,
+    combined: String,
+    map: SourceMap,
+    /// The files being included, innermost last, to catch cycles.
+    stack: Vec<Url>,
+}
+
+impl Expander<'_> {
+    /// Appends `text`, the contents of `uri`, to the combined source with its includes
+    /// expanded. `file` is the index of `uri` in the map and `directive` the range of the
+    /// document's include that led here, both `None` for the document itself.
+    fn expand(
+        &mut self,
+        uri: &Url,
+        text: &str,
+        file: Option<usize>,
+        directive: Option<Range<usize>>,
+    ) -> Result<(), IncludeError> {
+        let mut copied = 0;
+        let mut line_start = 0;
+        for line in text.split_inclusive('\n') {
+            let content = line_start..line_start + line.trim_end_matches(&['\r', '\n'][..]).len();
+            line_start += line.len();
+            let path = match text[content.clone()]
+                .trim_start()
+                .strip_prefix(self.directive)
+            {
+                Some(rest) => rest.trim(),
+                None => continue,
+            };
+            let here = directive.clone().unwrap_or_else(|| content.clone());
+            let error = |message: String| IncludeError {
+                range: here.clone(),
+                message,
+            };
+            let path = path
+                .strip_prefix('"')
+                .and_then(|path| path.strip_suffix('"'))
+                .ok_or_else(|| error(format!("expected a quoted path after {}", self.directive)))?;
+            let included = uri
+                .join(path)
+                .map_err(|err| error(format!("invalid include path {}: {}", path, err)))?;
+            if self.stack.contains(&included) {
+                return Err(error(format!("{} includes itself", path)));
+            }
+
+            // the directive is replaced, but the line break after it kept
+            self.copy(text, copied..content.start, file);
+            copied = content.end;
+            if self.map.files.iter().any(|(uri, _)| *uri == included) {
+                continue;
+            }
+            let file_path = included
+                .to_file_path()
+                .map_err(|()| error(format!("{} is not a file", included)))?;
+            let contents = std::fs::read_to_string(&file_path)
+                .map_err(|err| error(format!("failed to read {}: {}", file_path.display(), err)))?;
+
+            let index = self.map.files.len();
+            self.map.files.push((included.clone(), String::new()));
+            self.stack.push(included.clone());
+            self.expand(&included, &contents, Some(index), Some(here.clone()))?;
+            self.stack.pop();
+            self.map.files[index].1 = contents;
+        }
+        self.copy(text, copied..text.len(), file);
+        Ok(())
+    }
+
+    fn copy(&mut self, text: &str, range: Range<usize>, file: Option<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let start = self.combined.len();
+        self.combined.push_str(&text[range.clone()]);
+        self.map.segments.push(Segment {
+            combined: start..self.combined.len(),
+            file,
+            offset: range.start,
+        });
+    }
+}
@@ -0,0 +1,437 @@
+//! Validation of only what an edit could have changed: the functions whose code changed
+//! and the functions and entry points calling them, directly or not.
+//!
+//! naga only validates whole modules, so those are validated in a module of their own
+//! along with the functions they call, and the info of every other function is carried
+//! over from the previous validation. naga has no way to put a `ModuleInfo` together
+//! either, so the infos are spliced through their serialized form.
+
+use std::collections::hash_map::{DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
+
+use naga::valid::{
+    Capabilities, EntryPointError, FunctionError, ModuleInfo, ValidationError, ValidationFlags,
+};
+use naga::{Block, Expression, Function, Handle, Module, Statement, WithSpan};
+use serde_json::Value;
+
+/// Fingerprints of the parts of a validated module, to tell which an edit changed.
+///
+/// Functions are fingerprinted by their serialized form, which leaves out their spans,
+/// so that functions which only moved keep theirs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprints {
+    /// Everything but the functions and entry points, along with the flags and
+    /// capabilities the module is validated with.
+    module: u64,
+    functions: Vec<u64>,
+    entry_points: Vec<u64>,
+}
+
+impl Fingerprints {
+    /// `None` if some function couldn't be serialized, which leaves only validating the
+    /// whole module.
+    pub fn of(module: &Module, flags: ValidationFlags, capabilities: Capabilities) -> Option<Self> {
+        let mut hasher = DefaultHasher::new();
+        // these hold no spans, so their debug output is only of the code
+        format!(
+            "{:?} {:?} {:?} {:?} {:?}",
+            module.types,
+            module.special_types,
+            module.constants,
+            module.global_variables,
+            module.const_expressions
+        )
+        .hash(&mut hasher);
+        flags.bits().hash(&mut hasher);
+        capabilities.bits().hash(&mut hasher);
+        let functions = module
+            .functions
+            .iter()
+            .map(|(_, function)| fingerprint(function))
+            .collect::<Option<_>>()?;
+        let entry_points = module
+            .entry_points
+            .iter()
+            .map(fingerprint)
+            .collect::<Option<_>>()?;
+        Some(Fingerprints {
+            module: hasher.finish(),
+            functions,
+            entry_points,
+        })
+    }
+}
+
+fn fingerprint(value: &impl serde::Serialize) -> Option<u64> {
+    let serialized = serde_json::to_vec(value)
+        .map_err(|err| log::debug!("can't fingerprint a function: {}", err))
+        .ok()?;
+    let mut hasher = DefaultHasher::new();
+    serialized.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// The functions and entry points of a module to validate again, in a module of their
+/// own, along with where the info of the others comes from.
+pub struct Reduced {
+    pub module: Module,
+    /// The handle in the full module of each function of the reduced one.
+    functions: Vec<Handle<Function>>,
+    /// The index in the full module of each entry point of the reduced one.
+    entry_points: Vec<usize>,
+    /// For each function of the full module, where its info is: in the reduced
+    /// validation or, if it wasn't validated again, in the previous one.
+    function_infos: Vec<Source>,
+    entry_point_infos: Vec<Source>,
+}
+
+#[derive(Clone, Copy)]
+enum Source {
+    Reduced(usize),
+    Previous(usize),
+}
+
+/// The part of `module` an edit could have changed since the module `previous` is of
+/// was validated, or `None` if the whole module has to be validated again, as the
+/// types, constants or globals changed or every function is affected.
+pub fn reduce(module: &Module, current: &Fingerprints, previous: &Fingerprints) -> Option<Reduced> {
+    if current.module != previous.module {
+        return None;
+    }
+    let indices = |fingerprints: &[u64]| -> HashMap<u64, usize> {
+        let pairs = fingerprints.iter().enumerate();
+        pairs
+            .map(|(index, &fingerprint)| (fingerprint, index))
+            .collect()
+    };
+    let previous_functions = indices(&previous.functions);
+    let previous_entry_points = indices(&previous.entry_points);
+    let unchanged: Vec<Option<usize>> = current
+        .functions
+        .iter()
+        .map(|fingerprint| previous_functions.get(fingerprint).copied())
+        .collect();
+
+    let callees: Vec<Vec<Handle<Function>>> = module
+        .functions
+        .iter()
+        .map(|(_, function)| calls(function))
+        .collect();
+    // callers come after their callees, but an invalid module may call ahead, so this
+    // goes on until nothing changes
+    let mut affected: Vec<bool> = unchanged.iter().map(Option::is_none).collect();
+    loop {
+        let mut spread = false;
+        for (index, callees) in callees.iter().enumerate() {
+            if !affected[index] && callees.iter().any(|callee| affected[callee.index()]) {
+                affected[index] = true;
+                spread = true;
+            }
+        }
+        if !spread {
+            break;
+        }
+    }
+    let entry_points: Vec<usize> = module
+        .entry_points
+        .iter()
+        .enumerate()
+        .filter(|&(index, entry_point)| {
+            !previous_entry_points.contains_key(&current.entry_points[index])
+                || calls(&entry_point.function)
+                    .iter()
+                    .any(|callee| affected[callee.index()])
+        })
+        .map(|(index, _)| index)
+        .collect();
+    if affected.iter().all(|&affected| affected) {
+        return None;
+    }
+
+    // the functions validated again need the functions they call
+    let mut needed = affected.clone();
+    let mut stack: Vec<Handle<Function>> = module
+        .functions
+        .iter()
+        .filter(|&(handle, _)| affected[handle.index()])
+        .map(|(handle, _)| handle)
+        .collect();
+    for &index in &entry_points {
+        stack.extend(calls(&module.entry_points[index].function));
+    }
+    while let Some(function) = stack.pop() {
+        for &callee in &callees[function.index()] {
+            if !needed[callee.index()] {
+                needed[callee.index()] = true;
+                stack.push(callee);
+            }
+        }
+        needed[function.index()] = true;
+    }
+
+    let mut reduced = Module {
+        types: module.types.clone(),
+        special_types: module.special_types.clone(),
+        constants: module.constants.clone(),
+        global_variables: module.global_variables.clone(),
+        const_expressions: module.const_expressions.clone(),
+        ..Module::default()
+    };
+    let mut functions = Vec::new();
+    let mut renumbered = vec![None; module.functions.len()];
+    for (handle, function) in module.functions.iter() {
+        if !needed[handle.index()] {
+            continue;
+        }
+        let mut function = function.clone();
+        renumber(&mut function, &renumbered)?;
+        let span = module.functions.get_span(handle);
+        renumbered[handle.index()] = Some(reduced.functions.append(function, span));
+        functions.push(handle);
+    }
+    for &index in &entry_points {
+        let mut entry_point = module.entry_points[index].clone();
+        renumber(&mut entry_point.function, &renumbered)?;
+        reduced.entry_points.push(entry_point);
+    }
+
+    let function_infos = (0..module.functions.len())
+        .map(
+            |index| match (affected[index], renumbered[index], unchanged[index]) {
+                (false, _, Some(previous)) => Source::Previous(previous),
+                (_, Some(handle), _) => Source::Reduced(handle.index()),
+                _ => unreachable!("a changed function is always validated again"),
+            },
+        )
+        .collect();
+    let entry_point_infos = (0..module.entry_points.len())
+        .map(
+            |index| match entry_points.iter().position(|&other| other == index) {
+                Some(reduced) => Source::Reduced(reduced),
+                None => Source::Previous(previous_entry_points[&current.entry_points[index]]),
+            },
+        )
+        .collect();
+    Some(Reduced {
+        module: reduced,
+        functions,
+        entry_points,
+        function_infos,
+        entry_point_infos,
+    })
+}
+
+impl Reduced {
+    /// How many functions and entry points are validated again.
+    pub fn validated(&self) -> usize {
+        self.functions.len() + self.entry_points.len()
+    }
+
+    /// The info of the full module, from that of the reduced module and the previous
+    /// info of the functions left out of it.
+    pub fn splice(&self, reduced: &ModuleInfo, previous: &ModuleInfo) -> Option<ModuleInfo> {
+        let serialize = |info: &ModuleInfo| {
+            serde_json::to_value(info)
+                .map_err(|err| log::warn!("can't serialize validation info: {}", err))
+                .ok()
+        };
+        let (mut spliced, mut previous) = (serialize(reduced)?, serialize(previous)?);
+        for (key, sources) in [
+            ("functions", &self.function_infos),
+            ("entry_points", &self.entry_point_infos),
+        ] {
+            let mut take = |source: Source| match source {
+                Source::Reduced(index) => spliced[key].get_mut(index).map(Value::take),
+                Source::Previous(index) => previous[key].get_mut(index).map(Value::take),
+            };
+            let infos: Option<Vec<Value>> = sources.iter().map(|&source| take(source)).collect();
+            spliced[key] = Value::Array(infos?);
+        }
+        serde_json::from_value(spliced)
+            .map_err(|err| log::warn!("can't splice validation info: {}", err))
+            .ok()
+    }
+
+    /// An error of the reduced module, with the handles of its functions turned back
+    /// into those of the full module.
+    pub fn restore_handles(&self, err: WithSpan<ValidationError>) -> WithSpan<ValidationError> {
+        let restore = |handle: &mut Handle<Function>| *handle = self.functions[handle.index()];
+        err.and_then(|mut inner| {
+            match inner {
+                ValidationError::Function {
+                    ref mut handle,
+                    ref mut source,
+                    ..
+                } => {
+                    restore(handle);
+                    if let FunctionError::InvalidCall {
+                        ref mut function, ..
+                    } = *source
+                    {
+                        restore(function);
+                    }
+                }
+                ValidationError::EntryPoint {
+                    source:
+                        EntryPointError::Function(FunctionError::InvalidCall {
+                            ref mut function, ..
+                        }),
+                    ..
+                } => restore(function),
+                _ => {}
+            }
+            WithSpan::new(inner)
+        })
+    }
+}
+
+/// The functions `function` calls, in the order of its code.
+fn calls(function: &Function) -> Vec<Handle<Function>> {
+    fn walk(block: &Block, calls: &mut Vec<Handle<Function>>) {
+        for statement in block.iter() {
+            match *statement {
+                Statement::Call { function, .. } => calls.push(function),
+                Statement::Block(ref block) => walk(block, calls),
+                Statement::If {
+                    ref accept,
+                    ref reject,
+                    ..
+                } => {
+                    walk(accept, calls);
+                    walk(reject, calls);
+                }
+                Statement::Switch { ref cases, .. } => {
+                    for case in cases {
+                        walk(&case.body, calls);
+                    }
+                }
+                Statement::Loop {
+                    ref body,
+                    ref continuing,
+                    ..
+                } => {
+                    walk(body, calls);
+                    walk(continuing, calls);
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut calls = Vec::new();
+    walk(&function.body, &mut calls);
+    calls
+}
+
+/// Points the calls of `function` at the functions of the reduced module, by the
+/// handles they were given there. `None` if it calls a function not in it.
+fn renumber(function: &mut Function, renumbered: &[Option<Handle<Function>>]) -> Option<()> {
+    fn walk(block: &mut Block, renumbered: &[Option<Handle<Function>>]) -> Option<()> {
+        for statement in block.iter_mut() {
+            match *statement {
+                Statement::Call {
+                    ref mut function, ..
+                } => *function = renumbered.get(function.index()).copied().flatten()?,
+                Statement::Block(ref mut block) => walk(block, renumbered)?,
+                Statement::If {
+                    ref mut accept,
+                    ref mut reject,
+                    ..
+                } => {
+                    walk(accept, renumbered)?;
+                    walk(reject, renumbered)?;
+                }
+                Statement::Switch { ref mut cases, .. } => {
+                    for case in cases {
+                        walk(&mut case.body, renumbered)?;
+                    }
+                }
+                Statement::Loop {
+                    ref mut body,
+                    ref mut continuing,
+                    ..
+                } => {
+                    walk(body, renumbered)?;
+                    walk(continuing, renumbered)?;
+                }
+                _ => {}
+            }
+        }
+        Some(())
+    }
+    for (_, expression) in function.expressions.iter_mut() {
+        if let Expression::CallResult(ref mut callee) = *expression {
+            *callee = renumbered.get(callee.index()).copied().flatten()?;
+        }
+    }
+    walk(&mut function.body, renumbered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use naga::valid::Validator;
+
+    const BEFORE: &str = "
+        fn helper(x: f32) -> f32 { return x * 2.0; }
+        fn other(x: f32) -> f32 { return x + 1.0; }
+        fn caller() -> f32 { return helper(1.0); }
+        @fragment fn main() -> @location(0) vec4<f32> { return vec4(caller()); }
+        @compute @workgroup_size(1) fn unrelated() { let y = other(2.0); }
+    ";
+
+    fn validate(module: &Module) -> ModuleInfo {
+        Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(module)
+            .expect("the module is valid")
+    }
+
+    fn fingerprints(module: &Module) -> Fingerprints {
+        Fingerprints::of(module, ValidationFlags::all(), Capabilities::empty()).unwrap()
+    }
+
+    #[test]
+    fn only_the_changed_function_and_its_callers_are_validated() {
+        let before = naga::front::wgsl::parse_str(BEFORE).unwrap();
+        let after = naga::front::wgsl::parse_str(&BEFORE.replace("x * 2.0", "x * 3.0")).unwrap();
+        let info = validate(&before);
+
+        let reduced = reduce(&after, &fingerprints(&after), &fingerprints(&before))
+            .expect("only some functions changed");
+        // `helper`, `caller` and `main`, but neither `other` nor `unrelated`
+        assert_eq!(reduced.validated(), 3);
+        let fresh = validate(&reduced.module);
+        let spliced = reduced.splice(&fresh, &info).expect("the infos splice");
+        assert_eq!(format!("{:?}", spliced), format!("{:?}", validate(&after)));
+    }
+
+    #[test]
+    fn changed_globals_validate_everything() {
+        let before = naga::front::wgsl::parse_str(BEFORE).unwrap();
+        let after =
+            naga::front::wgsl::parse_str(&format!("var<private> g: f32;\n{}", BEFORE)).unwrap();
+        assert!(reduce(&after, &fingerprints(&after), &fingerprints(&before)).is_none());
+    }
+
+    #[test]
+    fn errors_name_the_functions_of_the_full_module() {
+        let before = naga::front::wgsl::parse_str(BEFORE).unwrap();
+        let invalid = BEFORE.replace(
+            "return x + 1.0;",
+            "return x + 1.0; }\nfn broken() -> f32 { return 1u;",
+        );
+        let after = naga::front::wgsl::parse_str(&invalid).unwrap();
+        let reduced = reduce(&after, &fingerprints(&after), &fingerprints(&before))
+            .expect("only some functions changed");
+        let err = Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&reduced.module)
+            .expect_err("`broken` is invalid");
+        let full = Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&after)
+            .expect_err("`broken` is invalid");
+        assert_eq!(
+            reduced.restore_handles(err).as_inner().to_string(),
+            full.as_inner().to_string()
+        );
+    }
+}
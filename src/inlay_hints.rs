@@ -0,0 +1,157 @@
+use lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, InlayHintTooltip, Range};
+
+use crate::document::Document;
+use crate::position::{byte_offset_to_position, position_to_byte_offset};
+use crate::resolve::{declaration_type, expected_type, expression_type};
+use crate::syntax::{DeclarationKind, SourceIndex, TokenKind};
+use crate::types::canonical_type_name;
+
+/// The inferred type after the name of every `let` and `var` in `range` that has no
+/// annotation. With `conversions`, abstract literals in `range` that become another type
+/// than they look like, such as `1` becoming an `f32`, are followed by that type too.
+pub fn inlay_hints(document: &Document, range: Range, conversions: bool) -> Vec<InlayHint> {
+    let (module, info) = match (&document.module, &document.info) {
+        (Some(module), Some(info)) => (module, info),
+        _ => return Vec::new(),
+    };
+    let text = &document.text;
+    let start = position_to_byte_offset(text, range.start);
+    let end = position_to_byte_offset(text, range.end);
+    let index = SourceIndex::new(text);
+
+    let declarations = index
+        .declarations
+        .iter()
+        .enumerate()
+        .filter(|(_, decl)| {
+            matches!(decl.kind, DeclarationKind::Let | DeclarationKind::Var)
+                && decl.ty.is_none()
+                && start <= decl.selection.start
+                && decl.selection.end <= end
+        })
+        .filter_map(|(i, decl)| {
+            let ty = declaration_type(module, Some(info), &index, i)?;
+            Some(InlayHint {
+                position: byte_offset_to_position(text, decl.selection.end),
+                label: InlayHintLabel::String(format!(": {}", ty)),
+                kind: Some(InlayHintKind::TYPE),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: None,
+                data: None,
+            })
+        });
+    let mut hints: Vec<InlayHint> = declarations.collect();
+    if conversions {
+        hints.extend(conversion_hints(document, &index, start, end));
+    }
+    hints
+}
+
+const ARITHMETIC: &[&str] = &["+", "-", "*", "/", "%"];
+
+/// The concrete type each abstract literal between `start` and `end` takes, where it
+/// isn't the `i32` or `f32` the literal would be on its own.
+fn conversion_hints(
+    document: &Document,
+    index: &SourceIndex,
+    start: usize,
+    end: usize,
+) -> Vec<InlayHint> {
+    let text = &document.text;
+    let mut hints = Vec::new();
+    for (i, token) in index.tokens.iter().enumerate() {
+        if token.kind != TokenKind::Number || token.start < start || token.end > end {
+            continue;
+        }
+        let literal = token.text(text).to_ascii_lowercase();
+        let hex = literal.starts_with("0x");
+        let float = if hex {
+            literal.contains('.') || literal.contains('p')
+        } else {
+            literal.contains('.') || literal.contains('e')
+        };
+        let suffixed = literal.ends_with('i')
+            || literal.ends_with('u')
+            || ((!hex || float) && (literal.ends_with('f') || literal.ends_with('h')));
+        if suffixed {
+            continue;
+        }
+        let concrete = match context_type(document, index, i).as_deref().and_then(scalar) {
+            Some(concrete) => concrete,
+            None => continue,
+        };
+        let default = if float { "f32" } else { "i32" };
+        // a float literal can't become an integer, which naga reports as an error
+        if concrete == default || (float && !concrete.starts_with('f')) {
+            continue;
+        }
+        hints.push(InlayHint {
+            position: byte_offset_to_position(text, token.end),
+            label: InlayHintLabel::String(concrete.to_string()),
+            kind: Some(InlayHintKind::TYPE),
+            text_edits: None,
+            tooltip: Some(InlayHintTooltip::String(format!(
+                "the abstract literal `{}` becomes {} here",
+                token.text(text),
+                concrete
+            ))),
+            padding_left: Some(true),
+            padding_right: None,
+            data: None,
+        });
+    }
+    hints
+}
+
+/// The type the literal at token `i` takes from where it is: the constructor or user
+/// function it is passed to, the other operand of arithmetic, or else what the
+/// statement expects.
+fn context_type(document: &Document, index: &SourceIndex, i: usize) -> Option<String> {
+    let text = &document.text;
+    let token = index.tokens[i];
+    if let Some((callee, _)) = index.enclosing_call(text, token.start) {
+        let callee = index.tokens[callee];
+        let function = index
+            .resolve(callee.text(text), callee.start)
+            .filter(|&decl| index.declarations[decl].kind == DeclarationKind::Function);
+        // a constructor such as `vec3f(..)` names the type, and the arguments of
+        // builtins only concretize once the overload is known
+        if function.is_none() {
+            return Some(callee.text(text).to_string()).filter(|ty| scalar(ty).is_some());
+        }
+    }
+
+    let text_at = |at: usize| index.tokens.get(at).map(|token| token.text(text));
+    if i >= 2 && text_at(i - 1).is_some_and(|op| ARITHMETIC.contains(&op)) {
+        if let Some(ty) = expression_type(document, index, i - 2) {
+            return Some(ty);
+        }
+    }
+    let simple_operand = index.tokens.get(i + 2).is_some_and(|operand| {
+        operand.kind == TokenKind::Ident
+            && !matches!(text_at(i + 3), Some(".") | Some("(") | Some("["))
+    });
+    if simple_operand && text_at(i + 1).is_some_and(|op| ARITHMETIC.contains(&op)) {
+        if let Some(ty) = expression_type(document, index, i + 2) {
+            return Some(ty);
+        }
+    }
+    expected_type(document, index, token.start)
+}
+
+/// The scalar type of a scalar, vector or matrix type.
+fn scalar(ty: &str) -> Option<&'static str> {
+    let ty = canonical_type_name(ty);
+    let scalar = match ty.find('<') {
+        Some(open) if ty.starts_with("vec") || ty.starts_with("mat") => {
+            ty[open + 1..].trim_end_matches('>')
+        }
+        _ => ty.as_str(),
+    };
+    ["i32", "u32", "f32", "f16"]
+        .iter()
+        .copied()
+        .find(|&known| known == scalar)
+}
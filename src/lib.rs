@@ -0,0 +1,54 @@
+//! The language features of naga-lsp, independent of the transport that reaches them, so
+//! that the stdio server and the WASM build share them.
+
+pub mod access;
+pub mod backends;
+pub mod bindings;
+pub mod builtins;
+pub mod call_hierarchy;
+pub mod code_actions;
+pub mod code_lens;
+pub mod commands;
+pub mod completion;
+pub mod config;
+pub mod diagnostics;
+pub mod document;
+pub mod enables;
+pub mod folding;
+pub mod formatting;
+pub mod goto;
+pub mod highlight;
+pub mod hover;
+pub mod includes;
+pub mod incremental;
+pub mod inlay_hints;
+pub mod lints;
+pub mod locations;
+pub mod monikers;
+pub mod overflow;
+pub mod overrides;
+pub mod position;
+pub mod progress;
+pub mod recursion;
+pub mod references;
+pub mod reflection;
+pub mod rename;
+pub mod resolve;
+pub mod returns;
+pub mod samplers;
+pub mod selection_range;
+pub mod semantic_tokens;
+pub mod signature_help;
+pub mod snippets;
+pub mod stages;
+pub mod swizzles;
+pub mod symbols;
+pub mod syntax;
+pub mod types;
+pub mod undeclared;
+pub mod uniformity;
+pub mod workgroups;
+pub mod workspace;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
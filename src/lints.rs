@@ -0,0 +1,69 @@
+//! Warnings for code that has no effect: functions that are never called and locals that
+//! are never read.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, DiagnosticTag, NumberOrString};
+
+use crate::highlight::is_assigned;
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+const ENTRY_POINT_ATTRIBUTES: &[&str] = &["vertex", "fragment", "compute"];
+
+pub fn lint(text: &str, index: &SourceIndex, source: &str) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    for (decl, declaration) in index.declarations.iter().enumerate() {
+        // a leading underscore marks a name as intentionally unused
+        if declaration.name.starts_with('_') {
+            continue;
+        }
+        let mut uses = index
+            .references
+            .iter()
+            .filter(|reference| reference.target == Some(decl));
+        let (code, message) = match declaration.kind {
+            DeclarationKind::Function => {
+                if is_entry_point(index, text, decl) || uses.next().is_some() {
+                    continue;
+                }
+                (
+                    "unused-function",
+                    format!("function `{}` is never called", declaration.name),
+                )
+            }
+            DeclarationKind::Let | DeclarationKind::Var | DeclarationKind::LocalConst => {
+                if uses.any(|reference| !is_assigned(index, text, reference.span.start)) {
+                    continue;
+                }
+                (
+                    "unused-variable",
+                    format!("`{}` is never read", declaration.name),
+                )
+            }
+            _ => continue,
+        };
+        diags.push(Diagnostic {
+            range: byte_range_to_range(text, declaration.selection.clone()),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(code.to_string())),
+            source: Some(source.to_string()),
+            message,
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            ..Default::default()
+        });
+    }
+    diags
+}
+
+/// Whether the function declared at `decl` has a stage attribute.
+fn is_entry_point(index: &SourceIndex, text: &str, decl: usize) -> bool {
+    let full = &index.declarations[decl].full;
+    let tokens = index
+        .tokens
+        .iter()
+        .filter(|token| full.start <= token.start && token.end <= full.end)
+        .map(|token| token.text(text))
+        .collect::<Vec<_>>();
+    tokens
+        .windows(2)
+        .any(|pair| pair[0] == "@" && ENTRY_POINT_ATTRIBUTES.contains(&pair[1]))
+}
@@ -0,0 +1,184 @@
+//! Checks that the inputs and outputs of each entry point use distinct `@location`s,
+//! which WebGPU rejects at pipeline creation otherwise.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Url,
+};
+
+use naga::{Binding, Handle, Module, Type, TypeInner};
+
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// Where a location is bound: an argument or the result of the entry point, or a member
+/// of the struct either is.
+enum Site<'a> {
+    Argument(&'a str),
+    Result,
+    Member(&'a str, &'a str),
+}
+
+/// Reports every input or output of an entry point whose `@location` another input or
+/// output of it already uses, linking to the others.
+pub fn collisions(
+    uri: &Url,
+    text: &str,
+    index: &SourceIndex,
+    module: &Module,
+    source: &str,
+) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    for entry_point in &module.entry_points {
+        let function = &entry_point.function;
+        let mut inputs: BTreeMap<u32, Vec<Site>> = BTreeMap::new();
+        for argument in &function.arguments {
+            let name = argument.name.as_deref().unwrap_or("<unnamed>");
+            let site = Site::Argument(name);
+            for (location, site) in bound_locations(module, &argument.binding, argument.ty, site) {
+                inputs.entry(location).or_default().push(site);
+            }
+        }
+        let mut outputs: BTreeMap<u32, Vec<Site>> = BTreeMap::new();
+        if let Some(ref result) = function.result {
+            for (location, site) in
+                bound_locations(module, &result.binding, result.ty, Site::Result)
+            {
+                outputs.entry(location).or_default().push(site);
+            }
+        }
+
+        let function_decl = index.declarations.iter().position(|decl| {
+            decl.kind == DeclarationKind::Function && decl.name == entry_point.name
+        });
+        let locate = |site: &Site| -> lsp_types::Range {
+            let range = function_decl.and_then(|decl| site_range(index, text, decl, site));
+            let fallback = function_decl.map(|decl| index.declarations[decl].selection.clone());
+            range
+                .or(fallback)
+                .map_or_else(lsp_types::Range::default, |range| {
+                    byte_range_to_range(text, range)
+                })
+        };
+        for (what, bound) in [("inputs", inputs), ("outputs", outputs)].iter() {
+            for (location, sites) in bound.iter().filter(|(_, sites)| sites.len() > 1) {
+                for (i, site) in sites.iter().enumerate() {
+                    let others = sites
+                        .iter()
+                        .enumerate()
+                        .filter(|&(j, _)| j != i)
+                        .map(|(_, other)| other);
+                    let related = others
+                        .clone()
+                        .map(|other| DiagnosticRelatedInformation {
+                            location: Location {
+                                uri: uri.clone(),
+                                range: locate(other),
+                            },
+                            message: format!(
+                                "{} also uses @location({})",
+                                describe(other),
+                                location
+                            ),
+                        })
+                        .collect();
+                    let names = others.map(describe).collect::<Vec<_>>().join(", ");
+                    diags.push(Diagnostic {
+                        range: locate(site),
+                        severity: Some(DiagnosticSeverity::ERROR),
+                        code: Some(NumberOrString::String("location-collision".to_string())),
+                        source: Some(source.to_string()),
+                        message: format!(
+                            "{} shares @location({}) with {} among the {} of entry point `{}`",
+                            describe(site),
+                            location,
+                            names,
+                            what,
+                            entry_point.name
+                        ),
+                        related_information: Some(related),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    }
+    diags
+}
+
+/// The locations bound by an argument or result, either directly or by the members of
+/// its struct type.
+fn bound_locations<'a>(
+    module: &'a Module,
+    binding: &'a Option<Binding>,
+    ty: Handle<Type>,
+    site: Site<'a>,
+) -> Vec<(u32, Site<'a>)> {
+    if let Some(Binding::Location { location, .. }) = *binding {
+        return vec![(location, site)];
+    }
+    let (name, members) = match module.types[ty] {
+        Type {
+            name: Some(ref name),
+            inner: TypeInner::Struct { ref members, .. },
+        } => (name, members),
+        _ => return Vec::new(),
+    };
+    members
+        .iter()
+        .filter_map(|member| match member.binding {
+            Some(Binding::Location { location, .. }) => {
+                let member_name = member.name.as_deref().unwrap_or("<unnamed>");
+                Some((location, Site::Member(name, member_name)))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The span of the `@location(..)` attribute of a site in the source, `function` being
+/// the declaration of the entry point.
+fn site_range(
+    index: &SourceIndex,
+    text: &str,
+    function: usize,
+    site: &Site,
+) -> Option<Range<usize>> {
+    match *site {
+        Site::Argument(name) => {
+            let param = index.params(function).find(|param| param.name == name)?;
+            index.attribute(text, param.full.clone(), "location")
+        }
+        Site::Result => {
+            // the attributes of the result follow the parameter list
+            let declaration = &index.declarations[function];
+            let end = declaration
+                .body
+                .as_ref()
+                .map_or(declaration.full.end, |body| body.start);
+            let params_end = index
+                .params(function)
+                .map(|param| param.full.end)
+                .max()
+                .unwrap_or(declaration.selection.end);
+            index.attribute(text, params_end..end, "location")
+        }
+        Site::Member(structure, name) => {
+            let structure = index.find_struct(structure)?;
+            let member = index
+                .members(structure)
+                .find(|member| member.name == name)?;
+            index.attribute(text, member.full.clone(), "location")
+        }
+    }
+}
+
+fn describe(site: &Site) -> String {
+    match *site {
+        Site::Argument(name) => format!("argument `{}`", name),
+        Site::Result => "the result".to_string(),
+        Site::Member(structure, name) => format!("`{}.{}`", structure, name),
+    }
+}
@@ -0,0 +1,38 @@
+//! Logging to stderr, as stdout carries the protocol.
+//!
+//! The `NAGA_LSP_LOG` environment variable takes `env_logger` filters such as `debug` or
+//! `naga_lsp=trace`. Without it, the `logLevel` setting picks the level, defaulting to
+//! `info`.
+
+use log::LevelFilter;
+
+use naga_lsp::config::Config;
+
+const ENV: &str = "NAGA_LSP_LOG";
+
+pub fn init() {
+    let mut builder = env_logger::Builder::new();
+    builder
+        .filter_level(LevelFilter::Trace)
+        .target(env_logger::Target::Stderr)
+        .parse_env(ENV)
+        .init();
+    if std::env::var_os(ENV).is_none() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+/// Applies the configured level, unless the environment variable already decided.
+pub fn set_level(config: &Config) {
+    if std::env::var_os(ENV).is_some() {
+        return;
+    }
+    let level = match config.log_level.as_deref() {
+        Some(level) => level.parse().unwrap_or_else(|_| {
+            log::warn!("unknown log level {}", level);
+            LevelFilter::Info
+        }),
+        None => LevelFilter::Info,
+    };
+    log::set_max_level(level);
+}
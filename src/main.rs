@@ -42,143 +42,1070 @@
 //! {"jsonrpc": "2.0", "method": "exit", "params": null}
 //! ```
 use std::error::Error;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Instant;
+
+mod cli;
+mod logging;
 
 use lsp_types::{
-    notification::DidChangeTextDocument, Diagnostic, InitializeParams, PublishDiagnosticsParams,
-    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
-    VersionedTextDocumentIdentifier,
+    notification::{
+        DidChangeConfiguration, DidChangeTextDocument, DidChangeWatchedFiles, DidCloseTextDocument,
+        DidOpenTextDocument, DidSaveTextDocument,
+    },
+    request::{
+        CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+        CodeActionRequest, CodeLensRequest, Completion, DocumentDiagnosticRequest,
+        DocumentHighlightRequest, DocumentLinkRequest, DocumentSymbolRequest, ExecuteCommand,
+        FoldingRangeRequest, Formatting, GotoDeclaration, GotoDefinition, GotoTypeDefinition,
+        HoverRequest, InlayHintRequest, LinkedEditingRange, MonikerRequest, OnTypeFormatting,
+        PrepareRenameRequest, RangeFormatting, References, Rename, ResolveCompletionItem,
+        SelectionRangeRequest, SemanticTokensFullRequest, SignatureHelpRequest, WillSaveWaitUntil,
+        WorkspaceSymbolRequest,
+    },
+    CallHierarchyServerCapability, ClientCapabilities, CodeActionKind, CodeActionOptions,
+    CodeActionProviderCapability, CodeLensOptions, CompletionOptions, CompletionResponse,
+    DeclarationCapability, Diagnostic, DiagnosticOptions, DiagnosticServerCapabilities,
+    DocumentLinkOptions, DocumentOnTypeFormattingOptions, DocumentSymbolResponse,
+    ExecuteCommandOptions, FileChangeType, FoldingRangeProviderCapability, HoverProviderCapability,
+    InitializeParams, LinkedEditingRangeServerCapabilities, OneOf, PublishDiagnosticsParams,
+    Registration, RegistrationParams, RenameOptions, SelectionRangeProviderCapability,
+    SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensResult,
+    SemanticTokensServerCapabilities, ServerCapabilities, SignatureHelpOptions,
+    TextDocumentClientCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, TypeDefinitionProviderCapability, Url,
+    WorkDoneProgressOptions, WorkspaceSymbolResponse,
 };
 
-use lsp_server::{Connection, Message, Notification};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+
+use cli::{Command, Transport};
+use naga_lsp::config::Config;
+use naga_lsp::diagnostics::parse_document;
+use naga_lsp::document::{Document, DocumentStore, Language};
+use naga_lsp::workspace::Workspace;
+use naga_lsp::{
+    call_hierarchy, code_actions, code_lens, commands, completion, diagnostics, folding,
+    formatting, goto, highlight, hover, includes, inlay_hints, monikers, position, progress,
+    references, rename, selection_range, semantic_tokens, signature_help, symbols,
+};
 
-use naga::front::wgsl;
+/// Numbers the `workspace/diagnostic/refresh` requests we send, as each needs an id of
+/// its own.
+static NEXT_REFRESH: AtomicU32 = AtomicU32::new(0);
 
 fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
     // Note that  we must have our logging only write out to stderr.
-    eprintln!("starting generic LSP server");
+    logging::init();
+    log::info!("starting generic LSP server");
 
-    // Create the transport. Includes the stdio (stdin and stdout) versions but this could
-    // also be implemented to use sockets or HTTP.
-    let (connection, io_threads) = Connection::stdio();
+    let transport = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(transport)) => transport,
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
 
-    // Run the server and wait for the two threads to end (typically by trigger LSP Exit event).
-    let mut server_caps = ServerCapabilities::default();
-    server_caps.text_document_sync =
-        Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::Full));
+    // Create the transport, stdio unless a socket was asked for.
+    let (connection, io_threads) = match transport {
+        Transport::Stdio => Connection::stdio(),
+        Transport::Connect(port) => Connection::connect(("127.0.0.1", port))?,
+        Transport::Listen(port) => {
+            log::info!("waiting for a client on port {}", port);
+            Connection::listen(("127.0.0.1", port))?
+        }
+    };
 
-    let server_capabilities = serde_json::to_value(&server_caps).unwrap();
-    let initialization_params = connection.initialize(server_capabilities)?;
-    main_loop(&connection, initialization_params)?;
+    // Run the server and wait for the two threads to end (typically by trigger LSP Exit event).
+    let (initialize_id, params) = connection.initialize_start()?;
+    // some clients send sparse params, so don't give up if they don't deserialize
+    let params: InitializeParams = serde_json::from_value(params).unwrap_or_else(|err| {
+        log::warn!("failed to parse initialize params: {}", err);
+        InitializeParams::default()
+    });
+    // positions are counted in the encoding we agree on from here on
+    position::negotiate(&params.capabilities);
+    let initialize_result = serde_json::json!({
+        "capabilities": server_capabilities(&params.capabilities),
+        "serverInfo": {
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+    connection.initialize_finish(initialize_id, initialize_result)?;
+    let exit = main_loop(&connection, params)?;
+    // the writer thread only finishes once every sender is gone
+    drop(connection);
     io_threads.join()?;
 
     // Shut down gracefully.
-    eprintln!("shutting down server");
-    Ok(())
+    log::info!("shutting down server");
+    match exit {
+        Exit::Clean => Ok(()),
+        unclean => std::process::exit(unclean.code()),
+    }
+}
+
+/// The features we advertise, leaving out those the client didn't declare support for.
+fn server_capabilities(client: &ClientCapabilities) -> ServerCapabilities {
+    let text_document = client.text_document.as_ref();
+    let supports =
+        |feature: fn(&TextDocumentClientCapabilities) -> bool| text_document.is_some_and(feature);
+
+    let mut server_caps = ServerCapabilities {
+        position_encoding: Some(position::encoding().kind()),
+        ..ServerCapabilities::default()
+    };
+    server_caps.text_document_sync = Some(TextDocumentSyncCapability::Options(
+        TextDocumentSyncOptions {
+            open_close: Some(true),
+            change: Some(TextDocumentSyncKind::INCREMENTAL),
+            save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+            will_save_wait_until: Some(supports(|caps| {
+                caps.synchronization
+                    .as_ref()
+                    .and_then(|sync| sync.will_save_wait_until)
+                    == Some(true)
+            })),
+            ..Default::default()
+        },
+    ));
+    server_caps.hover_provider = Some(HoverProviderCapability::Simple(true));
+    server_caps.definition_provider = Some(OneOf::Left(true));
+    if supports(|caps| caps.declaration.is_some()) {
+        server_caps.declaration_provider = Some(DeclarationCapability::Simple(true));
+    }
+    if supports(|caps| caps.moniker.is_some()) {
+        server_caps.moniker_provider = Some(OneOf::Left(true));
+    }
+    if supports(|caps| caps.type_definition.is_some()) {
+        server_caps.type_definition_provider = Some(TypeDefinitionProviderCapability::Simple(true));
+    }
+    server_caps.references_provider = Some(OneOf::Left(true));
+    server_caps.rename_provider = Some(OneOf::Right(RenameOptions {
+        prepare_provider: text_document
+            .and_then(|caps| caps.rename.as_ref())
+            .and_then(|rename| rename.prepare_support),
+        work_done_progress_options: Default::default(),
+    }));
+    server_caps.document_highlight_provider = Some(OneOf::Left(true));
+    if supports(|caps| caps.linked_editing_range.is_some()) {
+        server_caps.linked_editing_range_provider =
+            Some(LinkedEditingRangeServerCapabilities::Simple(true));
+    }
+    server_caps.workspace_symbol_provider = Some(OneOf::Left(true));
+    server_caps.document_symbol_provider = Some(OneOf::Left(true));
+    server_caps.completion_provider = Some(CompletionOptions {
+        trigger_characters: Some(vec![".".to_string(), ":".to_string()]),
+        resolve_provider: Some(true),
+        ..Default::default()
+    });
+    if supports(|caps| caps.signature_help.is_some()) {
+        server_caps.signature_help_provider = Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+            retrigger_characters: Some(vec![",".to_string()]),
+            work_done_progress_options: Default::default(),
+        });
+    }
+    if supports(|caps| caps.semantic_tokens.is_some()) {
+        server_caps.semantic_tokens_provider = Some(
+            SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                legend: semantic_tokens::legend(),
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            }),
+        );
+    }
+    if supports(|caps| caps.diagnostic.is_some()) {
+        server_caps.diagnostic_provider =
+            Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                identifier: Some("naga".to_string()),
+                // a document's diagnostics depend on the files it includes
+                inter_file_dependencies: true,
+                workspace_diagnostics: false,
+                work_done_progress_options: Default::default(),
+            }));
+    }
+    if supports(|caps| caps.document_link.is_some()) {
+        server_caps.document_link_provider = Some(DocumentLinkOptions {
+            resolve_provider: Some(false),
+            work_done_progress_options: Default::default(),
+        });
+    }
+    if supports(|caps| caps.inlay_hint.is_some()) {
+        server_caps.inlay_hint_provider = Some(OneOf::Left(true));
+    }
+    // our actions carry their edits, which needs literal support
+    if supports(|caps| {
+        caps.code_action
+            .as_ref()
+            .is_some_and(|caps| caps.code_action_literal_support.is_some())
+    }) {
+        server_caps.code_action_provider =
+            Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                code_action_kinds: Some(vec![
+                    CodeActionKind::QUICKFIX,
+                    CodeActionKind::REFACTOR_EXTRACT,
+                ]),
+                ..Default::default()
+            }));
+    }
+    server_caps.document_formatting_provider = Some(OneOf::Left(true));
+    server_caps.document_range_formatting_provider = Some(OneOf::Left(true));
+    if supports(|caps| caps.on_type_formatting.is_some()) {
+        server_caps.document_on_type_formatting_provider = Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "}".to_string(),
+            more_trigger_character: Some(vec!["\n".to_string()]),
+        });
+    }
+    if supports(|caps| caps.folding_range.is_some()) {
+        server_caps.folding_range_provider = Some(FoldingRangeProviderCapability::Simple(true));
+    }
+    if supports(|caps| caps.selection_range.is_some()) {
+        server_caps.selection_range_provider = Some(SelectionRangeProviderCapability::Simple(true));
+    }
+    if supports(|caps| caps.call_hierarchy.is_some()) {
+        server_caps.call_hierarchy_provider = Some(CallHierarchyServerCapability::Simple(true));
+    }
+    if supports(|caps| caps.code_lens.is_some()) {
+        server_caps.code_lens_provider = Some(CodeLensOptions {
+            resolve_provider: Some(false),
+        });
+    }
+    server_caps.execute_command_provider = Some(ExecuteCommandOptions {
+        commands: commands::COMMANDS.iter().map(|c| c.to_string()).collect(),
+        work_done_progress_options: WorkDoneProgressOptions {
+            work_done_progress: Some(true),
+        },
+    });
+    server_caps
+}
+
+/// How the main loop ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Exit {
+    /// `exit` followed `shutdown`, as the spec asks.
+    Clean,
+    /// The client sent `exit` without asking us to shut down first.
+    WithoutShutdown,
+    /// The client went away without saying anything.
+    ConnectionLost,
+}
+
+impl Exit {
+    /// The process exit code; the spec asks for 1 when `exit` comes without `shutdown`.
+    fn code(self) -> i32 {
+        match self {
+            Exit::Clean => 0,
+            Exit::WithoutShutdown => 1,
+            Exit::ConnectionLost => 2,
+        }
+    }
 }
 
+/// A request for the workers, along with the settings it was made under and a snapshot
+/// of the documents as they were when it arrived.
+type Job = (Request, Arc<Config>, DocumentStore);
+
+/// Runs the server until the client exits, handling requests on a pool of workers so
+/// that a slow one doesn't hold up the notifications that follow it.
 fn main_loop(
     connection: &Connection,
-    params: serde_json::Value,
-) -> Result<(), Box<dyn Error + Sync + Send>> {
-    let _params: InitializeParams = serde_json::from_value(params).unwrap();
-    eprintln!("starting example main loop");
-    for msg in &connection.receiver {
-        eprintln!("got msg: {:?}", msg);
+    params: InitializeParams,
+) -> Result<Exit, Box<dyn Error + Sync + Send>> {
+    let config = Arc::new(Config::from_value(params.initialization_options.clone()));
+    logging::set_level(&config);
+    let mut workspace = Workspace::new(&params);
+    workspace.reindex(&config);
+    watch_files(connection, &workspace, &params.capabilities)?;
+    let workspace = RwLock::new(workspace);
+    let workers = thread::available_parallelism().map_or(1, |count| count.get());
+    let (jobs, queue) = mpsc::channel::<Job>();
+    let queue = Mutex::new(queue);
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| work(connection, &workspace, &params.capabilities, &queue));
+        }
+        // `jobs` is dropped on the way out, which lets the workers finish
+        serve(connection, &workspace, &params, config, jobs)
+    })
+}
+
+/// Answers the requests queued for the workers until the main loop ends.
+fn work(
+    connection: &Connection,
+    workspace: &RwLock<Workspace>,
+    client: &ClientCapabilities,
+    queue: &Mutex<mpsc::Receiver<Job>>,
+) {
+    loop {
+        // the queue is only locked while waiting for a job, not while handling it
+        let job = queue.lock().unwrap().recv();
+        let (req, config, documents) = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let workspace = workspace.read().unwrap();
+        let id = req.id.clone();
+        // a panicking handler is answered with an error, which leaves the worker, and the
+        // lock on the workspace, usable for the requests that follow
+        let handled = diagnostics::catch_panic(|| {
+            handle_request(connection, &documents, &workspace, &config, client, req)
+        });
+        let result = match handled {
+            Some(result) => result,
+            None => {
+                let message = "the request could not be answered".to_string();
+                let resp = Response::new_err(id, ErrorCode::InternalError as i32, message);
+                connection
+                    .sender
+                    .send(Message::Response(resp))
+                    .map_err(Into::into)
+            }
+        };
+        if let Err(err) = result {
+            log::error!("failed to answer a request: {}", err);
+        }
+    }
+}
+
+/// Whether a request is answered with edits to the text as the client had it when
+/// asking, or with an export of it, which need the pending reparse of their document done
+/// before the snapshot they are answered from is taken.
+fn in_order(method: &str) -> bool {
+    matches!(
+        method,
+        "textDocument/formatting"
+            | "textDocument/rangeFormatting"
+            | "textDocument/onTypeFormatting"
+            | "textDocument/willSaveWaitUntil"
+            | "textDocument/prepareRename"
+            | "textDocument/rename"
+            | "textDocument/codeAction"
+            | "workspace/executeCommand"
+    )
+}
+
+/// Handles the messages of the client until it exits, passing the requests on to the
+/// workers through `jobs`.
+fn serve(
+    connection: &Connection,
+    workspace: &RwLock<Workspace>,
+    params: &InitializeParams,
+    mut config: Arc<Config>,
+    jobs: mpsc::Sender<Job>,
+) -> Result<Exit, Box<dyn Error + Sync + Send>> {
+    let pull = Pull::new(&params.capabilities);
+    let mut documents = DocumentStore::new();
+    log::info!("starting main loop");
+    loop {
+        let due = documents.next_reparse();
+        let msg = match due {
+            Some(due) => {
+                let timeout = due.saturating_duration_since(Instant::now());
+                match connection.receiver.recv_timeout(timeout) {
+                    Ok(msg) => msg,
+                    Err(err) if err.is_timeout() => {
+                        reparse_due(connection, &mut documents, &config, Instant::now(), pull)?;
+                        continue;
+                    }
+                    Err(_) => return Ok(connection_lost()),
+                }
+            }
+            None => match connection.receiver.recv() {
+                Ok(msg) => msg,
+                Err(_) => return Ok(connection_lost()),
+            },
+        };
+        log::trace!("got msg: {:?}", msg);
         match msg {
             Message::Request(req) => {
+                // waits for the `exit` notification that has to follow
                 if connection.handle_shutdown(&req)? {
-                    return Ok(());
+                    return Ok(Exit::Clean);
                 }
-                // eprintln!("got request: {:?}", req);
-
-                /*                 match cast::<GotoDefinition>(req) {
-                    Ok((id, params)) => {
-                        eprintln!("got gotoDefinition request #{}: {:?}", id, params);
-                        let result = Some(GotoDefinitionResponse::Array(Vec::new()));
-                        let result = serde_json::to_value(&result).unwrap();
-                        let resp = Response { id, result: Some(result), error: None };
-                        connection.sender.send(Message::Response(resp))?;
-                        continue;
+                // edits and exports have to be of the current text, so the pending
+                // reparse of their document can't wait, while the rest answer from the
+                // last parse, so that requests sent on every keystroke don't defeat the
+                // debounce
+                if let Some(uri) = target(&req).filter(|_| in_order(&req.method)) {
+                    if documents
+                        .get(&uri)
+                        .is_some_and(|doc| doc.reparse_at.is_some())
+                    {
+                        reparse(connection, &mut documents, &config, &uri, pull)?;
                     }
-                    Err(req) => req,
-                };*/
-                // ...
-            }
-            Message::Response(_resp) => {
-                // eprintln!("got response: {:?}", resp);
+                }
+                // the workers answer from a snapshot, so the changes that follow don't
+                // wait for them
+                jobs.send((req, Arc::clone(&config), documents.clone()))?;
             }
+            Message::Response(_resp) => {}
             Message::Notification(not) => {
-                // eprintln!("got notification: {:?}", not);
-
-                if let Ok(did_change) = cast_notification::<DidChangeTextDocument>(not) {
-                    // eprintln!("didChange {:?}", did_change);
-
-                    // we are in full sync, so assume only one
-                    let change = &did_change.content_changes[0];
-
-                    let text = &change.text;
-
-                    let res = wgsl::parse_str(text);
-                    let mut diags = Vec::new();
-
-                    match res {
-                        Ok(_) => {}
-                        Err(err) => {
-                            eprint!("compile err: {:?}", err);
-
-                            // let result = Some(Diagno);
-                            // let result = serde_json::to_value(&result).unwrap();
-                            // let resp = Response { id, result: Some(result), error: None };
-                            let diag = Diagnostic {
-                                range: lsp_types::Range {
-                                    start: lsp_types::Position {
-                                        line: err.pos.0 as u32 - 1,
-                                        character: err.pos.1 as u32 - 1,
-                                    },
-                                    end: lsp_types::Position {
-                                        line: err.pos.0 as u32 - 1,
-                                        character: err.pos.1 as u32 + 99, // TODO,
-                                    },
-                                },
-                                severity: Some(lsp_types::DiagnosticSeverity::Error),
-                                code: None,
-                                code_description: None,
-                                source: None,
-                                message: format!("{:?}", err),
-                                related_information: None,
-                                tags: None,
-                                data: None,
-                            };
-                            diags.push(diag);
+                if not.method == "exit" {
+                    log::warn!("got exit without a shutdown request");
+                    return Ok(Exit::WithoutShutdown);
+                }
+
+                let not = match cast_notification::<DidOpenTextDocument>(not) {
+                    Ok(did_open) => {
+                        let doc = did_open.text_document;
+                        if !Language::is_shader(&doc.uri, Some(&doc.language_id)) {
+                            log::info!("not analyzing {}, a {} document", doc.uri, doc.language_id);
+                            continue;
+                        }
+                        let language = Language::detect(&doc.uri, Some(&doc.language_id));
+                        let document =
+                            documents.open(doc.uri.clone(), doc.text, doc.version, language);
+                        let files = parse_document(&doc.uri, document, &config, false);
+                        publish(connection, &doc.uri, document, files, pull)?;
+                        continue;
+                    }
+                    Err(not) => not,
+                };
+
+                let not = match cast_notification::<DidChangeTextDocument>(not) {
+                    Ok(did_change) => {
+                        let doc = did_change.text_document;
+                        let changes = did_change.content_changes;
+                        if let Some(document) = documents.change(doc.uri, changes, doc.version) {
+                            document.reparse_at = Some(Instant::now() + config.debounce());
                         }
+                        continue;
+                    }
+                    Err(not) => not,
+                };
+
+                let not = match cast_notification::<DidSaveTextDocument>(not) {
+                    Ok(did_save) => {
+                        let uri = did_save.text_document.uri;
+                        if let Some(document) = documents.get_mut(&uri) {
+                            // trust the saved text over ours, should the two have drifted
+                            if let Some(text) = did_save.text {
+                                document.text = text;
+                            }
+                            document.reparse_at = None;
+                            let files = parse_document(&uri, document, &config, true);
+                            publish(connection, &uri, document, files, pull)?;
+                        }
+                        continue;
+                    }
+                    Err(not) => not,
+                };
+
+                let not = match cast_notification::<DidChangeWatchedFiles>(not) {
+                    Ok(did_change) => {
+                        let workspace = workspace.read().unwrap();
+                        for change in did_change.changes {
+                            if change.typ == FileChangeType::DELETED {
+                                workspace.remove(&change.uri);
+                            } else {
+                                workspace.update(&change.uri);
+                            }
+                        }
+                        continue;
                     }
-                    send_diagnostics(connection, did_change.text_document, diags)?;
+                    Err(not) => not,
+                };
+
+                let not = match cast_notification::<DidChangeConfiguration>(not) {
+                    Ok(did_change) => {
+                        config = Arc::new(Config::from_value(Some(did_change.settings)));
+                        logging::set_level(&config);
+                        // refresh every document's diagnostics under the new settings
+                        documents.reparse_all(Instant::now());
+                        workspace.write().unwrap().reindex(&config);
+                        continue;
+                    }
+                    Err(not) => not,
+                };
+
+                if let Ok(did_close) = cast_notification::<DidCloseTextDocument>(not) {
+                    let uri = did_close.text_document.uri;
+                    // the document is no longer tracked, so clear whatever we published for it
+                    if let Some(document) = documents.close(&uri) {
+                        for file in &document.diagnosed_includes {
+                            send_diagnostics(connection, file.clone(), None, Vec::new())?;
+                        }
+                    }
+                    send_diagnostics(connection, uri, None, Vec::new())?;
                 }
             }
         }
     }
+}
+
+fn connection_lost() -> Exit {
+    log::warn!("the client closed the connection without shutting down");
+    Exit::ConnectionLost
+}
+
+/// Reparses and publishes diagnostics for every document whose edits are due by `now`.
+fn reparse_due(
+    connection: &Connection,
+    documents: &mut DocumentStore,
+    config: &Config,
+    now: Instant,
+    pull: Pull,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    for uri in documents.due_for_reparse(now) {
+        reparse(connection, documents, config, &uri, pull)?;
+    }
+    Ok(())
+}
+
+/// Reparses and publishes diagnostics for one document, whether or not it is due.
+fn reparse(
+    connection: &Connection,
+    documents: &mut DocumentStore,
+    config: &Config,
+    uri: &Url,
+    pull: Pull,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    if let Some(document) = documents.get_mut(uri) {
+        document.reparse_at = None;
+        let files = parse_document(uri, document, config, false);
+        publish(connection, uri, document, files, pull)?;
+    }
+    Ok(())
+}
+
+/// The document a request is about, from its `textDocument`, or for commands the uri
+/// they take first.
+fn target(req: &Request) -> Option<Url> {
+    let uri = req
+        .params
+        .pointer("/textDocument/uri")
+        .or_else(|| req.params.pointer("/arguments/0"))?;
+    serde_json::from_value(uri.clone()).ok()
+}
+
+/// Asks the client to tell us about changes to the files of the workspace we index, as
+/// they may change outside of the editor.
+fn watch_files(
+    connection: &Connection,
+    workspace: &Workspace,
+    client: &ClientCapabilities,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let dynamic = client
+        .workspace
+        .as_ref()
+        .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+        .and_then(|caps| caps.dynamic_registration)
+        .unwrap_or(false);
+    if !dynamic || !workspace.is_indexed() {
+        return Ok(());
+    }
+    let registration = Registration {
+        id: "watched-files".to_string(),
+        method: "workspace/didChangeWatchedFiles".to_string(),
+        register_options: Some(serde_json::json!({
+            "watchers": [{ "globPattern": workspace.glob() }],
+        })),
+    };
+    let register = Request::new(
+        RequestId::from("register/watched-files".to_string()),
+        "client/registerCapability".to_string(),
+        RegistrationParams {
+            registrations: vec![registration],
+        },
+    );
+    connection.sender.send(Message::Request(register))?;
+    Ok(())
+}
+
+/// How the client gets diagnostics. Clients that pull them shouldn't have them pushed as
+/// well, and are asked to pull again, if they let us, when a delayed reparse changed them.
+#[derive(Clone, Copy)]
+enum Pull {
+    No,
+    Yes { refresh: bool },
+}
+
+impl Pull {
+    fn new(client: &ClientCapabilities) -> Pull {
+        let pulls = client
+            .text_document
+            .as_ref()
+            .is_some_and(|caps| caps.diagnostic.is_some());
+        let refresh = client
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.diagnostic.as_ref())
+            .and_then(|diagnostic| diagnostic.refresh_support)
+            .unwrap_or(false);
+        if pulls {
+            Pull::Yes { refresh }
+        } else {
+            Pull::No
+        }
+    }
+}
+
+fn handle_request(
+    connection: &Connection,
+    documents: &DocumentStore,
+    workspace: &Workspace,
+    config: &Config,
+    client: &ClientCapabilities,
+    req: Request,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let req = match cast::<DocumentDiagnosticRequest>(req) {
+        Ok((id, params)) => {
+            let document = documents.get(&params.text_document.uri);
+            let previous = params.previous_result_id.as_deref();
+            let result = diagnostics::report(document, previous);
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<DocumentLinkRequest>(req) {
+        Ok((id, params)) => {
+            let uri = &params.text_document.uri;
+            let links = match (documents.get(uri), &config.include_directive) {
+                (Some(document), Some(directive)) if document.language == Language::Wgsl => {
+                    includes::document_links(uri, &document.text, directive)
+                }
+                _ => Vec::new(),
+            };
+            return send_response(connection, id, links);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<HoverRequest>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let result = documents
+                .get(&params.text_document.uri)
+                .and_then(|document| hover::hover(document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<GotoTypeDefinition>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let uri = &params.text_document.uri;
+            let result = documents
+                .get(uri)
+                .map(|document| goto::type_definition(uri, document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<GotoDeclaration>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let uri = &params.text_document.uri;
+            let result = documents
+                .get(uri)
+                .map(|document| goto::declaration(uri, document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<MonikerRequest>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let uri = &params.text_document.uri;
+            let result = documents
+                .get(uri)
+                .map(|document| monikers::monikers(uri, document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<GotoDefinition>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let uri = &params.text_document.uri;
+            let result = documents
+                .get(uri)
+                .map(|document| goto::definition(uri, document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<References>(req) {
+        Ok((id, params)) => {
+            let position = &params.text_document_position;
+            let uri = &position.text_document.uri;
+            let result = references::references(
+                uri,
+                documents,
+                workspace,
+                position.position,
+                params.context.include_declaration,
+            );
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<PrepareRenameRequest>(req) {
+        Ok((id, params)) => {
+            let result = documents
+                .get(&params.text_document.uri)
+                .and_then(|document| rename::prepare_rename(document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<Rename>(req) {
+        Ok((id, params)) => {
+            let position = params.text_document_position;
+            let uri = &position.text_document.uri;
+            let result = match documents.get(uri) {
+                Some(document) => {
+                    rename::rename(uri, document, position.position, &params.new_name).map(Some)
+                }
+                None => Ok(None),
+            };
+            return match result {
+                Ok(result) => send_response(connection, id, result),
+                Err(message) => {
+                    let resp = Response::new_err(id, ErrorCode::InvalidParams as i32, message);
+                    connection.sender.send(Message::Response(resp))?;
+                    Ok(())
+                }
+            };
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<DocumentHighlightRequest>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let result = documents
+                .get(&params.text_document.uri)
+                .map(|document| highlight::document_highlights(document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<LinkedEditingRange>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let result = documents
+                .get(&params.text_document.uri)
+                .and_then(|document| highlight::linked_editing_ranges(document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<DocumentSymbolRequest>(req) {
+        Ok((id, params)) => {
+            let result = documents.get(&params.text_document.uri).map(|document| {
+                DocumentSymbolResponse::Nested(symbols::document_symbols(document))
+            });
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<Completion>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position;
+            let resolve = completion::resolves_documentation(client);
+            let snippets = completion::supports_snippets(client);
+            let result = documents.get(&params.text_document.uri).map(|document| {
+                let items = completion::completion(document, params.position, resolve, snippets);
+                CompletionResponse::Array(items)
+            });
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<ResolveCompletionItem>(req) {
+        Ok((id, item)) => return send_response(connection, id, completion::resolve(item)),
+        Err(req) => req,
+    };
+
+    let req = match cast::<SignatureHelpRequest>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let result = documents
+                .get(&params.text_document.uri)
+                .and_then(|document| signature_help::signature_help(document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<SemanticTokensFullRequest>(req) {
+        Ok((id, params)) => {
+            let result = documents.get(&params.text_document.uri).map(|document| {
+                SemanticTokensResult::Tokens(semantic_tokens::semantic_tokens(document))
+            });
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<InlayHintRequest>(req) {
+        Ok((id, params)) => {
+            let result = documents.get(&params.text_document.uri).map(|document| {
+                inlay_hints::inlay_hints(document, params.range, config.conversion_hints)
+            });
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<CodeActionRequest>(req) {
+        Ok((id, params)) => {
+            let uri = &params.text_document.uri;
+            let result = documents.get(uri).map(|document| {
+                code_actions::code_actions(uri, document, params.range, &params.context.diagnostics)
+            });
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<Formatting>(req) {
+        Ok((id, params)) => {
+            let result = documents.get(&params.text_document.uri).map(|document| {
+                let style = config.format_style.as_ref();
+                formatting::format_document(document, style, Some(&params.options))
+            });
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<WillSaveWaitUntil>(req) {
+        Ok((id, params)) => {
+            let result = documents
+                .get(&params.text_document.uri)
+                .filter(|_| config.format_on_save)
+                .map(|document| formatting::on_save(document, config.format_style.as_ref()));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<RangeFormatting>(req) {
+        Ok((id, params)) => {
+            let result = documents
+                .get(&params.text_document.uri)
+                .map(|document| formatting::format_range(document, params.range, &params.options));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<OnTypeFormatting>(req) {
+        Ok((id, params)) => {
+            let position = &params.text_document_position;
+            let result = documents.get(&position.text_document.uri).map(|document| {
+                formatting::on_type(document, position.position, &params.ch, &params.options)
+            });
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<FoldingRangeRequest>(req) {
+        Ok((id, params)) => {
+            let result = documents
+                .get(&params.text_document.uri)
+                .map(folding::folding_ranges);
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<SelectionRangeRequest>(req) {
+        Ok((id, params)) => {
+            let result = documents
+                .get(&params.text_document.uri)
+                .map(|document| selection_range::selection_ranges(document, params.positions));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<WorkspaceSymbolRequest>(req) {
+        Ok((id, params)) => {
+            let symbols = symbols::workspace_symbols(documents, workspace, &params.query);
+            return send_response(connection, id, WorkspaceSymbolResponse::Flat(symbols));
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<CallHierarchyPrepare>(req) {
+        Ok((id, params)) => {
+            let params = params.text_document_position_params;
+            let uri = &params.text_document.uri;
+            let result = documents
+                .get(uri)
+                .map(|document| call_hierarchy::prepare(uri, document, params.position));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<CallHierarchyIncomingCalls>(req) {
+        Ok((id, params)) => {
+            let result = documents
+                .get(&params.item.uri)
+                .map(|document| call_hierarchy::incoming_calls(document, &params.item));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<CallHierarchyOutgoingCalls>(req) {
+        Ok((id, params)) => {
+            let result = documents
+                .get(&params.item.uri)
+                .map(|document| call_hierarchy::outgoing_calls(document, &params.item));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<CodeLensRequest>(req) {
+        Ok((id, params)) => {
+            let uri = &params.text_document.uri;
+            let result = documents
+                .get(uri)
+                .map(|document| code_lens::code_lenses(uri, document));
+            return send_response(connection, id, result);
+        }
+        Err(req) => req,
+    };
+
+    let req = match cast::<ExecuteCommand>(req) {
+        Ok((id, params)) => {
+            let token = params.work_done_progress_params.work_done_token.clone();
+            let title = commands::title(&params.command);
+            let progress = progress::Progress::begin(connection, client, token, title);
+            let result = commands::execute(documents, config, params, &progress);
+            drop(progress);
+            return match result {
+                Ok(result) => send_response(connection, id, result),
+                Err(message) => {
+                    let resp = Response::new_err(id, ErrorCode::InternalError as i32, message);
+                    connection.sender.send(Message::Response(resp))?;
+                    Ok(())
+                }
+            };
+        }
+        Err(req) => req,
+    };
+
+    let resp = Response::new_err(
+        req.id,
+        ErrorCode::MethodNotFound as i32,
+        format!("unhandled request {} or invalid params", req.method),
+    );
+    connection.sender.send(Message::Response(resp))?;
     Ok(())
 }
 
-// fn cast<R>(req: Request) -> Result<(RequestId, R::Params), Request>
-// where
-//     R: lsp_types::request::Request,
-//     R::Params: serde::de::DeserializeOwned,
-// {
-//     req.extract(R::METHOD)
-// }
+fn send_response<R: serde::Serialize>(
+    connection: &Connection,
+    id: RequestId,
+    result: R,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let resp = Response {
+        id,
+        result: Some(serde_json::to_value(result)?),
+        error: None,
+    };
+    connection.sender.send(Message::Response(resp))?;
+    Ok(())
+}
+
+/// Extracts the params of a request for `R`, handing back any other request untouched.
+///
+/// Params that don't deserialize are logged and the request is handed back too, which
+/// ends up answered with an error rather than taking down the server.
+fn cast<R>(req: Request) -> Result<(RequestId, R::Params), Request>
+where
+    R: lsp_types::request::Request,
+    R::Params: serde::de::DeserializeOwned,
+{
+    if req.method != R::METHOD {
+        return Err(req);
+    }
+    match serde_json::from_value(req.params.clone()) {
+        Ok(params) => Ok((req.id, params)),
+        Err(err) => {
+            log::warn!("invalid params for {}: {}", req.method, err);
+            Err(req)
+        }
+    }
+}
+
+/// Publishes the diagnostics of a document and the files it includes, only the document's
+/// own being for a particular version, or keeps them for the client to `pull`.
+fn publish(
+    connection: &Connection,
+    uri: &Url,
+    document: &mut Document,
+    files: Vec<(Url, Vec<Diagnostic>)>,
+    pull: Pull,
+) -> Result<(), Box<dyn Error + Sync + Send>> {
+    if let Pull::Yes { refresh } = pull {
+        if files != document.diagnostics {
+            document.diagnostics = files;
+            document.diagnostics_id += 1;
+            if refresh {
+                let number = NEXT_REFRESH.fetch_add(1, Ordering::Relaxed);
+                let refresh = Request::new(
+                    RequestId::from(format!("diagnostic/refresh/{}", number)),
+                    "workspace/diagnostic/refresh".to_string(),
+                    (),
+                );
+                connection.sender.send(Message::Request(refresh))?;
+            }
+        }
+        return Ok(());
+    }
+    for (file, diags) in files {
+        let version = if file == *uri {
+            Some(document.version)
+        } else {
+            None
+        };
+        send_diagnostics(connection, file, version, diags)?;
+    }
+    Ok(())
+}
 
 fn send_diagnostics(
     connection: &Connection,
-    text_document: VersionedTextDocumentIdentifier,
+    uri: Url,
+    version: Option<i32>,
     diags: Vec<Diagnostic>,
 ) -> Result<(), Box<dyn Error + Sync + Send>> {
     let pubdiag_params = PublishDiagnosticsParams {
-        uri: text_document.uri,
+        uri,
         diagnostics: diags,
-        version: Some(text_document.version),
+        version,
     };
-    let pubdiag_json = serde_json::to_value(&pubdiag_params).unwrap();
+    let pubdiag_json = serde_json::to_value(&pubdiag_params)?;
     let diag_not = Notification {
         method: "textDocument/publishDiagnostics".to_string(),
         params: pubdiag_json,
@@ -195,5 +1122,14 @@ where
     N: lsp_types::notification::Notification,
     N::Params: serde::de::DeserializeOwned,
 {
-    not.extract(N::METHOD)
+    if not.method != N::METHOD {
+        return Err(not);
+    }
+    match serde_json::from_value(not.params.clone()) {
+        Ok(params) => Ok(params),
+        Err(err) => {
+            log::warn!("invalid params for {}: {}", not.method, err);
+            Err(not)
+        }
+    }
 }
@@ -0,0 +1,60 @@
+//! Monikers, which name the module-scope symbols of a document so that indexers outside
+//! the editor can tell uses of the same symbol apart from others of the same name.
+
+use lsp_types::{Moniker, MonikerKind, Position, UniquenessLevel, Url};
+
+use crate::document::Document;
+use crate::position::position_to_byte_offset;
+use crate::resolve::member_at;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// The scheme of our monikers.
+const SCHEME: &str = "naga";
+
+/// The moniker of the function, type, module-scope variable or struct member under the
+/// cursor, as the document's uri and the name qualified by its struct if it is a member.
+/// Locals have none, as nothing outside their function can refer to them.
+pub fn monikers(uri: &Url, document: &Document, position: Position) -> Vec<Moniker> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let decl = match index
+        .declaration_at(offset)
+        .or_else(|| member_at(document, &index, offset))
+    {
+        Some(decl) => decl,
+        None => return Vec::new(),
+    };
+    let declaration = &index.declarations[decl];
+    let name = match declaration.kind {
+        DeclarationKind::Function
+        | DeclarationKind::Struct
+        | DeclarationKind::Alias
+        | DeclarationKind::GlobalVar
+        | DeclarationKind::Const
+        | DeclarationKind::Override => declaration.name.clone(),
+        DeclarationKind::Member => match declaration.parent {
+            Some(parent) => format!("{}.{}", index.declarations[parent].name, declaration.name),
+            None => return Vec::new(),
+        },
+        _ => return Vec::new(),
+    };
+    // entry points are what the document offers to the pipelines using it
+    let entry_point = declaration.kind == DeclarationKind::Function
+        && document.module.as_ref().is_some_and(|module| {
+            module
+                .entry_points
+                .iter()
+                .any(|entry_point| entry_point.name == declaration.name)
+        });
+    vec![Moniker {
+        scheme: SCHEME.to_string(),
+        identifier: format!("{}#{}", uri, name),
+        unique: UniquenessLevel::Scheme,
+        kind: Some(if entry_point {
+            MonikerKind::Export
+        } else {
+            MonikerKind::Local
+        }),
+    }]
+}
@@ -0,0 +1,278 @@
+//! Integer literals and constant expressions whose value doesn't fit their type, which
+//! the shader compiler would reject or silently wrap.
+
+use std::ops::Range;
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex, Token, TokenKind};
+
+/// The concrete integer types, along with the abstract integers of unsuffixed literals,
+/// which are 64-bit while being evaluated.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum IntType {
+    I32,
+    U32,
+    Abstract,
+}
+
+impl IntType {
+    fn from_name(name: &str) -> Option<IntType> {
+        match name {
+            "i32" => Some(IntType::I32),
+            "u32" => Some(IntType::U32),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            IntType::I32 => "i32",
+            IntType::U32 => "u32",
+            IntType::Abstract => "an abstract integer",
+        }
+    }
+
+    fn range(self) -> (i128, i128) {
+        match self {
+            IntType::I32 => (i32::MIN as i128, i32::MAX as i128),
+            IntType::U32 => (0, u32::MAX as i128),
+            IntType::Abstract => (i64::MIN as i128, i64::MAX as i128),
+        }
+    }
+
+    fn fits(self, value: i128) -> bool {
+        let (min, max) = self.range();
+        min <= value && value <= max
+    }
+}
+
+/// The value and type of an integer literal, or `None` for float literals and literals
+/// too long to read.
+fn integer(literal: &str) -> Option<(i128, IntType)> {
+    let (digits, ty) = match literal.as_bytes().last()? {
+        b'i' => (&literal[..literal.len() - 1], IntType::I32),
+        b'u' => (&literal[..literal.len() - 1], IntType::U32),
+        _ => (literal, IntType::Abstract),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some((value, ty))
+}
+
+/// Reports integer literals out of the range of their suffix, and the initializers of
+/// `i32` and `u32` declarations whose constant value doesn't fit.
+pub fn check(text: &str, index: &SourceIndex, source: &str) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    let mut report = |span: Range<usize>, what: String, ty: IntType| {
+        let (min, max) = ty.range();
+        diags.push(Diagnostic {
+            range: byte_range_to_range(text, span),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String("constant-overflow".to_string())),
+            source: Some(source.to_string()),
+            message: format!(
+                "{} doesn't fit in {}, which ranges from {} to {}",
+                what,
+                ty.name(),
+                min,
+                max
+            ),
+            ..Default::default()
+        });
+    };
+
+    let mut flagged = Vec::new();
+    for token in index
+        .tokens
+        .iter()
+        .filter(|token| token.kind == TokenKind::Number)
+    {
+        let literal = token.text(text);
+        // literals that don't even parse as an i128 are far out of any range
+        let (value, ty) = match integer(literal) {
+            Some(integer) => integer,
+            None if literal.bytes().all(|b| b.is_ascii_digit()) => (i128::MAX, IntType::Abstract),
+            None => continue,
+        };
+        if !ty.fits(value) {
+            report(token.span(), format!("`{}`", literal), ty);
+            flagged.push(token.start);
+        }
+    }
+
+    for decl in &index.declarations {
+        let evaluated = matches!(
+            decl.kind,
+            DeclarationKind::Const
+                | DeclarationKind::LocalConst
+                | DeclarationKind::Let
+                | DeclarationKind::Var
+                | DeclarationKind::GlobalVar
+                | DeclarationKind::Override
+        );
+        let ty = match decl.ty.as_deref().and_then(IntType::from_name) {
+            Some(ty) if evaluated => ty,
+            _ => continue,
+        };
+        // the initializer runs from the `=` to the `;` ending the declaration
+        let tokens: Vec<&Token> = index
+            .tokens
+            .iter()
+            .filter(|token| decl.selection.end <= token.start && token.end <= decl.full.end)
+            .skip_while(|token| token.text(text) != "=")
+            .skip(1)
+            .take_while(|token| token.text(text) != ";")
+            .collect();
+        if tokens.is_empty() || tokens.iter().any(|token| flagged.contains(&token.start)) {
+            continue;
+        }
+        let value = match Evaluator::new(text, &tokens).run() {
+            Some(value) => value,
+            None => continue,
+        };
+        if ty.fits(value) {
+            continue;
+        }
+        let span = tokens[0].start..tokens[tokens.len() - 1].end;
+        let what = if tokens.len() == 1 {
+            format!("`{}`", &text[span.clone()])
+        } else {
+            format!("the value {} of `{}`", value, decl.name)
+        };
+        report(span, what, ty);
+    }
+    diags
+}
+
+/// Evaluates the integer arithmetic of an initializer, giving up on anything else, such
+/// as names, calls and float literals.
+struct Evaluator<'a> {
+    text: &'a str,
+    tokens: &'a [&'a Token],
+    pos: usize,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(text: &'a str, tokens: &'a [&'a Token]) -> Self {
+        Evaluator {
+            text,
+            tokens,
+            pos: 0,
+        }
+    }
+
+    fn run(mut self) -> Option<i128> {
+        let value = self.additive()?;
+        if self.pos == self.tokens.len() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn peek(&self) -> &'a str {
+        self.tokens
+            .get(self.pos)
+            .map_or("", |token| token.text(self.text))
+    }
+
+    fn additive(&mut self) -> Option<i128> {
+        let mut value = self.multiplicative()?;
+        loop {
+            let op = self.peek();
+            if op != "+" && op != "-" {
+                return Some(value);
+            }
+            self.pos += 1;
+            let rhs = self.multiplicative()?;
+            value = if op == "+" {
+                value.checked_add(rhs)?
+            } else {
+                value.checked_sub(rhs)?
+            };
+            // intermediate values are still bounded by the widest integer
+            if !IntType::Abstract.fits(value) {
+                return None;
+            }
+        }
+    }
+
+    fn multiplicative(&mut self) -> Option<i128> {
+        let mut value = self.unary()?;
+        loop {
+            let op = self.peek();
+            if op != "*" && op != "/" && op != "%" {
+                return Some(value);
+            }
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = match op {
+                "*" => value.checked_mul(rhs)?,
+                "/" => value.checked_div(rhs)?,
+                _ => value.checked_rem(rhs)?,
+            };
+            if !IntType::Abstract.fits(value) {
+                return None;
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<i128> {
+        if self.peek() == "-" {
+            self.pos += 1;
+            return self.unary().map(|value| -value);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Option<i128> {
+        let token = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        match token.kind {
+            TokenKind::Number => integer(token.text(self.text)).map(|(value, _)| value),
+            TokenKind::Punct if token.text(self.text) == "(" => {
+                let value = self.additive()?;
+                if self.peek() != ")" {
+                    return None;
+                }
+                self.pos += 1;
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overflows(text: &str) -> Vec<String> {
+        let index = SourceIndex::new(text);
+        check(text, &index, "test")
+            .into_iter()
+            .map(|diag| diag.message)
+            .collect()
+    }
+
+    #[test]
+    fn flags_literals_and_initializers_out_of_range() {
+        let messages = overflows("const big = 4294967296u;\nconst sum: i32 = 2147483647 + 1;\n");
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(messages[0].starts_with("`4294967296u` doesn't fit in u32"));
+        assert!(messages[1].starts_with("the value 2147483648 of `sum` doesn't fit in i32"));
+    }
+
+    #[test]
+    fn accepts_the_ends_of_each_range() {
+        let text = "const low: i32 = -2147483648;\nconst high = 0xffffffffu;\n";
+        assert!(overflows(text).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Pipeline-overridable constants, which engines have to know about to set them when
+//! creating pipelines.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Url,
+};
+use serde_json::Value;
+
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// The types of the values a pipeline can give an override.
+const SCALARS: &[&str] = &["bool", "i32", "u32", "f32", "f16"];
+
+/// An `override` declaration as written.
+struct Override<'a> {
+    name: &'a str,
+    selection: Range<usize>,
+    /// The `@id` and the span of its attribute.
+    id: Option<(u32, Range<usize>)>,
+    ty: Option<&'a str>,
+    default: Option<&'a str>,
+}
+
+fn overrides<'a>(text: &'a str, index: &'a SourceIndex) -> Vec<Override<'a>> {
+    index
+        .declarations
+        .iter()
+        .filter(|decl| decl.kind == DeclarationKind::Override)
+        .map(|decl| {
+            let id = index
+                .attribute(text, decl.full.clone(), "id")
+                .and_then(|attribute| {
+                    let value = text[attribute.clone()]
+                        .trim_start_matches("@id")
+                        .trim()
+                        .strip_prefix('(')?
+                        .strip_suffix(')')?
+                        .trim();
+                    // a trailing `u` or `i` suffix doesn't change the id
+                    let value = value.trim_end_matches(['u', 'i']);
+                    Some((value.parse().ok()?, attribute))
+                });
+            // the initializer runs from the `=` to the `;` ending the declaration
+            let tokens = index
+                .tokens
+                .iter()
+                .filter(|token| decl.selection.end <= token.start && token.end <= decl.full.end);
+            let mut tokens = tokens.skip_while(|token| token.text(text) != "=").skip(1);
+            let default = tokens.next().map(|first| {
+                let end = index
+                    .tokens
+                    .iter()
+                    .rev()
+                    .find(|token| token.end <= decl.full.end && token.text(text) == ";")
+                    .filter(|semicolon| semicolon.start >= first.start)
+                    .map_or(decl.full.end, |semicolon| semicolon.start);
+                text[first.start..end].trim()
+            });
+            Override {
+                name: &decl.name,
+                selection: decl.selection.clone(),
+                id,
+                ty: decl.ty.as_deref(),
+                default,
+            }
+        })
+        .collect()
+}
+
+/// Lists every override of the document with its `@id`, type and default value, any of
+/// which may be missing.
+pub fn list(text: &str) -> Value {
+    let index = SourceIndex::new(text);
+    let overrides: Vec<Value> = overrides(text, &index)
+        .into_iter()
+        .map(|item| {
+            serde_json::json!({
+                "name": item.name,
+                "id": item.id.map(|(id, _)| id),
+                "type": item.ty,
+                "default": item.default,
+            })
+        })
+        .collect();
+    serde_json::json!({ "overrides": overrides })
+}
+
+/// Reports overrides that have no default and that no pipeline could give a value, as
+/// their type isn't a scalar or isn't known, and overrides sharing an `@id`.
+pub fn check(uri: &Url, text: &str, index: &SourceIndex, source: &str) -> Vec<Diagnostic> {
+    let overrides = overrides(text, index);
+    let mut diags = Vec::new();
+    let mut ids: HashMap<u32, &Override> = HashMap::new();
+    for item in &overrides {
+        let assignable = item.ty.is_some_and(|ty| SCALARS.contains(&ty));
+        if item.default.is_none() && !assignable {
+            let message = match item.ty {
+                Some(ty) => format!(
+                    "override `{}` has no default and pipelines can't set a `{}`",
+                    item.name, ty
+                ),
+                None => format!("override `{}` needs a type or a default", item.name),
+            };
+            diags.push(Diagnostic {
+                range: byte_range_to_range(text, item.selection.clone()),
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: Some(NumberOrString::String("unassignable-override".to_string())),
+                source: Some(source.to_string()),
+                message,
+                ..Default::default()
+            });
+        }
+
+        let (id, attribute) = match item.id {
+            Some((id, ref attribute)) => (id, attribute),
+            None => continue,
+        };
+        let first = match ids.get(&id) {
+            Some(first) => first,
+            None => {
+                ids.insert(id, item);
+                continue;
+            }
+        };
+        let first_attribute = first.id.as_ref().map_or(0..0, |(_, span)| span.clone());
+        diags.push(Diagnostic {
+            range: byte_range_to_range(text, attribute.clone()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String("override-id-collision".to_string())),
+            source: Some(source.to_string()),
+            message: format!(
+                "overrides `{}` and `{}` share @id({})",
+                first.name, item.name, id
+            ),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location {
+                    uri: uri.clone(),
+                    range: byte_range_to_range(text, first_attribute),
+                },
+                message: format!("`{}` has @id({}) here", first.name, id),
+            }]),
+            ..Default::default()
+        });
+    }
+    diags
+}
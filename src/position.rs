@@ -0,0 +1,193 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use lsp_types::{ClientCapabilities, Position, PositionEncodingKind, Range};
+
+/// What the `character` of an LSP position counts, as agreed with the client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+impl Encoding {
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Encoding::Utf8 => PositionEncodingKind::UTF8,
+            Encoding::Utf16 => PositionEncodingKind::UTF16,
+            Encoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    /// The code units `c` takes up.
+    fn units(self, c: char) -> u32 {
+        match self {
+            Encoding::Utf8 => c.len_utf8() as u32,
+            Encoding::Utf16 => c.len_utf16() as u32,
+            Encoding::Utf32 => 1,
+        }
+    }
+}
+
+/// The negotiated encoding, UTF-16 until then as the spec wants for clients that don't
+/// say.
+static ENCODING: AtomicU8 = AtomicU8::new(Encoding::Utf16 as u8);
+
+/// Picks the position encoding out of those the client offers, preferring UTF-8 as it
+/// counts in bytes like our offsets, and uses it for every conversion from then on.
+pub fn negotiate(client: &ClientCapabilities) -> Encoding {
+    let offered = client
+        .general
+        .as_ref()
+        .and_then(|general| general.position_encodings.as_ref());
+    let offers = |kind: PositionEncodingKind| offered.is_some_and(|kinds| kinds.contains(&kind));
+    let encoding = if offers(PositionEncodingKind::UTF8) {
+        Encoding::Utf8
+    } else if offers(PositionEncodingKind::UTF32) {
+        Encoding::Utf32
+    } else {
+        Encoding::Utf16
+    };
+    ENCODING.store(encoding as u8, Ordering::Relaxed);
+    encoding
+}
+
+pub fn encoding() -> Encoding {
+    match ENCODING.load(Ordering::Relaxed) {
+        x if x == Encoding::Utf8 as u8 => Encoding::Utf8,
+        x if x == Encoding::Utf32 as u8 => Encoding::Utf32,
+        _ => Encoding::Utf16,
+    }
+}
+
+/// The length of `text` in code units of the negotiated encoding.
+pub fn text_len(text: &str) -> u32 {
+    match encoding() {
+        Encoding::Utf8 => text.len() as u32,
+        encoding => text.chars().map(|c| encoding.units(c)).sum(),
+    }
+}
+
+/// Converts a byte offset into `text` to an LSP position, whose `character` counts code
+/// units of the negotiated encoding from the start of the line.
+///
+/// Offsets past the end of the text are clamped to the end.
+pub fn byte_offset_to_position(text: &str, offset: usize) -> Position {
+    // an offset inside a character counts as its start
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let line_start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+    Position {
+        line: text[..line_start].matches('\n').count() as u32,
+        character: text_len(&text[line_start..offset]),
+    }
+}
+
+/// Converts a byte range to an LSP range. Both ends are mapped on their own, so a range
+/// may start and end on different lines.
+pub fn byte_range_to_range(text: &str, range: std::ops::Range<usize>) -> Range {
+    Range {
+        start: byte_offset_to_position(text, range.start),
+        end: byte_offset_to_position(text, range.end),
+    }
+}
+
+/// Converts an LSP position back to a byte offset into `text`.
+///
+/// Positions past the end of a line are clamped to the end of that line, and positions
+/// past the last line to the end of the text. A position inside a character, which
+/// only a UTF-8 or UTF-16 client can send, maps to the character's start.
+pub fn position_to_byte_offset(text: &str, position: Position) -> usize {
+    let line_start = match position.line {
+        0 => 0,
+        line => match text.match_indices('\n').nth(line as usize - 1) {
+            Some((newline, _)) => newline + 1,
+            None => return text.len(),
+        },
+    };
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |newline| line_start + newline);
+    let encoding = encoding();
+    if encoding == Encoding::Utf8 {
+        let mut offset = (line_start + position.character as usize).min(line_end);
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        return offset;
+    }
+    let mut character = 0;
+    for (index, c) in text[line_start..line_end].char_indices() {
+        character += encoding.units(c);
+        if character > position.character {
+            return line_start + index;
+        }
+    }
+    line_end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // the tests leave the encoding at the UTF-16 default, as it is shared by every test
+
+    #[test]
+    fn counts_utf16_units_after_non_ascii() {
+        let text = "// héllo 🦀\nlet x = 1;";
+        // `é` takes one UTF-16 unit and the crab a surrogate pair
+        let end_of_comment = text.find('\n').unwrap();
+        assert_eq!(
+            byte_offset_to_position(text, end_of_comment),
+            Position::new(0, 11)
+        );
+        let x = text.find('x').unwrap();
+        assert_eq!(byte_offset_to_position(text, x), Position::new(1, 4));
+    }
+
+    #[test]
+    fn error_after_emoji_on_the_same_line() {
+        let text = "/* 🦀 */ fn";
+        let fn_start = text.find("fn").unwrap();
+        assert_eq!(byte_offset_to_position(text, fn_start), Position::new(0, 9));
+        assert_eq!(position_to_byte_offset(text, Position::new(0, 9)), fn_start);
+    }
+
+    #[test]
+    fn round_trips_every_character_boundary() {
+        let text = "fn main() {\n    // ünïcödé 𝄞\n    let s = 1;\n}\n";
+        for (offset, _) in text.char_indices() {
+            let position = byte_offset_to_position(text, offset);
+            assert_eq!(position_to_byte_offset(text, position), offset);
+        }
+    }
+
+    #[test]
+    fn clamps_past_the_end() {
+        let text = "ab\ncd";
+        assert_eq!(byte_offset_to_position(text, 100), Position::new(1, 2));
+        assert_eq!(position_to_byte_offset(text, Position::new(0, 10)), 2);
+        assert_eq!(
+            position_to_byte_offset(text, Position::new(5, 0)),
+            text.len()
+        );
+    }
+
+    #[test]
+    fn ranges_can_span_lines() {
+        let text = "foo(\n  1,\n  2)";
+        let range = byte_range_to_range(text, 0..text.len());
+        assert_eq!(range.start, Position::new(0, 0));
+        assert_eq!(range.end, Position::new(2, 4));
+    }
+
+    #[test]
+    fn offsets_inside_a_character_map_to_its_start() {
+        let text = "a🦀b";
+        assert_eq!(byte_offset_to_position(text, 2), Position::new(0, 1));
+        // the middle of the surrogate pair
+        assert_eq!(position_to_byte_offset(text, Position::new(0, 2)), 1);
+        assert_eq!(position_to_byte_offset(text, Position::new(0, 3)), 5);
+    }
+}
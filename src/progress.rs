@@ -0,0 +1,89 @@
+//! Work done progress, which clients show while a long request such as an export runs.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::{
+    ClientCapabilities, NumberOrString, ProgressParams, ProgressParamsValue, ProgressToken,
+    WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+    WorkDoneProgressReport,
+};
+
+/// Numbers the progress tokens we ask the client to create.
+static NEXT_TOKEN: AtomicU32 = AtomicU32::new(0);
+
+/// A running progress report, which ends when dropped.
+pub struct Progress<'a> {
+    connection: &'a Connection,
+    /// `None` if the client can't show progress, in which case nothing is sent.
+    token: Option<ProgressToken>,
+}
+
+impl<'a> Progress<'a> {
+    /// Starts reporting progress under `title`. The token the client sent along with the
+    /// request is used if there is one, or else we ask the client to create one.
+    pub fn begin(
+        connection: &'a Connection,
+        client: &ClientCapabilities,
+        token: Option<ProgressToken>,
+        title: &str,
+    ) -> Self {
+        let token = token.or_else(|| {
+            let supported = client
+                .window
+                .as_ref()
+                .and_then(|window| window.work_done_progress)
+                .unwrap_or(false);
+            if !supported {
+                return None;
+            }
+            let number = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+            let token = NumberOrString::String(format!("naga-lsp/progress/{}", number));
+            // we don't wait for the response, which the main loop drops, as clients
+            // accept progress sent right after the create request
+            let create = Request::new(
+                RequestId::from(format!("progress/{}", number)),
+                "window/workDoneProgress/create".to_string(),
+                WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                },
+            );
+            connection.sender.send(Message::Request(create)).ok()?;
+            Some(token)
+        });
+        let progress = Progress { connection, token };
+        progress.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: title.to_string(),
+            ..Default::default()
+        }));
+        progress
+    }
+
+    /// Says what is being worked on now.
+    pub fn report(&self, message: &str) {
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            message: Some(message.to_string()),
+            ..Default::default()
+        }));
+    }
+
+    fn send(&self, value: WorkDoneProgress) {
+        let token = match self.token {
+            Some(ref token) => token.clone(),
+            None => return,
+        };
+        let params = ProgressParams {
+            token,
+            value: ProgressParamsValue::WorkDone(value),
+        };
+        let not = Notification::new("$/progress".to_string(), params);
+        // a closed connection is noticed by the main loop, so there is nothing to do here
+        let _ = self.connection.sender.send(Message::Notification(not));
+    }
+}
+
+impl Drop for Progress<'_> {
+    fn drop(&mut self) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd { message: None }));
+    }
+}
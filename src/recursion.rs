@@ -0,0 +1,120 @@
+//! Reports recursion, which WGSL forbids, with the path of calls that leads a function
+//! back to itself.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Url,
+};
+
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// Reports every function that calls itself, directly or through other functions, at its
+/// name, linking to each call of the shortest such cycle.
+pub fn check(uri: &Url, text: &str, index: &SourceIndex, source: &str) -> Vec<Diagnostic> {
+    let calls = call_graph(index);
+    let mut diags = Vec::new();
+    for (function, _) in calls.iter().enumerate() {
+        if index.declarations[function].kind != DeclarationKind::Function {
+            continue;
+        }
+        let cycle = match cycle(&calls, function) {
+            Some(cycle) => cycle,
+            None => continue,
+        };
+        let name = |decl: usize| index.declarations[decl].name.as_str();
+        let mut path = vec![name(function)];
+        path.extend(cycle.iter().map(|&(callee, _)| name(callee)));
+        let mut caller = function;
+        let related = cycle
+            .iter()
+            .map(|&(callee, ref call)| {
+                let message = format!("`{}` calls `{}` here", name(caller), name(callee));
+                caller = callee;
+                DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: uri.clone(),
+                        range: byte_range_to_range(text, call.clone()),
+                    },
+                    message,
+                }
+            })
+            .collect();
+        diags.push(Diagnostic {
+            range: byte_range_to_range(text, index.declarations[function].selection.clone()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String("recursion".to_string())),
+            source: Some(source.to_string()),
+            message: format!(
+                "function `{}` is recursive, which WGSL doesn't allow: {}",
+                name(function),
+                path.join(" -> ")
+            ),
+            related_information: Some(related),
+            ..Default::default()
+        });
+    }
+    diags
+}
+
+/// The functions each declaration calls, with the span of each call. Only functions have
+/// any.
+fn call_graph(index: &SourceIndex) -> Vec<Vec<(usize, Range<usize>)>> {
+    let mut calls = vec![Vec::new(); index.declarations.len()];
+    for (caller, decl) in index.declarations.iter().enumerate() {
+        let body = match (decl.kind, &decl.body) {
+            (DeclarationKind::Function, Some(body)) => body,
+            _ => continue,
+        };
+        for reference in &index.references {
+            match reference.target {
+                Some(callee)
+                    if body.contains(&reference.span.start)
+                        && index.declarations[callee].kind == DeclarationKind::Function =>
+                {
+                    calls[caller].push((callee, reference.span.clone()));
+                }
+                _ => {}
+            }
+        }
+    }
+    calls
+}
+
+/// The shortest chain of calls from `function` back to itself, as the function each call
+/// reaches and the span of the call.
+fn cycle(
+    calls: &[Vec<(usize, Range<usize>)>],
+    function: usize,
+) -> Option<Vec<(usize, Range<usize>)>> {
+    // the call that first reached each function, searching breadth first
+    let mut reached_by: Vec<Option<(usize, usize)>> = vec![None; calls.len()];
+    let mut queue = VecDeque::new();
+    queue.push_back(function);
+    while let Some(caller) = queue.pop_front() {
+        for (call, &(callee, _)) in calls[caller].iter().enumerate() {
+            if reached_by[callee].is_some() {
+                continue;
+            }
+            reached_by[callee] = Some((caller, call));
+            if callee == function {
+                // walk back from the call that closed the cycle
+                let mut path = Vec::new();
+                let mut at = function;
+                while let Some((caller, call)) = reached_by[at] {
+                    path.push(calls[caller][call].clone());
+                    at = caller;
+                    if at == function {
+                        break;
+                    }
+                }
+                path.reverse();
+                return Some(path);
+            }
+            queue.push_back(callee);
+        }
+    }
+    None
+}
@@ -0,0 +1,85 @@
+use lsp_types::{Location, Position, Url};
+
+use crate::document::{DocumentStore, Language};
+use crate::position::{byte_range_to_range, position_to_byte_offset};
+use crate::syntax::SourceIndex;
+use crate::workspace::Workspace;
+
+use std::ops::Range;
+
+/// Every identifier referring to the same declaration as the one under the cursor,
+/// optionally with the declaration itself first.
+///
+/// A module-scope declaration may also be used by the files including this one, so the
+/// names other WGSL files leave undeclared are taken to refer to it too, both in open
+/// documents and in the indexed files of the workspace.
+pub fn references(
+    uri: &Url,
+    documents: &DocumentStore,
+    workspace: &Workspace,
+    position: Position,
+    include_declaration: bool,
+) -> Vec<Location> {
+    let document = match documents.get(uri) {
+        Some(document) => document,
+        None => return Vec::new(),
+    };
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let decl = match index.declaration_at(offset) {
+        Some(decl) => decl,
+        None => return Vec::new(),
+    };
+
+    let mut locations: Vec<Location> = occurrences(&index, decl, include_declaration)
+        .into_iter()
+        .map(|span| Location {
+            uri: uri.clone(),
+            range: byte_range_to_range(text, span),
+        })
+        .collect();
+    let declaration = &index.declarations[decl];
+    if declaration.parent.is_some() || declaration.kind.is_local() {
+        return locations;
+    }
+    let mut undeclared_uses = |other: &Url, text: &str, index: &SourceIndex| {
+        let uses = index.references.iter().filter(|reference| {
+            reference.target.is_none() && text[reference.span.clone()] == declaration.name
+        });
+        locations.extend(uses.map(|reference| Location {
+            uri: other.clone(),
+            range: byte_range_to_range(text, reference.span.clone()),
+        }));
+    };
+    for (other, document) in documents.iter() {
+        if other != uri && document.language == Language::Wgsl {
+            undeclared_uses(other, &document.text, &SourceIndex::new(&document.text));
+        }
+    }
+    workspace.with_files(|files| {
+        for (other, file) in files {
+            if other != uri && documents.get(other).is_none() {
+                undeclared_uses(other, &file.text, &file.index);
+            }
+        }
+    });
+    locations
+}
+
+/// The spans of the identifiers referring to `decl`, preceded by its name if
+/// `include_declaration` is set.
+pub fn occurrences(
+    index: &SourceIndex,
+    decl: usize,
+    include_declaration: bool,
+) -> Vec<Range<usize>> {
+    let declaration =
+        Some(index.declarations[decl].selection.clone()).filter(|_| include_declaration);
+    let uses = index
+        .references
+        .iter()
+        .filter(|reference| reference.target == Some(decl))
+        .map(|reference| reference.span.clone());
+    declaration.into_iter().chain(uses).collect()
+}
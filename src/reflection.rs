@@ -0,0 +1,124 @@
+//! Reflection of a module's pipeline interface, for engines and other tools to build
+//! their pipelines from.
+
+use serde_json::{json, Value};
+
+use naga::valid::{GlobalUse, ModuleInfo};
+use naga::{Binding, Handle, Module, ShaderStage, Type, TypeInner};
+
+use crate::commands::stage_name;
+use crate::types::{address_space_name, builtin_name, type_name};
+
+/// Describes every entry point as `entry_point` does.
+pub fn reflect(module: &Module, info: &ModuleInfo) -> Value {
+    let entry_points = (0..module.entry_points.len())
+        .map(|index| entry_point(module, info, index))
+        .collect::<Vec<_>>();
+    json!({ "entryPoints": entry_points })
+}
+
+/// Describes the entry point at `index`: its stage, the inputs and outputs with their
+/// locations or builtins, the resources it binds and, for compute shaders, its
+/// workgroup size.
+pub fn entry_point(module: &Module, info: &ModuleInfo, index: usize) -> Value {
+    let entry_point = &module.entry_points[index];
+    let function = &entry_point.function;
+    let mut inputs = Vec::new();
+    for argument in &function.arguments {
+        let name = argument.name.as_deref().unwrap_or("");
+        interface(
+            module,
+            name,
+            argument.ty,
+            argument.binding.as_ref(),
+            &mut inputs,
+        );
+    }
+    let mut outputs = Vec::new();
+    if let Some(ref result) = function.result {
+        interface(module, "", result.ty, result.binding.as_ref(), &mut outputs);
+    }
+
+    let entry_info = info.get_entry_point(index);
+    let bindings = module
+        .global_variables
+        .iter()
+        .filter_map(|(handle, global)| {
+            let binding = global.binding.as_ref()?;
+            let usage = entry_info[handle];
+            if usage.is_empty() {
+                return None;
+            }
+            Some(json!({
+                "group": binding.group,
+                "binding": binding.binding,
+                "name": global.name,
+                "space": address_space_name(global.space),
+                "type": type_name(module, global.ty),
+                "read": usage.contains(GlobalUse::READ),
+                "write": usage.contains(GlobalUse::WRITE),
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    let mut reflected = json!({
+        "name": entry_point.name,
+        "stage": stage_name(entry_point.stage),
+        "inputs": inputs,
+        "outputs": outputs,
+        "bindings": bindings,
+    });
+    if entry_point.stage == ShaderStage::Compute {
+        reflected["workgroupSize"] = json!(entry_point.workgroup_size);
+    }
+    reflected
+}
+
+/// Adds the values passed through an argument or result to `values`, one for each
+/// member if it is a struct of bound members.
+fn interface(
+    module: &Module,
+    name: &str,
+    ty: Handle<Type>,
+    binding: Option<&Binding>,
+    values: &mut Vec<Value>,
+) {
+    match binding {
+        Some(binding) => values.push(interface_value(module, name, ty, binding)),
+        None => {
+            if let TypeInner::Struct { ref members, .. } = module.types[ty].inner {
+                for member in members {
+                    if let Some(ref binding) = member.binding {
+                        let name = member.name.as_deref().unwrap_or("");
+                        values.push(interface_value(module, name, member.ty, binding));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn interface_value(module: &Module, name: &str, ty: Handle<Type>, binding: &Binding) -> Value {
+    let mut value = json!({
+        "name": name,
+        "type": type_name(module, ty),
+    });
+    match *binding {
+        Binding::BuiltIn(builtin) => value["builtin"] = json!(builtin_name(builtin)),
+        Binding::Location {
+            location,
+            interpolation,
+            sampling,
+            ..
+        } => {
+            value["location"] = json!(location);
+            if let Some(interpolation) = interpolation {
+                value["interpolation"] = json!(format!("{:?}", interpolation).to_lowercase());
+            }
+            if let Some(sampling) = sampling {
+                value["sampling"] = json!(format!("{:?}", sampling).to_lowercase());
+            }
+        }
+    }
+    value
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use lsp_types::{Position, PrepareRenameResponse, TextEdit, Url, WorkspaceEdit};
+
+use crate::document::Document;
+use crate::position::{byte_range_to_range, position_to_byte_offset};
+use crate::references::occurrences;
+use crate::syntax::{DeclarationKind, SourceIndex, KEYWORDS};
+
+/// The name under the cursor, if it is a declaration that can be renamed.
+///
+/// Struct members are excluded, as their uses can only be found with type information.
+pub fn prepare_rename(document: &Document, position: Position) -> Option<PrepareRenameResponse> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let token = index.ident_at(offset)?;
+    let decl = index.declaration_at(offset)?;
+    if index.declarations[decl].kind == DeclarationKind::Member {
+        return None;
+    }
+    Some(PrepareRenameResponse::Range(byte_range_to_range(
+        text,
+        token.span(),
+    )))
+}
+
+/// Renames the declaration under the cursor and every reference to it.
+pub fn rename(
+    uri: &Url,
+    document: &Document,
+    position: Position,
+    new_name: &str,
+) -> Result<WorkspaceEdit, String> {
+    validate_identifier(new_name)?;
+
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    let offset = position_to_byte_offset(text, position);
+    let decl = index
+        .declaration_at(offset)
+        .filter(|&decl| index.declarations[decl].kind != DeclarationKind::Member)
+        .ok_or_else(|| "no renameable symbol at the cursor".to_string())?;
+
+    let edits = occurrences(&index, decl, true)
+        .into_iter()
+        .map(|span| TextEdit {
+            range: byte_range_to_range(text, span),
+            new_text: new_name.to_string(),
+        })
+        .collect();
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), edits);
+    Ok(WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    })
+}
+
+/// Checks `name` against the WGSL rules for identifiers.
+fn validate_identifier(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(first) => {
+            (first.is_alphabetic() || first == '_')
+                && chars.all(|c| c.is_alphanumeric() || c == '_')
+        }
+        None => false,
+    };
+    if !valid {
+        return Err(format!("`{}` is not a valid identifier", name));
+    }
+    if name == "_" || name.starts_with("__") {
+        return Err(format!(
+            "`{}` is reserved: identifiers can't be `_` or start with `__`",
+            name
+        ));
+    }
+    if KEYWORDS.contains(&name) {
+        return Err(format!("`{}` is a keyword", name));
+    }
+    Ok(())
+}
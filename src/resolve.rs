@@ -0,0 +1,301 @@
+//! Matching declarations found in the source text up with their counterparts in the
+//! parsed naga module.
+
+use naga::valid::{FunctionInfo, ModuleInfo};
+use naga::{Function, Handle, Module, TypeInner};
+
+use crate::document::Document;
+use crate::syntax::{DeclarationKind, SourceIndex, TokenKind};
+use crate::types::{type_inner_name, type_name};
+
+#[derive(Clone, Copy, Debug)]
+pub enum FunctionRef {
+    Function(Handle<Function>),
+    EntryPoint(usize),
+}
+
+/// Looks up a function or entry point by name.
+pub fn find_function<'a>(module: &'a Module, name: &str) -> Option<(FunctionRef, &'a Function)> {
+    if let Some((handle, function)) = module
+        .functions
+        .iter()
+        .find(|(_, function)| function.name.as_deref() == Some(name))
+    {
+        return Some((FunctionRef::Function(handle), function));
+    }
+    module
+        .entry_points
+        .iter()
+        .enumerate()
+        .find(|(_, entry_point)| entry_point.name == name)
+        .map(|(index, entry_point)| (FunctionRef::EntryPoint(index), &entry_point.function))
+}
+
+pub fn function_info(info: &ModuleInfo, function: FunctionRef) -> &FunctionInfo {
+    match function {
+        FunctionRef::Function(handle) => &info[handle],
+        FunctionRef::EntryPoint(index) => info.get_entry_point(index),
+    }
+}
+
+/// Looks up a struct type by name.
+pub fn find_struct<'a>(module: &'a Module, name: &str) -> Option<&'a [naga::StructMember]> {
+    module.types.iter().find_map(|(_, ty)| match ty.inner {
+        TypeInner::Struct { ref members, .. } if ty.name.as_deref() == Some(name) => {
+            Some(members.as_slice())
+        }
+        _ => None,
+    })
+}
+
+/// The resolved type of a parameter, local, struct member or global, formatted as WGSL.
+///
+/// The type of a `let` without annotation is only known after validation, so `info` is
+/// needed for those.
+pub fn declaration_type(
+    module: &Module,
+    info: Option<&ModuleInfo>,
+    index: &SourceIndex,
+    decl: usize,
+) -> Option<String> {
+    let declaration = &index.declarations[decl];
+    let name = declaration.name.as_str();
+    let parent = declaration.parent.map(|parent| &index.declarations[parent]);
+
+    match declaration.kind {
+        DeclarationKind::Param => {
+            let (_, function) = find_function(module, &parent?.name)?;
+            let argument = function
+                .arguments
+                .iter()
+                .find(|argument| argument.name.as_deref() == Some(name))?;
+            Some(type_name(module, argument.ty))
+        }
+        DeclarationKind::Var => {
+            let (_, function) = find_function(module, &parent?.name)?;
+            let (_, local) = function
+                .local_variables
+                .iter()
+                .filter(|(_, local)| local.name.as_deref() == Some(name))
+                .nth(shadow_ordinal(index, decl))?;
+            Some(type_name(module, local.ty))
+        }
+        DeclarationKind::Let => {
+            let (function_ref, function) = find_function(module, &parent?.name)?;
+            let (&handle, _) = function
+                .named_expressions
+                .iter()
+                .filter(|(_, expr_name)| expr_name.as_str() == name)
+                .nth(shadow_ordinal(index, decl))?;
+            let resolution = &function_info(info?, function_ref)[handle].ty;
+            Some(match *resolution {
+                naga::proc::TypeResolution::Handle(ty) => type_name(module, ty),
+                naga::proc::TypeResolution::Value(ref inner) => type_inner_name(module, inner),
+            })
+        }
+        DeclarationKind::Member => {
+            let members = find_struct(module, &parent?.name)?;
+            let member = members
+                .iter()
+                .find(|member| member.name.as_deref() == Some(name))?;
+            Some(type_name(module, member.ty))
+        }
+        DeclarationKind::GlobalVar => {
+            let (_, global) = module
+                .global_variables
+                .iter()
+                .find(|(_, global)| global.name.as_deref() == Some(name))?;
+            Some(type_name(module, global.ty))
+        }
+        DeclarationKind::Const => {
+            let (_, constant) = module
+                .constants
+                .iter()
+                .find(|(_, constant)| constant.name.as_deref() == Some(name))?;
+            Some(type_name(module, constant.ty))
+        }
+        _ => None,
+    }
+}
+
+/// How many declarations of the same name and kind precede `decl` in its function, so
+/// shadowed locals can be matched with naga's in declaration order.
+fn shadow_ordinal(index: &SourceIndex, decl: usize) -> usize {
+    let declaration = &index.declarations[decl];
+    index.declarations[..decl]
+        .iter()
+        .filter(|other| {
+            other.kind == declaration.kind
+                && other.name == declaration.name
+                && other.parent == declaration.parent
+        })
+        .count()
+}
+
+/// The type of a declaration, resolved through the module if the document parsed and
+/// as written in the source otherwise.
+pub fn type_of(document: &Document, index: &SourceIndex, decl: usize) -> Option<String> {
+    let resolved = document
+        .module
+        .as_ref()
+        .and_then(|module| declaration_type(module, document.info.as_deref(), index, decl));
+    resolved.or_else(|| index.declarations[decl].ty.clone())
+}
+
+/// The type of the expression ending with the token at `end`, for the simple
+/// expressions that appear before a `.`: names, member accesses, indexing and calls.
+pub fn expression_type(document: &Document, index: &SourceIndex, end: usize) -> Option<String> {
+    let text = &document.text;
+    let token = index.tokens.get(end)?;
+    let ty = match token.text(text) {
+        "]" => {
+            let open = index.matching[end]?;
+            let base = expression_type(document, index, open.checked_sub(1)?)?;
+            element_type(&base)?
+        }
+        ")" => {
+            let open = index.matching[end]?;
+            let callee = index.tokens.get(open.checked_sub(1)?)?;
+            let decl = index.resolve(callee.text(text), callee.start)?;
+            match index.declarations[decl].kind {
+                DeclarationKind::Function => index.declarations[decl].ty.clone()?,
+                // a constructor such as `Light(...)`
+                DeclarationKind::Struct | DeclarationKind::Alias => callee.text(text).to_string(),
+                _ => return None,
+            }
+        }
+        name if token.kind == TokenKind::Ident => {
+            if end >= 2 && index.tokens[end - 1].text(text) == "." {
+                let base = expression_type(document, index, end - 2)?;
+                let structure = index.find_struct(&base)?;
+                let member = index
+                    .members(structure)
+                    .find(|member| member.name == name)?;
+                member.ty.clone()?
+            } else {
+                let decl = index.resolve(name, token.start)?;
+                type_of(document, index, decl)?
+            }
+        }
+        _ => return None,
+    };
+    Some(resolve_alias(index, ty))
+}
+
+/// The type the expression at `offset` should have, when it is an argument of a user
+/// function, the initializer of a declaration with a type, or a returned value.
+pub fn expected_type(document: &Document, index: &SourceIndex, offset: usize) -> Option<String> {
+    let text = &document.text;
+
+    if let Some((callee, argument)) = index.enclosing_call(text, offset) {
+        let callee = index.tokens[callee];
+        let function = index
+            .resolve(callee.text(text), callee.start)
+            .filter(|&decl| index.declarations[decl].kind == DeclarationKind::Function);
+        if let Some(function) = function {
+            let param = index.params(function).nth(argument as usize)?;
+            return param.ty.clone();
+        }
+    }
+
+    // the start of the statement the cursor is in
+    let mut start = index.token_before(offset)?;
+    loop {
+        match index.tokens[start].text(text) {
+            ";" | "{" | "}" => {
+                start += 1;
+                break;
+            }
+            ")" | "]" => start = index.matching[start].unwrap_or(start),
+            _ => {}
+        }
+        if start == 0 {
+            break;
+        }
+        start -= 1;
+    }
+    let start = index.tokens.get(start)?;
+
+    if start.text(text) == "return" {
+        return index
+            .declarations
+            .iter()
+            .find(|decl| {
+                decl.kind == DeclarationKind::Function
+                    && decl
+                        .body
+                        .as_ref()
+                        .is_some_and(|body| body.contains(&offset))
+            })?
+            .ty
+            .clone();
+    }
+
+    // `let name: ty = |`
+    let declaration = index.declarations.iter().rev().find(|decl| {
+        decl.kind != DeclarationKind::Member
+            && start.start <= decl.selection.start
+            && decl.selection.end <= offset
+    })?;
+    let assigned = index.tokens.iter().any(|token| {
+        declaration.selection.end <= token.start && token.end <= offset && token.text(text) == "="
+    });
+    if assigned {
+        declaration.ty.clone()
+    } else {
+        None
+    }
+}
+
+/// The struct member named by the identifier at `offset`, when it follows a `.` and the
+/// expression before it is a struct.
+pub fn member_at(document: &Document, index: &SourceIndex, offset: usize) -> Option<usize> {
+    let text = &document.text;
+    let token = index.ident_at(offset)?;
+    let position = index
+        .tokens
+        .iter()
+        .position(|other| other.start == token.start)?;
+    if position < 2 || index.tokens[position - 1].text(text) != "." {
+        return None;
+    }
+    let base = expression_type(document, index, position - 2)?;
+    let structure = index.find_struct(&base)?;
+    index.declarations.iter().position(|decl| {
+        decl.kind == DeclarationKind::Member
+            && decl.parent == Some(structure)
+            && decl.name == token.text(text)
+    })
+}
+
+/// Replaces an alias name with the type it stands for.
+fn resolve_alias(index: &SourceIndex, ty: String) -> String {
+    let mut ty = ty;
+    // bounded, in case of cyclic aliases
+    for _ in 0..8 {
+        let alias = index
+            .declarations
+            .iter()
+            .find(|decl| decl.kind == DeclarationKind::Alias && decl.name == ty);
+        match alias.and_then(|alias| alias.ty.clone()) {
+            Some(target) => ty = target,
+            None => break,
+        }
+    }
+    ty
+}
+
+/// The element type of `array<T, N>` or `array<T>`.
+fn element_type(ty: &str) -> Option<String> {
+    let inner = ty.strip_prefix("array<")?.strip_suffix('>')?;
+    let mut depth = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => return Some(inner[..i].trim().to_string()),
+            _ => {}
+        }
+    }
+    Some(inner.trim().to_string())
+}
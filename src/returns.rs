@@ -0,0 +1,201 @@
+//! A check that functions returning a value do so on every path, which naga only reports
+//! as a mismatched return type somewhere in the function.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use naga::Module;
+
+use crate::position::byte_range_to_range;
+use crate::resolve::find_function;
+use crate::syntax::{DeclarationKind, SourceIndex};
+use crate::types::type_name;
+
+/// Reports every function with a return type whose body can reach its closing brace,
+/// pointing at the brace and naming the return type naga resolved.
+pub fn missing_returns(
+    text: &str,
+    index: &SourceIndex,
+    module: &Module,
+    source: &str,
+) -> Vec<Diagnostic> {
+    let walker = Walker { index, text };
+    let mut diags = Vec::new();
+    for declaration in &index.declarations {
+        let (ty, body) = match (&declaration.ty, &declaration.body) {
+            (Some(ty), Some(body)) if declaration.kind == DeclarationKind::Function => (ty, body),
+            _ => continue,
+        };
+        let open = match index
+            .tokens
+            .iter()
+            .position(|token| token.start == body.start)
+        {
+            Some(open) => open,
+            None => continue,
+        };
+        let close = match index.matching[open] {
+            Some(close) => close,
+            // the parser reports the unclosed brace
+            None => continue,
+        };
+        if walker.block_returns(open) {
+            continue;
+        }
+        let ty = find_function(module, &declaration.name)
+            .and_then(|(_, function)| function.result.as_ref())
+            .map_or_else(|| ty.clone(), |result| type_name(module, result.ty));
+        diags.push(Diagnostic {
+            range: byte_range_to_range(text, index.tokens[close].span()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String("missing-return".to_string())),
+            source: Some(source.to_string()),
+            message: format!(
+                "function `{}` must return a value of type `{}`, but can reach its end without returning",
+                declaration.name, ty
+            ),
+            ..Default::default()
+        });
+    }
+    diags
+}
+
+/// Follows the statements of function bodies by their tokens.
+struct Walker<'a> {
+    index: &'a SourceIndex,
+    text: &'a str,
+}
+
+impl Walker<'_> {
+    fn word(&self, token: usize) -> &str {
+        self.index
+            .tokens
+            .get(token)
+            .map_or("", |token| token.text(self.text))
+    }
+
+    /// The token after the bracket pair `open` starts, or after `open` if it is unclosed.
+    fn after(&self, open: usize) -> usize {
+        self.index.matching[open].map_or(open, |close| close) + 1
+    }
+
+    /// The first `{` from `token` on, skipping over parenthesized expressions.
+    fn next_brace(&self, mut token: usize, end: usize) -> Option<usize> {
+        while token < end {
+            match self.word(token) {
+                "{" => return Some(token),
+                "(" | "[" => token = self.after(token),
+                _ => token += 1,
+            }
+        }
+        None
+    }
+
+    /// Whether every path through the block opened at `open` returns or discards.
+    fn block_returns(&self, open: usize) -> bool {
+        let close = match self.index.matching[open] {
+            Some(close) => close,
+            None => return false,
+        };
+        let mut token = open + 1;
+        while token < close {
+            let (returns, next) = match self.word(token) {
+                "return" | "discard" => return true,
+                "{" => (self.block_returns(token), self.after(token)),
+                "if" => self.if_returns(token, close),
+                "switch" => self.switch_returns(token, close),
+                "loop" => match self.next_brace(token, close) {
+                    // a loop that never breaks never falls through
+                    Some(body) => (!self.breaks(body), self.after(body)),
+                    None => (false, close),
+                },
+                _ => (false, self.statement_end(token, close)),
+            };
+            if returns {
+                return true;
+            }
+            token = next;
+        }
+        false
+    }
+
+    /// Whether every branch of the `if` at `token` returns, which needs a final `else`,
+    /// along with the token after it.
+    fn if_returns(&self, token: usize, end: usize) -> (bool, usize) {
+        let body = match self.next_brace(token + 1, end) {
+            Some(body) => body,
+            None => return (false, end),
+        };
+        let returns = self.block_returns(body);
+        let next = self.after(body);
+        if self.word(next) != "else" {
+            return (false, next);
+        }
+        if self.word(next + 1) == "if" {
+            let (rest, next) = self.if_returns(next + 1, end);
+            return (returns && rest, next);
+        }
+        match self.next_brace(next + 1, end) {
+            Some(body) => (returns && self.block_returns(body), self.after(body)),
+            None => (false, end),
+        }
+    }
+
+    /// Whether the `switch` at `token` has a default and every clause returns, along with
+    /// the token after it.
+    fn switch_returns(&self, token: usize, end: usize) -> (bool, usize) {
+        let body = match self.next_brace(token + 1, end) {
+            Some(body) => body,
+            None => return (false, end),
+        };
+        let close = self.after(body) - 1;
+        let mut has_default = false;
+        let mut all_return = true;
+        let mut clause = body + 1;
+        while clause < close {
+            match self.word(clause) {
+                "{" => {
+                    all_return &= self.block_returns(clause);
+                    clause = self.after(clause);
+                }
+                "default" => {
+                    has_default = true;
+                    clause += 1;
+                }
+                "(" | "[" => clause = self.after(clause),
+                _ => clause += 1,
+            }
+        }
+        (has_default && all_return, close + 1)
+    }
+
+    /// Whether the loop body opened at `open` breaks out of the loop, not counting breaks
+    /// out of loops and switches nested in it.
+    fn breaks(&self, open: usize) -> bool {
+        let close = self.after(open) - 1;
+        let mut token = open + 1;
+        while token < close {
+            match self.word(token) {
+                "break" => return true,
+                "loop" | "for" | "while" | "switch" => match self.next_brace(token + 1, close) {
+                    Some(body) => token = self.after(body),
+                    None => return false,
+                },
+                _ => token += 1,
+            }
+        }
+        false
+    }
+
+    /// The token after the simple statement or the loop starting at `token`.
+    fn statement_end(&self, mut token: usize, end: usize) -> usize {
+        while token < end {
+            match self.word(token) {
+                ";" => return token + 1,
+                "{" => return self.after(token),
+                "(" | "[" => token = self.after(token),
+                _ => token += 1,
+            }
+        }
+        end
+    }
+}
@@ -0,0 +1,148 @@
+//! Checks that textures are sampled with samplers they can be used with, which drivers
+//! only reject when the pipeline is created.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Url};
+
+use naga::valid::{FunctionInfo, ModuleInfo};
+use naga::{Expression, Function, Handle, ImageClass, Module, ScalarKind, Span, TypeInner};
+
+use crate::includes::SourceMap;
+
+/// Reports texture sampling calls whose texture and sampler don't go together, at the
+/// offending argument: depth comparisons need a depth texture and a comparison sampler,
+/// which can't be used without one, and only float textures that are neither
+/// multisampled nor storage textures can be filtered.
+///
+/// The operand types come from `info` when the module validated, and otherwise from the
+/// globals and arguments passed, as textures and samplers can't come from anywhere else.
+pub fn check(
+    uri: &Url,
+    text: &str,
+    map: &SourceMap,
+    module: &Module,
+    info: Option<&ModuleInfo>,
+    source: &str,
+) -> Vec<(Url, Diagnostic)> {
+    let functions = module
+        .functions
+        .iter()
+        .map(|(handle, function)| (function, info.map(|info| &info[handle])));
+    let entry_points = module
+        .entry_points
+        .iter()
+        .enumerate()
+        .map(|(index, entry_point)| {
+            let function_info = info.map(|info| info.get_entry_point(index));
+            (&entry_point.function, function_info)
+        });
+
+    let mut diags = Vec::new();
+    for (function, function_info) in functions.chain(entry_points) {
+        for (handle, expression) in function.expressions.iter() {
+            let (image, sampler, gather, depth_ref) = match *expression {
+                Expression::ImageSample {
+                    image,
+                    sampler,
+                    gather,
+                    depth_ref,
+                    ..
+                } => (image, sampler, gather, depth_ref),
+                _ => continue,
+            };
+            let operand = |expr| operand_type(module, function, function_info, expr);
+            let comparison = depth_ref.is_some();
+            let image_problem = match operand(image) {
+                Some(&TypeInner::Image {
+                    class: ImageClass::Storage { .. },
+                    ..
+                }) => Some("storage textures can't be sampled, only loaded from".to_string()),
+                Some(&TypeInner::Image {
+                    class: ImageClass::Sampled { multi: true, .. },
+                    ..
+                })
+                | Some(&TypeInner::Image {
+                    class: ImageClass::Depth { multi: true },
+                    ..
+                }) => Some("multisampled textures can't be sampled, only loaded from".to_string()),
+                Some(&TypeInner::Image {
+                    class: ImageClass::Sampled { kind, .. },
+                    ..
+                }) if comparison => Some(format!(
+                    "sampling with a depth comparison needs a depth texture, not a {} one",
+                    kind_name(kind)
+                )),
+                Some(&TypeInner::Image {
+                    class: ImageClass::Sampled { kind, .. },
+                    ..
+                }) if kind != ScalarKind::Float && gather.is_none() => Some(format!(
+                    "{} textures can't be filtered, only gathered from or loaded from",
+                    kind_name(kind)
+                )),
+                _ => None,
+            };
+            let sampler_problem = match operand(sampler) {
+                Some(&TypeInner::Sampler { comparison: false }) if comparison => Some(
+                    "sampling with a depth comparison needs a `sampler_comparison`, not a `sampler`"
+                        .to_string(),
+                ),
+                Some(&TypeInner::Sampler { comparison: true }) if !comparison => Some(
+                    "a `sampler_comparison` can only sample with a depth comparison".to_string(),
+                ),
+                _ => None,
+            };
+
+            let problems = [(image, image_problem), (sampler, sampler_problem)];
+            for (argument, problem) in problems.iter() {
+                let message = match problem {
+                    Some(message) => message.clone(),
+                    None => continue,
+                };
+                // fall back to the whole call if the argument has no span of its own
+                let span = match function.expressions.get_span(*argument) {
+                    span if span == Span::default() => function.expressions.get_span(handle),
+                    span => span,
+                };
+                let location = map.locate(uri, text, span);
+                let diag = Diagnostic {
+                    range: location.range,
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    code: Some(NumberOrString::String(
+                        "texture-sampler-mismatch".to_string(),
+                    )),
+                    source: Some(source.to_string()),
+                    message,
+                    ..Default::default()
+                };
+                diags.push((location.uri, diag));
+            }
+        }
+    }
+    diags
+}
+
+/// The type of a texture or sampler operand of `function`.
+fn operand_type<'a>(
+    module: &'a Module,
+    function: &'a Function,
+    info: Option<&'a FunctionInfo>,
+    expr: Handle<Expression>,
+) -> Option<&'a TypeInner> {
+    if let Some(info) = info {
+        return Some(info[expr].ty.inner_with(&module.types));
+    }
+    let ty = match function.expressions[expr] {
+        Expression::GlobalVariable(global) => module.global_variables[global].ty,
+        Expression::FunctionArgument(index) => function.arguments.get(index as usize)?.ty,
+        _ => return None,
+    };
+    Some(&module.types[ty].inner)
+}
+
+fn kind_name(kind: ScalarKind) -> &'static str {
+    match kind {
+        ScalarKind::Sint => "signed integer",
+        ScalarKind::Uint => "unsigned integer",
+        ScalarKind::Float => "float",
+        ScalarKind::Bool => "boolean",
+    }
+}
@@ -0,0 +1,126 @@
+use std::ops::Range;
+
+use lsp_types::{Position, SelectionRange};
+
+use crate::document::Document;
+use crate::position::{byte_range_to_range, position_to_byte_offset};
+use crate::syntax::SourceIndex;
+
+/// The ranges to grow the selection through from each position: the identifier, the
+/// brackets around it, the statement, the block and finally the whole declaration.
+pub fn selection_ranges(document: &Document, positions: Vec<Position>) -> Vec<SelectionRange> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    positions
+        .into_iter()
+        .map(|position| {
+            let offset = position_to_byte_offset(text, position);
+            let mut ranges = enclosing_ranges(&index, text, offset);
+            ranges.sort_by_key(|range| (range.len(), std::cmp::Reverse(range.start)));
+
+            // only keep properly nested ranges, and link them from the outside in
+            let mut nested: Vec<Range<usize>> = Vec::new();
+            for range in ranges {
+                match nested.last() {
+                    Some(last) if range == *last => {}
+                    Some(last) if range.start > last.start || range.end < last.end => {}
+                    _ => nested.push(range),
+                }
+            }
+            nested
+                .into_iter()
+                .rev()
+                .fold(None, |parent, range| {
+                    Some(SelectionRange {
+                        range: byte_range_to_range(text, range),
+                        parent: parent.map(Box::new),
+                    })
+                })
+                .unwrap_or(SelectionRange {
+                    range: lsp_types::Range {
+                        start: position,
+                        end: position,
+                    },
+                    parent: None,
+                })
+        })
+        .collect()
+}
+
+/// Every syntactic range containing `offset`, in no particular order.
+fn enclosing_ranges(index: &SourceIndex, text: &str, offset: usize) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    if let Some(token) = index.ident_at(offset) {
+        ranges.push(token.span());
+    }
+
+    for (open, token) in index.tokens.iter().enumerate() {
+        let close = match index.matching[open] {
+            Some(close) if close > open => close,
+            _ => continue,
+        };
+        let close_token = index.tokens[close];
+        if !(token.end <= offset && offset <= close_token.start) {
+            continue;
+        }
+        if close > open + 1 {
+            ranges.push(index.tokens[open + 1].start..index.tokens[close - 1].end);
+        }
+        ranges.push(token.start..close_token.end);
+        if token.text(text) == "{" {
+            if let Some(statement) = statement_at(index, text, open, close, offset) {
+                ranges.push(statement);
+            }
+        }
+    }
+
+    for decl in &index.declarations {
+        let end = match decl.body {
+            Some(ref body) => body.end,
+            None => decl.full.end,
+        };
+        if decl.full.start <= offset && offset <= end {
+            ranges.push(decl.full.start..end);
+        }
+    }
+
+    ranges.push(0..text.len());
+    ranges
+}
+
+/// The statement directly inside the braces at `open` and `close` that contains
+/// `offset`.
+fn statement_at(
+    index: &SourceIndex,
+    text: &str,
+    open: usize,
+    close: usize,
+    offset: usize,
+) -> Option<Range<usize>> {
+    let mut start = open + 1;
+    let mut i = open + 1;
+    while i < close {
+        let ends_statement = match index.tokens[i].text(text) {
+            ";" => true,
+            "(" | "[" => {
+                i = index.matching[i].unwrap_or(close);
+                false
+            }
+            "{" => {
+                i = index.matching[i].unwrap_or(close);
+                // `if` chains go on after the block
+                i < close && index.tokens.get(i + 1).map(|next| next.text(text)) != Some("else")
+            }
+            _ => false,
+        };
+        if ends_statement || i + 1 >= close {
+            let statement = index.tokens[start].start..index.tokens[i.min(close - 1)].end;
+            if statement.start <= offset && offset <= statement.end {
+                return Some(statement);
+            }
+            start = i + 1;
+        }
+        i += 1;
+    }
+    None
+}
@@ -0,0 +1,165 @@
+//! Semantic highlighting, classified from the source index so it keeps working while the
+//! document has parse errors.
+
+use lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensLegend,
+};
+
+use crate::builtins;
+use crate::document::Document;
+use crate::position::text_len;
+use crate::syntax::{is_predeclared_type, DeclarationKind, SourceIndex, TokenKind, KEYWORDS};
+
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::STRUCT,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::DECORATOR,
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::NUMBER,
+];
+
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+
+// indices into TOKEN_TYPES
+const FUNCTION: u32 = 0;
+const STRUCT: u32 = 1;
+const TYPE: u32 = 2;
+const PROPERTY: u32 = 3;
+const PARAMETER: u32 = 4;
+const VARIABLE: u32 = 5;
+const DECORATOR: u32 = 6;
+const KEYWORD: u32 = 7;
+const NUMBER: u32 = 8;
+
+// bits of the modifier set, in TOKEN_MODIFIERS order
+const DECLARATION: u32 = 1 << 0;
+const READONLY: u32 = 1 << 1;
+const DEFAULT_LIBRARY: u32 = 1 << 2;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+pub fn semantic_tokens(document: &Document) -> SemanticTokens {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+
+    // (start, end, type, modifiers), in source order
+    let mut classified = Vec::new();
+    let mut i = 0;
+    while i < index.tokens.len() {
+        let token = index.tokens[i];
+        let name = token.text(text);
+        let previous = i
+            .checked_sub(1)
+            .map(|previous| index.tokens[previous].text(text));
+        let next = index.tokens.get(i + 1);
+
+        if name == "@" {
+            // an attribute name directly follows its `@`
+            if let Some(next) = next.filter(|next| next.start == token.end) {
+                classified.push((token.start, next.end, DECORATOR, 0));
+                i += 2;
+                continue;
+            }
+        }
+
+        let class = match token.kind {
+            TokenKind::Number => Some((NUMBER, 0)),
+            TokenKind::Ident if KEYWORDS.contains(&name) => Some((KEYWORD, 0)),
+            TokenKind::Ident if previous == Some(".") => Some((PROPERTY, 0)),
+            TokenKind::Ident => {
+                if let Some(decl) = index
+                    .declarations
+                    .iter()
+                    .find(|decl| decl.selection == token.span())
+                {
+                    let (ty, modifiers) = declaration_class(decl.kind);
+                    Some((ty, modifiers | DECLARATION))
+                } else if let Some(decl) = index.resolve(name, token.start) {
+                    Some(declaration_class(index.declarations[decl].kind))
+                } else if is_predeclared_type(name) {
+                    Some((TYPE, DEFAULT_LIBRARY))
+                } else if builtins::find(name).is_some()
+                    && next.map(|next| next.text(text)) == Some("(")
+                {
+                    Some((FUNCTION, DEFAULT_LIBRARY))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some((ty, modifiers)) = class {
+            classified.push((token.start, token.end, ty, modifiers));
+        }
+        i += 1;
+    }
+
+    SemanticTokens {
+        result_id: None,
+        data: encode(text, &classified),
+    }
+}
+
+fn declaration_class(kind: DeclarationKind) -> (u32, u32) {
+    match kind {
+        DeclarationKind::Function => (FUNCTION, 0),
+        DeclarationKind::Struct => (STRUCT, 0),
+        DeclarationKind::Alias => (TYPE, 0),
+        DeclarationKind::Member => (PROPERTY, 0),
+        DeclarationKind::Param => (PARAMETER, 0),
+        DeclarationKind::GlobalVar | DeclarationKind::Var => (VARIABLE, 0),
+        DeclarationKind::Const
+        | DeclarationKind::Override
+        | DeclarationKind::Let
+        | DeclarationKind::LocalConst => (VARIABLE, READONLY),
+    }
+}
+
+/// Delta-encodes single-line tokens, given in source order, with columns in the
+/// negotiated position encoding.
+fn encode(text: &str, classified: &[(usize, usize, u32, u32)]) -> Vec<SemanticToken> {
+    let mut data = Vec::with_capacity(classified.len());
+    let (mut line, mut character) = (0, 0);
+    let (mut previous_line, mut previous_character) = (0, 0);
+    let mut cursor = 0;
+    for &(start, end, token_type, token_modifiers_bitset) in classified {
+        match text[cursor..start].rfind('\n') {
+            Some(newline) => {
+                line += text[cursor..start].matches('\n').count() as u32;
+                character = text_len(&text[cursor + newline + 1..start]);
+            }
+            None => character += text_len(&text[cursor..start]),
+        }
+        cursor = start;
+
+        let delta_line = line - previous_line;
+        let delta_start = if delta_line == 0 {
+            character - previous_character
+        } else {
+            character
+        };
+        data.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length: text_len(&text[start..end]),
+            token_type,
+            token_modifiers_bitset,
+        });
+        previous_line = line;
+        previous_character = character;
+    }
+    data
+}
@@ -0,0 +1,93 @@
+use lsp_types::{
+    Documentation, ParameterInformation, ParameterLabel, Position, SignatureHelp,
+    SignatureInformation,
+};
+
+use crate::builtins;
+use crate::document::Document;
+use crate::position::position_to_byte_offset;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// The signatures of the user function or builtin whose call the cursor is in.
+pub fn signature_help(document: &Document, position: Position) -> Option<SignatureHelp> {
+    let text = &document.text;
+    let offset = position_to_byte_offset(text, position);
+    let index = SourceIndex::new(text);
+
+    let (callee, active_parameter) = index.enclosing_call(text, offset)?;
+    let callee = index.tokens[callee];
+    let name = callee.text(text);
+
+    let user_function = index
+        .resolve(name, callee.start)
+        .filter(|&decl| index.declarations[decl].kind == DeclarationKind::Function);
+    let signatures = match user_function {
+        Some(function) => {
+            let params = index
+                .params(function)
+                .map(|param| match param.ty {
+                    Some(ref ty) => format!("{}: {}", param.name, ty),
+                    None => param.name.clone(),
+                })
+                .collect::<Vec<_>>();
+            let mut label = format!("{}({})", name, params.join(", "));
+            if let Some(ref ty) = index.declarations[function].ty {
+                label.push_str(&format!(" -> {}", ty));
+            }
+            vec![signature_information(label, params, None)]
+        }
+        None => {
+            let builtin = builtins::find(name)?;
+            builtin
+                .signatures
+                .iter()
+                .map(|signature| {
+                    let params = builtins::parameters(signature)
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect();
+                    signature_information(signature.to_string(), params, Some(builtin.doc))
+                })
+                .collect()
+        }
+    };
+
+    // prefer the first overload that takes enough arguments
+    let active_signature = signatures
+        .iter()
+        .position(|signature| {
+            signature
+                .parameters
+                .as_ref()
+                .map_or(0, |params| params.len())
+                > active_parameter as usize
+        })
+        .unwrap_or(0);
+
+    Some(SignatureHelp {
+        signatures,
+        active_signature: Some(active_signature as u32),
+        active_parameter: Some(active_parameter),
+    })
+}
+
+fn signature_information(
+    label: String,
+    params: Vec<String>,
+    doc: Option<&str>,
+) -> SignatureInformation {
+    SignatureInformation {
+        label,
+        documentation: doc.map(|doc| Documentation::String(doc.to_string())),
+        parameters: Some(
+            params
+                .into_iter()
+                .map(|param| ParameterInformation {
+                    label: ParameterLabel::Simple(param),
+                    documentation: None,
+                })
+                .collect(),
+        ),
+        active_parameter: None,
+    }
+}
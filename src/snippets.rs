@@ -0,0 +1,31 @@
+//! The scaffolds completion offers at the top level of a shader, as snippets whose
+//! `${n:placeholder}` tab stops the client visits in order.
+
+pub struct Snippet {
+    pub label: &'static str,
+    pub detail: &'static str,
+    pub body: &'static str,
+}
+
+pub const SNIPPETS: &[Snippet] = &[
+    Snippet {
+        label: "vertex",
+        detail: "vertex entry point",
+        body: "@vertex\nfn ${1:vs_main}(@builtin(vertex_index) ${2:index}: u32) -> @builtin(position) vec4<f32> {\n\t$0\n\treturn vec4<f32>(0.0, 0.0, 0.0, 1.0);\n}",
+    },
+    Snippet {
+        label: "fragment",
+        detail: "fragment entry point",
+        body: "@fragment\nfn ${1:fs_main}() -> @location(0) vec4<f32> {\n\t$0\n\treturn vec4<f32>(1.0, 1.0, 1.0, 1.0);\n}",
+    },
+    Snippet {
+        label: "compute",
+        detail: "compute entry point",
+        body: "@compute @workgroup_size(${1:64})\nfn ${2:cs_main}(@builtin(global_invocation_id) ${3:id}: vec3<u32>) {\n\t$0\n}",
+    },
+    Snippet {
+        label: "struct",
+        detail: "struct declaration",
+        body: "struct ${1:Name} {\n\t${2:member}: ${3:f32},\n}",
+    },
+];
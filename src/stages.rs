@@ -0,0 +1,86 @@
+//! The stage to assume for documents that don't say, such as GLSL files without a
+//! stage extension and WGSL fragments without an entry point.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+use naga::valid::{ModuleInfo, ShaderStages};
+use naga::{Module, ShaderStage};
+
+use crate::commands::{parse_stage, stage_name};
+use crate::config::Config;
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// What comments at the top of a document start with to pick its stage, as in
+/// `// naga-lsp: stage=fragment`.
+const MAGIC_COMMENT: &str = "naga-lsp:";
+
+/// The stage `text` is meant for, as picked by a magic comment among the comments it
+/// starts with, or else by the `defaultStage` setting. `None` if neither says.
+pub fn assumed(text: &str, config: &Config) -> Option<ShaderStage> {
+    let comment = text
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("//"))
+        .filter_map(|line| {
+            let stage = line
+                .trim_start_matches('/')
+                .trim()
+                .strip_prefix(MAGIC_COMMENT)?
+                .trim()
+                .strip_prefix("stage")?
+                .trim_start()
+                .strip_prefix('=')?
+                .trim();
+            parse_stage(stage)
+                .map_err(|err| log::warn!("{} in magic comment", err))
+                .ok()
+        })
+        .last();
+    comment.or_else(|| config.default_stage())
+}
+
+/// Reports the functions of a module without entry points that use operations `stage`
+/// doesn't allow, such as derivatives outside fragment shaders, which naga only checks
+/// for the entry points calling them.
+pub fn check(
+    text: &str,
+    index: &SourceIndex,
+    module: &Module,
+    info: &ModuleInfo,
+    stage: ShaderStage,
+    source: &str,
+) -> Vec<Diagnostic> {
+    if !module.entry_points.is_empty() {
+        return Vec::new();
+    }
+    let required = match stage {
+        ShaderStage::Vertex => ShaderStages::VERTEX,
+        ShaderStage::Fragment => ShaderStages::FRAGMENT,
+        ShaderStage::Compute => ShaderStages::COMPUTE,
+    };
+    module
+        .functions
+        .iter()
+        .filter(|&(handle, _)| !info[handle].available_stages.contains(required))
+        .filter_map(|(_, function)| {
+            let name = function.name.as_deref()?;
+            let decl = index
+                .declarations
+                .iter()
+                .find(|decl| decl.kind == DeclarationKind::Function && decl.name == name)?;
+            Some(Diagnostic {
+                range: byte_range_to_range(text, decl.selection.clone()),
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: Some(NumberOrString::String("stage-mismatch".to_string())),
+                source: Some(source.to_string()),
+                message: format!(
+                    "`{}` uses operations a {} shader can't, which this document is assumed \
+                     to be",
+                    name,
+                    stage_name(stage)
+                ),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
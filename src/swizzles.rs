@@ -0,0 +1,168 @@
+//! Checks the components of vector swizzles, pointing at the first bad one, which says
+//! more than the type errors naga gives for them.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use crate::document::Document;
+use crate::position::byte_range_to_range;
+use crate::resolve::expression_type;
+use crate::syntax::{SourceIndex, TokenKind};
+use crate::types::canonical_type_name;
+
+/// The two ways to name the components, which a swizzle can't mix.
+const SETS: &[&str] = &["xyzw", "rgba"];
+
+/// Reports swizzles of vectors that use components which don't exist or which the vector
+/// doesn't have, mix the two sets of names, take more than four components, or assign
+/// to several components at once.
+pub fn check(document: &Document, index: &SourceIndex, source: &str) -> Vec<Diagnostic> {
+    let text = &document.text;
+    let mut diags = Vec::new();
+    for (i, token) in index.tokens.iter().enumerate() {
+        if token.kind != TokenKind::Ident || i < 2 || index.tokens[i - 1].text(text) != "." {
+            continue;
+        }
+        let ty = match expression_type(document, index, i - 2) {
+            Some(ty) => canonical_type_name(&ty),
+            None => continue,
+        };
+        let size = match ty.strip_prefix("vec").and_then(|rest| rest.chars().next()) {
+            Some(size) => match size.to_digit(10) {
+                Some(size) => size as usize,
+                None => continue,
+            },
+            None => continue,
+        };
+        // `v.x = ..` and `v.x += ..`, but not `v.x == ..`
+        let next = |offset: usize| index.tokens.get(i + offset).map(|token| token.text(text));
+        let assigned = match next(1) {
+            Some("=") => next(2) != Some("="),
+            Some("+") | Some("-") | Some("*") | Some("/") | Some("%") | Some("&") | Some("|")
+            | Some("^") => next(2) == Some("="),
+            _ => false,
+        };
+        if let Some((offset, len, message)) = problem(token.text(text), size, &ty, assigned) {
+            let start = token.start + offset;
+            diags.push(Diagnostic {
+                range: byte_range_to_range(text, start..start + len),
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: Some(NumberOrString::String("invalid-swizzle".to_string())),
+                source: Some(source.to_string()),
+                message,
+                ..Default::default()
+            });
+        }
+    }
+    diags
+}
+
+/// The first thing wrong with the swizzle `components` of a vector of `size`, as the
+/// offset and length of the component at fault and a message.
+fn problem(
+    components: &str,
+    size: usize,
+    ty: &str,
+    assigned: bool,
+) -> Option<(usize, usize, String)> {
+    let set = components
+        .chars()
+        .next()
+        .and_then(|first| SETS.iter().find(|set| set.contains(first)));
+    for (count, (offset, c)) in components.char_indices().enumerate() {
+        let len = c.len_utf8();
+        let position = match SETS.iter().find_map(|set| set.find(c)) {
+            Some(position) => position,
+            None => {
+                return Some((
+                    offset,
+                    len,
+                    format!(
+                        "`{}` isn't a vector component, which are x, y, z, w or r, g, b, a",
+                        c
+                    ),
+                ));
+            }
+        };
+        if let Some(set) = set.filter(|set| !set.contains(c)) {
+            let name = &set[position..position + 1];
+            return Some((
+                offset,
+                len,
+                format!(
+                    "`{}` mixes component names, use `{}` to go with `{}`",
+                    c,
+                    name,
+                    &components[..1]
+                ),
+            ));
+        }
+        if position >= size {
+            return Some((
+                offset,
+                len,
+                format!(
+                    "`{}` is out of range for a {}, which has only {} components",
+                    c, ty, size
+                ),
+            ));
+        }
+        if count == 4 {
+            return Some((
+                offset,
+                components.len() - offset,
+                format!(
+                    "a swizzle takes at most 4 components, `{}` has more",
+                    components
+                ),
+            ));
+        }
+        if assigned && components[..offset].contains(c) {
+            return Some((
+                offset,
+                len,
+                format!("`{}` is assigned twice by the swizzle `{}`", c, components),
+            ));
+        }
+    }
+    if assigned && components.chars().count() > 1 {
+        return Some((
+            0,
+            components.len(),
+            format!(
+                "WGSL can't assign to the swizzle `{}`, only to one component at a time",
+                components
+            ),
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Language;
+
+    fn swizzle_errors(text: &str) -> Vec<Diagnostic> {
+        let document = Document::new(text.to_string(), 0, Language::Wgsl);
+        let index = SourceIndex::new(text);
+        check(&document, &index, "test")
+    }
+
+    #[test]
+    fn flags_components_the_vector_lacks() {
+        let diags = swizzle_errors(
+            "fn main() {\n    let v: vec2<f32> = vec2<f32>(1.0);\n    let a = v.xz;\n}\n",
+        );
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        // just the `z`
+        assert_eq!(diags[0].range.start, lsp_types::Position::new(2, 15));
+        assert_eq!(diags[0].range.end, lsp_types::Position::new(2, 16));
+    }
+
+    #[test]
+    fn accepts_components_within_the_vector() {
+        let text =
+            "fn main() {\n    let v: vec2<f32> = vec2<f32>(1.0);\n    let a = v.yx + v.rg;\n}\n";
+        assert!(swizzle_errors(text).is_empty());
+    }
+}
@@ -0,0 +1,202 @@
+use lsp_types::{DocumentSymbol, Location, SymbolInformation, SymbolKind, Url};
+
+use crate::document::{Document, DocumentStore, Language};
+use crate::position::byte_range_to_range;
+use crate::syntax::{Declaration, DeclarationKind, SourceIndex};
+use crate::workspace::Workspace;
+
+/// The module-scope declarations of a document, with struct members nested under their
+/// struct and the parameters and locals of a function under it, so that clients can
+/// show where the cursor is as a breadcrumb.
+pub fn document_symbols(document: &Document) -> Vec<DocumentSymbol> {
+    let text = &document.text;
+    let index = SourceIndex::new(text);
+    index
+        .declarations
+        .iter()
+        .enumerate()
+        .filter_map(|(decl, declaration)| {
+            let kind = symbol_kind(declaration.kind)?;
+            let members = index
+                .members(decl)
+                .map(|member| symbol(text, member, SymbolKind::FIELD, None));
+            let locals = index
+                .declarations
+                .iter()
+                .filter(|local| local.kind.is_local() && local.parent == Some(decl))
+                .map(|local| symbol(text, local, local_kind(local.kind), None));
+            let children = members.chain(locals).collect::<Vec<_>>();
+            let children = if children.is_empty() {
+                None
+            } else {
+                Some(children)
+            };
+            Some(symbol(text, declaration, kind, children))
+        })
+        .collect()
+}
+
+/// The module-scope declarations of every open WGSL document, and of the indexed files
+/// of the workspace that aren't open, whose name matches `query`, closest matches first.
+pub fn workspace_symbols(
+    documents: &DocumentStore,
+    workspace: &Workspace,
+    query: &str,
+) -> Vec<SymbolInformation> {
+    let query = query.to_lowercase();
+    let mut matches = Vec::new();
+    for (uri, document) in documents.iter() {
+        if document.language != Language::Wgsl {
+            continue;
+        }
+        let text = &document.text;
+        let index = SourceIndex::new(text);
+        matching_symbols(uri, text, &index, &query, &mut matches);
+    }
+    workspace.with_files(|files| {
+        for (uri, file) in files {
+            if documents.get(uri).is_none() {
+                matching_symbols(uri, &file.text, &file.index, &query, &mut matches);
+            }
+        }
+    });
+    matches.sort_by_key(|(rank, _)| *rank);
+    matches.into_iter().map(|(_, symbol)| symbol).collect()
+}
+
+/// Adds the declarations of one file that match `query` to `matches`, along with how well
+/// they match.
+#[allow(deprecated)]
+fn matching_symbols(
+    uri: &Url,
+    text: &str,
+    index: &SourceIndex,
+    query: &str,
+    matches: &mut Vec<(u8, SymbolInformation)>,
+) {
+    for declaration in &index.declarations {
+        let kind = match symbol_kind(declaration.kind) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let rank = match match_rank(&declaration.name.to_lowercase(), query) {
+            Some(rank) => rank,
+            None => continue,
+        };
+        let symbol = SymbolInformation {
+            name: declaration.name.clone(),
+            kind,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: uri.clone(),
+                range: byte_range_to_range(text, declaration.full.clone()),
+            },
+            container_name: None,
+        };
+        matches.push((rank, symbol));
+    }
+}
+
+/// How well `name` matches `query`, lower being better: a prefix, a substring or just
+/// the characters of the query in order.
+fn match_rank(name: &str, query: &str) -> Option<u8> {
+    if name.starts_with(query) {
+        return Some(0);
+    }
+    if name.contains(query) {
+        return Some(1);
+    }
+    let mut chars = name.chars();
+    if query.chars().all(|q| chars.any(|c| c == q)) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn symbol_kind(kind: DeclarationKind) -> Option<SymbolKind> {
+    match kind {
+        DeclarationKind::Function => Some(SymbolKind::FUNCTION),
+        DeclarationKind::Struct => Some(SymbolKind::STRUCT),
+        DeclarationKind::GlobalVar => Some(SymbolKind::VARIABLE),
+        DeclarationKind::Const | DeclarationKind::Override => Some(SymbolKind::CONSTANT),
+        _ => None,
+    }
+}
+
+fn local_kind(kind: DeclarationKind) -> SymbolKind {
+    match kind {
+        DeclarationKind::LocalConst => SymbolKind::CONSTANT,
+        _ => SymbolKind::VARIABLE,
+    }
+}
+
+// `deprecated` must still be given when building a `DocumentSymbol`
+#[allow(deprecated)]
+fn symbol(
+    text: &str,
+    declaration: &Declaration,
+    kind: SymbolKind,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    // clients may reject symbols whose name isn't within their range, which it should
+    // always be, but a half-typed declaration is better stretched than dropped
+    let range = declaration.full.start.min(declaration.selection.start)
+        ..declaration.full.end.max(declaration.selection.end);
+    DocumentSymbol {
+        name: declaration.name.clone(),
+        detail: declaration.ty.clone(),
+        kind,
+        tags: None,
+        deprecated: None,
+        range: byte_range_to_range(text, range),
+        selection_range: byte_range_to_range(text, declaration.selection.clone()),
+        children,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(outer: &lsp_types::Range, inner: &lsp_types::Range) -> bool {
+        outer.start <= inner.start && inner.end <= outer.end
+    }
+
+    /// Checks that each symbol's name is within its range, and its children within it.
+    fn check_nesting(symbols: &[DocumentSymbol]) {
+        for symbol in symbols {
+            assert!(
+                contains(&symbol.range, &symbol.selection_range),
+                "the name of {} is outside its range",
+                symbol.name
+            );
+            for child in symbol.children.iter().flatten() {
+                assert!(
+                    contains(&symbol.range, &child.range),
+                    "{} is outside {}",
+                    child.name,
+                    symbol.name
+                );
+            }
+            check_nesting(symbol.children.as_deref().unwrap_or_default());
+        }
+    }
+
+    #[test]
+    fn children_nest_inside_their_parent() {
+        let text = "struct Light {\n    color: vec3<f32>,\n    power: f32,\n}\n\n\
+                    fn shade(light: Light) -> f32 {\n    let scale = 2.0;\n    var total = light.power;\n    return total * scale;\n}\n";
+        let document = Document::new(text.to_string(), 0, Language::Wgsl);
+        let symbols = document_symbols(&document);
+        let names = |symbol: &DocumentSymbol| {
+            let children = symbol.children.iter().flatten();
+            children.map(|child| child.name.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(names(&symbols[0]), ["color", "power"]);
+        assert_eq!(names(&symbols[1]), ["light", "scale", "total"]);
+        check_nesting(&symbols);
+    }
+}
@@ -0,0 +1,802 @@
+//! A lightweight lexer and declaration index over WGSL source text.
+//!
+//! naga's IR keeps only a few source spans, and nothing at all once the text stops
+//! parsing, so editor features locate identifiers and their declarations here and only
+//! consult the parsed module to resolve types.
+
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Ident,
+    Number,
+    Punct,
+    Comment,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token {
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn text<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.start..self.end]
+    }
+}
+
+/// Splits `text` into tokens, skipping whitespace. Punctuation is always a single
+/// character, so `->` or `+=` come out as two tokens.
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let rest = &text[start..];
+        let kind = if c.is_whitespace() {
+            continue;
+        } else if rest.starts_with("//") {
+            while let Some(&(_, c)) = chars.peek() {
+                if c == '\n' {
+                    break;
+                }
+                chars.next();
+            }
+            TokenKind::Comment
+        } else if rest.starts_with("/*") {
+            // block comments nest in WGSL
+            chars.next();
+            let mut depth = 1;
+            while depth > 0 {
+                match chars.next() {
+                    Some((i, '/')) if text[i..].starts_with("/*") => {
+                        chars.next();
+                        depth += 1;
+                    }
+                    Some((i, '*')) if text[i..].starts_with("*/") => {
+                        chars.next();
+                        depth -= 1;
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            TokenKind::Comment
+        } else if c.is_alphabetic() || c == '_' {
+            while let Some(&(_, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                chars.next();
+            }
+            TokenKind::Ident
+        } else if c.is_ascii_digit()
+            || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+        {
+            let hex = rest.starts_with("0x") || rest.starts_with("0X");
+            let mut prev = c;
+            while let Some(&(_, c)) = chars.peek() {
+                let exponent_sign = (c == '+' || c == '-')
+                    && if hex {
+                        prev == 'p' || prev == 'P'
+                    } else {
+                        prev == 'e' || prev == 'E'
+                    };
+                if !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || exponent_sign) {
+                    break;
+                }
+                prev = c;
+                chars.next();
+            }
+            TokenKind::Number
+        } else {
+            TokenKind::Punct
+        };
+        let end = match chars.peek() {
+            Some(&(end, _)) => end,
+            None => text.len(),
+        };
+        tokens.push(Token { kind, start, end });
+    }
+    tokens
+}
+
+/// Words that can never name a user declaration.
+pub const KEYWORDS: &[&str] = &[
+    "alias",
+    "break",
+    "case",
+    "const",
+    "const_assert",
+    "continue",
+    "continuing",
+    "default",
+    "diagnostic",
+    "discard",
+    "else",
+    "enable",
+    "false",
+    "fn",
+    "for",
+    "if",
+    "let",
+    "loop",
+    "override",
+    "requires",
+    "return",
+    "struct",
+    "switch",
+    "true",
+    "type",
+    "var",
+    "while",
+];
+
+/// Whether `name` is one of the types or type generators WGSL predeclares.
+pub fn is_predeclared_type(name: &str) -> bool {
+    const TYPES: &[&str] = &[
+        "bool",
+        "i32",
+        "u32",
+        "f32",
+        "f16",
+        "array",
+        "atomic",
+        "ptr",
+        "sampler",
+        "sampler_comparison",
+    ];
+    if TYPES.contains(&name) || name.starts_with("texture_") {
+        return true;
+    }
+    // vecN, matCxR and their shorthands such as vec4f or mat3x3h
+    let dims = if let Some(rest) = name.strip_prefix("vec") {
+        rest
+    } else if let Some(rest) = name.strip_prefix("mat") {
+        match rest.as_bytes() {
+            [c, b'x', ..] if (b'2'..=b'4').contains(c) => &rest[2..],
+            _ => return false,
+        }
+    } else {
+        return false;
+    };
+    match dims.as_bytes() {
+        [n] => (b'2'..=b'4').contains(n),
+        [n, suffix] => (b'2'..=b'4').contains(n) && b"ifuh".contains(suffix),
+        _ => false,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeclarationKind {
+    Function,
+    Struct,
+    Member,
+    Alias,
+    GlobalVar,
+    Const,
+    Override,
+    Param,
+    Let,
+    Var,
+    LocalConst,
+}
+
+impl DeclarationKind {
+    pub fn is_local(self) -> bool {
+        matches!(
+            self,
+            DeclarationKind::Param
+                | DeclarationKind::Let
+                | DeclarationKind::Var
+                | DeclarationKind::LocalConst
+        )
+    }
+
+    /// The keyword that introduces this kind of declaration.
+    pub fn keyword(self) -> &'static str {
+        match self {
+            DeclarationKind::Function => "fn",
+            DeclarationKind::Struct => "struct",
+            DeclarationKind::Alias => "alias",
+            DeclarationKind::GlobalVar | DeclarationKind::Var => "var",
+            DeclarationKind::Const | DeclarationKind::LocalConst => "const",
+            DeclarationKind::Override => "override",
+            DeclarationKind::Let => "let",
+            DeclarationKind::Member | DeclarationKind::Param => "",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Declaration {
+    pub name: String,
+    pub kind: DeclarationKind,
+    /// The name token.
+    pub selection: Range<usize>,
+    /// The whole declaration, including any attributes.
+    pub full: Range<usize>,
+    /// Where the name can be referred to.
+    pub scope: Range<usize>,
+    /// The function of a parameter or local, or the struct of a member.
+    pub parent: Option<usize>,
+    /// The type annotation as written, if any.
+    pub ty: Option<String>,
+    /// The braces of a function body or struct, including the braces themselves.
+    pub body: Option<Range<usize>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Reference {
+    pub span: Range<usize>,
+    /// The declaration this identifier resolves to, if it is a user declaration.
+    pub target: Option<usize>,
+}
+
+/// The declarations of a WGSL document and the identifiers referring to them.
+pub struct SourceIndex {
+    /// Every token except comments.
+    pub tokens: Vec<Token>,
+    /// For each bracket token, the index of its partner.
+    pub matching: Vec<Option<usize>>,
+    pub declarations: Vec<Declaration>,
+    pub references: Vec<Reference>,
+}
+
+impl SourceIndex {
+    pub fn new(text: &str) -> Self {
+        let tokens = tokenize(text)
+            .into_iter()
+            .filter(|token| token.kind != TokenKind::Comment)
+            .collect();
+        let mut parser = Parser::new(text, tokens);
+        parser.parse_module();
+        let mut index = SourceIndex {
+            tokens: parser.tokens,
+            matching: parser.matching,
+            declarations: parser.declarations,
+            references: Vec::new(),
+        };
+        index.references = parser
+            .references
+            .into_iter()
+            .map(|span| Reference {
+                target: index.resolve(&text[span.clone()], span.start),
+                span,
+            })
+            .collect();
+        index
+    }
+
+    /// Finds the declaration that `name` refers to at `offset`: the innermost one in
+    /// scope, preferring later declarations so shadowing works.
+    pub fn resolve(&self, name: &str, offset: usize) -> Option<usize> {
+        self.declarations
+            .iter()
+            .enumerate()
+            .filter(|(_, decl)| {
+                decl.name == name
+                    && decl.kind != DeclarationKind::Member
+                    && decl.scope.start <= offset
+                    && offset < decl.scope.end
+            })
+            .max_by_key(|(_, decl)| decl.scope.start)
+            .map(|(index, _)| index)
+    }
+
+    /// The identifier token touching `offset`, including when the cursor sits just past
+    /// its last character.
+    pub fn ident_at(&self, offset: usize) -> Option<Token> {
+        self.tokens
+            .iter()
+            .find(|token| {
+                token.kind == TokenKind::Ident && token.start <= offset && offset <= token.end
+            })
+            .copied()
+    }
+
+    /// The index of the last token starting before `offset`.
+    pub fn token_before(&self, offset: usize) -> Option<usize> {
+        self.tokens.iter().rposition(|token| token.start < offset)
+    }
+
+    /// The struct declaration called `name`.
+    pub fn find_struct(&self, name: &str) -> Option<usize> {
+        self.declarations
+            .iter()
+            .position(|decl| decl.kind == DeclarationKind::Struct && decl.name == name)
+    }
+
+    /// The members of the struct declared at `structure`.
+    pub fn members(&self, structure: usize) -> impl Iterator<Item = &Declaration> {
+        self.declarations.iter().filter(move |decl| {
+            decl.kind == DeclarationKind::Member && decl.parent == Some(structure)
+        })
+    }
+
+    /// The span of the first `@name(..)` attribute that starts within `within`.
+    pub fn attribute(&self, text: &str, within: Range<usize>, name: &str) -> Option<Range<usize>> {
+        let tokens = &self.tokens;
+        let at = (0..tokens.len().saturating_sub(1)).find(|&i| {
+            within.start <= tokens[i].start
+                && tokens[i].end <= within.end
+                && tokens[i].text(text) == "@"
+                && tokens[i + 1].text(text) == name
+        })?;
+        let end = match self.matching.get(at + 2).copied().flatten() {
+            Some(close) => tokens[close].end,
+            None => tokens[at + 1].end,
+        };
+        Some(tokens[at].start..end)
+    }
+
+    /// The parameters of the function declared at `function`.
+    pub fn params(&self, function: usize) -> impl Iterator<Item = &Declaration> {
+        self.declarations.iter().filter(move |decl| {
+            decl.kind == DeclarationKind::Param && decl.parent == Some(function)
+        })
+    }
+
+    /// The call whose parentheses `offset` is in, as the index of the callee's token and
+    /// the position of the argument at `offset`.
+    pub fn enclosing_call(&self, text: &str, offset: usize) -> Option<(usize, u32)> {
+        // the innermost parenthesis that is still open at the cursor
+        let open = (0..self.tokens.len()).rev().find(|&i| {
+            let token = self.tokens[i];
+            token.start < offset
+                && token.text(text) == "("
+                && self.matching[i].is_none_or(|close| self.tokens[close].start >= offset)
+        })?;
+        let callee = open.checked_sub(1)?;
+
+        // commas directly inside this call, not inside nested brackets
+        let mut argument = 0;
+        let mut i = open + 1;
+        while i < self.tokens.len() && self.tokens[i].start < offset {
+            match self.tokens[i].text(text) {
+                "," => argument += 1,
+                "(" | "[" | "{" => {
+                    if let Some(close) = self.matching[i] {
+                        i = close;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        Some((callee, argument))
+    }
+
+    /// The declaration named or referred to by the identifier at `offset`.
+    pub fn declaration_at(&self, offset: usize) -> Option<usize> {
+        let token = self.ident_at(offset)?;
+        if let Some(index) = self
+            .declarations
+            .iter()
+            .position(|decl| decl.selection == token.span())
+        {
+            return Some(index);
+        }
+        self.references
+            .iter()
+            .find(|reference| reference.span == token.span())
+            .and_then(|reference| reference.target)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    /// For each bracket token, the index of its partner.
+    matching: Vec<Option<usize>>,
+    pos: usize,
+    declarations: Vec<Declaration>,
+    references: Vec<Range<usize>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str, tokens: Vec<Token>) -> Self {
+        let mut matching = vec![None; tokens.len()];
+        let mut open = Vec::new();
+        for (index, token) in tokens.iter().enumerate() {
+            match token.text(text) {
+                "(" | "[" | "{" => open.push(index),
+                ")" | "]" | "}" => {
+                    if let Some(start) = open.pop() {
+                        matching[start] = Some(index);
+                        matching[index] = Some(start);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Parser {
+            text,
+            tokens,
+            matching,
+            pos: 0,
+            declarations: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> &'a str {
+        self.peek_at(self.pos)
+    }
+
+    fn peek_at(&self, pos: usize) -> &'a str {
+        match self.tokens.get(pos) {
+            Some(token) => token.text(self.text),
+            None => "",
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn start_of(&self, pos: usize) -> usize {
+        match self.tokens.get(pos) {
+            Some(token) => token.start,
+            None => self.text.len(),
+        }
+    }
+
+    fn end_of(&self, pos: usize) -> usize {
+        match self.tokens.get(pos) {
+            Some(token) => token.end,
+            None => self.text.len(),
+        }
+    }
+
+    /// The index just past the bracket matching the one at `pos`.
+    fn past_matching(&self, pos: usize) -> usize {
+        match self.matching.get(pos).copied().flatten() {
+            Some(close) => close + 1,
+            None => self.tokens.len(),
+        }
+    }
+
+    fn ident(&mut self) -> Option<Token> {
+        let token = *self.tokens.get(self.pos)?;
+        if token.kind != TokenKind::Ident {
+            return None;
+        }
+        self.pos += 1;
+        Some(token)
+    }
+
+    fn declare(&mut self, name: Token, kind: DeclarationKind, full_start: usize) -> usize {
+        self.declarations.push(Declaration {
+            name: name.text(self.text).to_string(),
+            kind,
+            selection: name.span(),
+            full: full_start..name.end,
+            scope: 0..self.text.len(),
+            parent: None,
+            ty: None,
+            body: None,
+        });
+        self.declarations.len() - 1
+    }
+
+    fn reference(&mut self, pos: usize) {
+        let token = self.tokens[pos];
+        if token.kind != TokenKind::Ident || KEYWORDS.contains(&token.text(self.text)) {
+            return;
+        }
+        // member accesses can only be resolved with type information
+        if pos > 0 && self.peek_at(pos - 1) == "." {
+            return;
+        }
+        self.references.push(token.span());
+    }
+
+    /// Skips `@name` or `@name(...)`, recording references in the arguments.
+    fn skip_attribute(&mut self) {
+        self.pos += 1;
+        self.ident();
+        if self.peek() == "(" {
+            let end = self.past_matching(self.pos);
+            self.expression(end);
+        }
+    }
+
+    fn skip_attributes(&mut self) {
+        while self.peek() == "@" {
+            self.skip_attribute();
+        }
+    }
+
+    /// Records references in tokens up to `end`, leaving the parser there.
+    fn expression(&mut self, end: usize) {
+        while self.pos < end.min(self.tokens.len()) {
+            self.reference(self.pos);
+            self.pos += 1;
+        }
+    }
+
+    /// Reads a type, stopping at any of `stops` outside of template brackets, and
+    /// returns it as text.
+    fn ty(&mut self, stops: &[&str]) -> String {
+        let mut depth = 0;
+        let mut ty = String::new();
+        while !self.at_end() {
+            let token = self.peek();
+            if depth == 0 && (stops.contains(&token) || token == "{" || token == ";") {
+                break;
+            }
+            match token {
+                "<" => depth += 1,
+                ">" => depth -= 1,
+                _ => {}
+            }
+            self.reference(self.pos);
+            ty.push_str(token);
+            if token == "," {
+                ty.push(' ');
+            }
+            self.pos += 1;
+        }
+        ty
+    }
+
+    /// Skips optional `<...>` template arguments, such as a `var` address space.
+    fn skip_template(&mut self) {
+        if self.peek() != "<" {
+            return;
+        }
+        let mut depth = 0;
+        while !self.at_end() {
+            match self.peek() {
+                "<" => depth += 1,
+                ">" => depth -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Skips to just past the next `;` outside of brackets, recording references.
+    fn statement_end(&mut self) {
+        while !self.at_end() {
+            match self.peek() {
+                ";" => {
+                    self.pos += 1;
+                    return;
+                }
+                "(" | "[" => {
+                    let end = self.past_matching(self.pos);
+                    self.expression(end);
+                }
+                "{" | "}" => return,
+                _ => {
+                    self.reference(self.pos);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn parse_module(&mut self) {
+        while !self.at_end() {
+            let pos = self.pos;
+            let item_start = self.start_of(self.pos);
+            self.skip_attributes();
+            match self.peek() {
+                "fn" => self.function(item_start),
+                "struct" => self.structure(item_start),
+                "var" => self.global(DeclarationKind::GlobalVar, item_start),
+                "const" | "let" => self.global(DeclarationKind::Const, item_start),
+                "override" => self.global(DeclarationKind::Override, item_start),
+                "alias" | "type" => self.alias(item_start),
+                _ => self.statement_end(),
+            }
+            // stray braces at module scope would otherwise stop us from making progress
+            if self.pos == pos {
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn function(&mut self, item_start: usize) {
+        self.pos += 1;
+        let name = match self.ident() {
+            Some(name) => name,
+            None => return,
+        };
+        let function = self.declare(name, DeclarationKind::Function, item_start);
+
+        let mut params = Vec::new();
+        if self.peek() == "(" {
+            let end = self.past_matching(self.pos) - 1;
+            self.pos += 1;
+            while self.pos < end {
+                let param_start = self.start_of(self.pos);
+                self.skip_attributes();
+                if let Some(name) = self.ident() {
+                    let param = self.declare(name, DeclarationKind::Param, param_start);
+                    if self.peek() == ":" {
+                        self.pos += 1;
+                        let ty = self.ty(&[",", ")"]);
+                        self.declarations[param].ty = Some(ty);
+                    }
+                    self.declarations[param].full.end = self.end_of(self.pos - 1);
+                    self.declarations[param].parent = Some(function);
+                    params.push(param);
+                }
+                // skip anything unexpected up to the next parameter
+                while self.pos < end && self.peek() != "," {
+                    self.pos += 1;
+                }
+                self.pos += 1;
+            }
+            self.pos = end + 1;
+        }
+
+        if self.peek() == "-" && self.peek_at(self.pos + 1) == ">" {
+            self.pos += 2;
+            self.skip_attributes();
+            let ty = self.ty(&[]);
+            self.declarations[function].ty = Some(ty);
+        }
+
+        if self.peek() != "{" {
+            self.declarations[function].full.end = self.end_of(self.pos.saturating_sub(1));
+            return;
+        }
+        let open = self.pos;
+        let close = self.past_matching(open) - 1;
+        let body = self.start_of(open)..self.end_of(close);
+        for param in params {
+            self.declarations[param].scope = body.clone();
+        }
+        self.declarations[function].full.end = body.end;
+        self.declarations[function].body = Some(body);
+        self.pos = open + 1;
+        self.block(close, function);
+        self.pos = close + 1;
+    }
+
+    /// Walks a function body up to the closing brace at `close`.
+    fn block(&mut self, close: usize, function: usize) {
+        // the ends of the blocks enclosing the current token, innermost last
+        let mut scopes = vec![self.end_of(close)];
+        while self.pos < close {
+            let start = self.start_of(self.pos);
+            while scopes.len() > 1 && scopes[scopes.len() - 1] <= start {
+                scopes.pop();
+            }
+            match self.peek() {
+                "{" => {
+                    let end = self.past_matching(self.pos) - 1;
+                    scopes.push(self.end_of(end));
+                    self.pos += 1;
+                }
+                "for" if self.peek_at(self.pos + 1) == "(" => {
+                    // variables declared in the header are visible in the loop body only
+                    let after_header = self.past_matching(self.pos + 1);
+                    if self.peek_at(after_header) == "{" {
+                        let end = self.past_matching(after_header) - 1;
+                        scopes.push(self.end_of(end));
+                    }
+                    self.pos += 2;
+                }
+                "let" | "var" | "const" => {
+                    let kind = match self.peek() {
+                        "let" => DeclarationKind::Let,
+                        "var" => DeclarationKind::Var,
+                        _ => DeclarationKind::LocalConst,
+                    };
+                    self.pos += 1;
+                    self.skip_template();
+                    let name = match self.ident() {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    let local = self.declare(name, kind, start);
+                    if self.peek() == ":" {
+                        self.pos += 1;
+                        let ty = self.ty(&["=", ")"]);
+                        self.declarations[local].ty = Some(ty);
+                    }
+                    // the initializer can't see the name being declared
+                    self.statement_end();
+                    let end = self.end_of(self.pos - 1);
+                    let scope_end = scopes[scopes.len() - 1];
+                    let decl = &mut self.declarations[local];
+                    decl.full.end = end;
+                    decl.scope = end..scope_end;
+                    decl.parent = Some(function);
+                }
+                _ => {
+                    self.reference(self.pos);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn structure(&mut self, item_start: usize) {
+        self.pos += 1;
+        let name = match self.ident() {
+            Some(name) => name,
+            None => return,
+        };
+        let structure = self.declare(name, DeclarationKind::Struct, item_start);
+        if self.peek() != "{" {
+            return;
+        }
+        let open = self.pos;
+        let close = self.past_matching(open) - 1;
+        self.pos += 1;
+        while self.pos < close {
+            let member_start = self.start_of(self.pos);
+            self.skip_attributes();
+            if let Some(name) = self.ident() {
+                let member = self.declare(name, DeclarationKind::Member, member_start);
+                if self.peek() == ":" {
+                    self.pos += 1;
+                    let ty = self.ty(&[",", "}"]);
+                    self.declarations[member].ty = Some(ty);
+                }
+                let decl = &mut self.declarations[member];
+                decl.full.end = self.tokens[self.pos - 1].end;
+                decl.scope = 0..0;
+                decl.parent = Some(structure);
+            }
+            while self.pos < close && self.peek() != "," && self.peek() != ";" {
+                self.pos += 1;
+            }
+            self.pos += 1;
+        }
+        let body = self.start_of(open)..self.end_of(close);
+        self.declarations[structure].full.end = body.end;
+        self.declarations[structure].body = Some(body);
+        self.pos = close + 1;
+        if self.peek() == ";" {
+            self.pos += 1;
+        }
+    }
+
+    fn global(&mut self, kind: DeclarationKind, item_start: usize) {
+        self.pos += 1;
+        self.skip_template();
+        let name = match self.ident() {
+            Some(name) => name,
+            None => return,
+        };
+        let global = self.declare(name, kind, item_start);
+        if self.peek() == ":" {
+            self.pos += 1;
+            let ty = self.ty(&["="]);
+            self.declarations[global].ty = Some(ty);
+        }
+        self.statement_end();
+        self.declarations[global].full.end = self.end_of(self.pos - 1);
+    }
+
+    fn alias(&mut self, item_start: usize) {
+        self.pos += 1;
+        let name = match self.ident() {
+            Some(name) => name,
+            None => return,
+        };
+        let alias = self.declare(name, DeclarationKind::Alias, item_start);
+        if self.peek() == "=" {
+            self.pos += 1;
+            let ty = self.ty(&[]);
+            self.declarations[alias].ty = Some(ty);
+        }
+        self.statement_end();
+        self.declarations[alias].full.end = self.end_of(self.pos - 1);
+    }
+}
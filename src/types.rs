@@ -0,0 +1,219 @@
+//! Formatting of naga types back into WGSL syntax.
+
+use naga::{
+    AddressSpace, ArraySize, BuiltIn, Handle, ImageClass, ImageDimension, Module, ScalarKind,
+    StorageAccess, Type, TypeInner, VectorSize,
+};
+
+pub fn type_name(module: &Module, handle: Handle<Type>) -> String {
+    let ty = &module.types[handle];
+    match ty.name {
+        Some(ref name) => name.clone(),
+        None => type_inner_name(module, &ty.inner),
+    }
+}
+
+pub fn type_inner_name(module: &Module, inner: &TypeInner) -> String {
+    match *inner {
+        TypeInner::Scalar { kind, width } => scalar_name(kind, width).to_string(),
+        TypeInner::Vector { size, kind, width } => {
+            format!("vec{}<{}>", vector_size(size), scalar_name(kind, width))
+        }
+        TypeInner::Matrix {
+            columns,
+            rows,
+            width,
+        } => format!(
+            "mat{}x{}<{}>",
+            vector_size(columns),
+            vector_size(rows),
+            scalar_name(ScalarKind::Float, width)
+        ),
+        TypeInner::Atomic { kind, width } => format!("atomic<{}>", scalar_name(kind, width)),
+        TypeInner::Pointer { base, space } => format!(
+            "ptr<{}, {}>",
+            address_space_name(space),
+            type_name(module, base)
+        ),
+        TypeInner::ValuePointer {
+            size,
+            kind,
+            width,
+            space,
+        } => {
+            let base = match size {
+                Some(size) => format!("vec{}<{}>", vector_size(size), scalar_name(kind, width)),
+                None => scalar_name(kind, width).to_string(),
+            };
+            format!("ptr<{}, {}>", address_space_name(space), base)
+        }
+        TypeInner::Array { base, size, .. } => match size {
+            ArraySize::Constant(size) => format!("array<{}, {}>", type_name(module, base), size),
+            ArraySize::Dynamic => format!("array<{}>", type_name(module, base)),
+        },
+        TypeInner::BindingArray { base, size } => match size {
+            ArraySize::Constant(size) => {
+                format!("binding_array<{}, {}>", type_name(module, base), size)
+            }
+            ArraySize::Dynamic => format!("binding_array<{}>", type_name(module, base)),
+        },
+        TypeInner::Struct { .. } => "struct".to_string(),
+        TypeInner::Image {
+            dim,
+            arrayed,
+            class,
+        } => {
+            let dim = match dim {
+                ImageDimension::D1 => "1d",
+                ImageDimension::D2 => "2d",
+                ImageDimension::D3 => "3d",
+                ImageDimension::Cube => "cube",
+            };
+            let arrayed = if arrayed { "_array" } else { "" };
+            match class {
+                ImageClass::Sampled { kind, multi } => {
+                    let multi = if multi { "multisampled_" } else { "" };
+                    format!(
+                        "texture_{}{}{}<{}>",
+                        multi,
+                        dim,
+                        arrayed,
+                        scalar_name(kind, 4)
+                    )
+                }
+                ImageClass::Depth { multi } => {
+                    let multi = if multi { "multisampled_" } else { "" };
+                    format!("texture_depth_{}{}{}", multi, dim, arrayed)
+                }
+                ImageClass::Storage { format, access } => {
+                    let format = format!("{:?}", format).to_lowercase();
+                    format!(
+                        "texture_storage_{}{}<{}, {}>",
+                        dim,
+                        arrayed,
+                        format,
+                        access_name(access)
+                    )
+                }
+            }
+        }
+        TypeInner::Sampler { comparison } => {
+            if comparison {
+                "sampler_comparison".to_string()
+            } else {
+                "sampler".to_string()
+            }
+        }
+        TypeInner::AccelerationStructure => "acceleration_structure".to_string(),
+        TypeInner::RayQuery => "ray_query".to_string(),
+    }
+}
+
+pub fn scalar_name(kind: ScalarKind, width: u8) -> &'static str {
+    match (kind, width) {
+        (ScalarKind::Bool, _) => "bool",
+        (ScalarKind::Sint, _) => "i32",
+        (ScalarKind::Uint, _) => "u32",
+        (ScalarKind::Float, 2) => "f16",
+        (ScalarKind::Float, 8) => "f64",
+        (ScalarKind::Float, _) => "f32",
+    }
+}
+
+pub fn address_space_name(space: AddressSpace) -> String {
+    match space {
+        AddressSpace::Function => "function".to_string(),
+        AddressSpace::Private => "private".to_string(),
+        AddressSpace::WorkGroup => "workgroup".to_string(),
+        AddressSpace::Uniform => "uniform".to_string(),
+        AddressSpace::Storage { access } => format!("storage, {}", access_name(access)),
+        AddressSpace::Handle => "handle".to_string(),
+        AddressSpace::PushConstant => "push_constant".to_string(),
+    }
+}
+
+fn access_name(access: StorageAccess) -> &'static str {
+    if access.contains(StorageAccess::LOAD | StorageAccess::STORE) {
+        "read_write"
+    } else if access.contains(StorageAccess::STORE) {
+        "write"
+    } else {
+        "read"
+    }
+}
+
+fn vector_size(size: VectorSize) -> u8 {
+    size as u8
+}
+
+/// Spells a type written in WGSL the way `type_inner_name` does, without whitespace and
+/// with shorthands such as `vec3f` expanded, so the two can be compared.
+pub fn canonical_type_name(ty: &str) -> String {
+    let mut canonical = String::with_capacity(ty.len());
+    let mut word = String::new();
+    for c in ty.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            continue;
+        }
+        canonical.push_str(&expand_shorthand(&word).unwrap_or_else(|| word.clone()));
+        word.clear();
+        if !c.is_whitespace() {
+            canonical.push(c);
+        }
+    }
+    canonical
+}
+
+/// `vec3f` as `vec3<f32>`, `mat4x4h` as `mat4x4<f16>` and so on.
+fn expand_shorthand(word: &str) -> Option<String> {
+    if !(word.starts_with("vec") || word.starts_with("mat")) {
+        return None;
+    }
+    let (generic, suffix) = word.split_at(word.len() - 1);
+    let scalar = match suffix {
+        "f" => "f32",
+        "h" => "f16",
+        "i" if generic.starts_with("vec") => "i32",
+        "u" if generic.starts_with("vec") => "u32",
+        _ => return None,
+    };
+    let dimensions = &generic[3..];
+    let valid = match dimensions.as_bytes() {
+        [n] => (b'2'..=b'4').contains(n),
+        [c, b'x', r] => (b'2'..=b'4').contains(c) && (b'2'..=b'4').contains(r),
+        _ => false,
+    };
+    if valid {
+        Some(format!("{}<{}>", generic, scalar))
+    } else {
+        None
+    }
+}
+
+/// The WGSL name of a builtin value, or the GLSL one for those WGSL lacks.
+pub fn builtin_name(builtin: BuiltIn) -> &'static str {
+    match builtin {
+        BuiltIn::Position { .. } => "position",
+        BuiltIn::ViewIndex => "view_index",
+        BuiltIn::BaseInstance => "base_instance",
+        BuiltIn::BaseVertex => "base_vertex",
+        BuiltIn::ClipDistance => "clip_distances",
+        BuiltIn::CullDistance => "cull_distance",
+        BuiltIn::InstanceIndex => "instance_index",
+        BuiltIn::PointSize => "point_size",
+        BuiltIn::VertexIndex => "vertex_index",
+        BuiltIn::FragDepth => "frag_depth",
+        BuiltIn::PointCoord => "point_coord",
+        BuiltIn::FrontFacing => "front_facing",
+        BuiltIn::PrimitiveIndex => "primitive_index",
+        BuiltIn::SampleIndex => "sample_index",
+        BuiltIn::SampleMask => "sample_mask",
+        BuiltIn::GlobalInvocationId => "global_invocation_id",
+        BuiltIn::LocalInvocationId => "local_invocation_id",
+        BuiltIn::LocalInvocationIndex => "local_invocation_index",
+        BuiltIn::WorkGroupId => "workgroup_id",
+        BuiltIn::WorkGroupSize => "workgroup_size",
+        BuiltIn::NumWorkGroups => "num_workgroups",
+    }
+}
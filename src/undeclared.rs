@@ -0,0 +1,105 @@
+//! Reports names used in a function before the `let`, `var` or `const` declaring them,
+//! which WGSL doesn't allow, pointing at the declaration that comes too late.
+
+use std::ops::Range;
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Url,
+};
+
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// Reports every use of a name that doesn't resolve but that a local declared later in
+/// the same block, or a block around it, goes by. Uses of a shadowed name before the
+/// shadowing declaration refer to the outer one and are fine.
+pub fn check(uri: &Url, text: &str, index: &SourceIndex, source: &str) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    for reference in &index.references {
+        // a name that resolves, if only to something at module scope, is declared
+        if reference.target.is_some() {
+            continue;
+        }
+        let name = &text[reference.span.clone()];
+        let use_start = reference.span.start;
+        // the nearest later declaration whose block the use is in
+        let later = index
+            .declarations
+            .iter()
+            .filter(|decl| {
+                matches!(
+                    decl.kind,
+                    DeclarationKind::Let | DeclarationKind::Var | DeclarationKind::LocalConst
+                ) && decl.name == name
+                    && use_start < decl.selection.start
+            })
+            .filter(|decl| {
+                block(index, text, decl.selection.start)
+                    .is_some_and(|block| block.contains(&use_start))
+            })
+            .min_by_key(|decl| decl.selection.start);
+        let decl = match later {
+            Some(decl) => decl,
+            None => continue,
+        };
+        diags.push(Diagnostic {
+            range: byte_range_to_range(text, reference.span.clone()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String("use-before-declaration".to_string())),
+            source: Some(source.to_string()),
+            message: format!("`{}` is used before it is declared", name),
+            related_information: Some(vec![DiagnosticRelatedInformation {
+                location: Location {
+                    uri: uri.clone(),
+                    range: byte_range_to_range(text, decl.selection.clone()),
+                },
+                message: format!("`{}` is declared here", name),
+            }]),
+            ..Default::default()
+        });
+    }
+    diags
+}
+
+/// The span of the innermost braces around `offset`.
+fn block(index: &SourceIndex, text: &str, offset: usize) -> Option<Range<usize>> {
+    index
+        .tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| token.start < offset && token.text(text) == "{")
+        .filter_map(|(open, token)| {
+            let close = index.tokens[index.matching[open]?];
+            Some(token.start..close.end).filter(|block| block.contains(&offset))
+        })
+        .next_back()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn early_uses(text: &str) -> Vec<Diagnostic> {
+        let uri = Url::parse("file:///test.wgsl").unwrap();
+        let index = SourceIndex::new(text);
+        check(&uri, text, &index, "test")
+    }
+
+    #[test]
+    fn flags_uses_before_the_declaration() {
+        let diags = early_uses("fn main() {\n    let a = b + 1;\n    let b = 2;\n}\n");
+        assert_eq!(diags.len(), 1, "{:?}", diags);
+        assert_eq!(diags[0].message, "`b` is used before it is declared");
+        let related = diags[0].related_information.as_ref().unwrap();
+        assert_eq!(
+            related[0].location.range.start,
+            lsp_types::Position::new(2, 8)
+        );
+    }
+
+    #[test]
+    fn accepts_uses_of_a_name_shadowed_later() {
+        let text = "const b = 1;\nfn main() {\n    let a = b + 1;\n    let b = 2;\n}\n";
+        assert!(early_uses(text).is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+//! Explains naga's uniformity errors, whose own message only names the requirement and
+//! its cause by their handles.
+//!
+//! naga 0.14 only requires uniform control flow for barriers: the requirements of
+//! derivatives and implicit-level sampling are turned off for fragment shaders, so those
+//! never fail.
+
+use naga::valid::{EntryPointError, FunctionError, UniformityRequirements, ValidationError};
+
+/// What to do about code that needs uniform control flow and doesn't get it, if `err` is
+/// naga's uniformity analysis failing.
+pub fn explain(err: &ValidationError) -> Option<String> {
+    let requirement = match *err {
+        ValidationError::Function {
+            source: FunctionError::NonUniformControlFlow(requirement, ..),
+            ..
+        }
+        | ValidationError::EntryPoint {
+            source: EntryPointError::Function(FunctionError::NonUniformControlFlow(requirement, ..)),
+            ..
+        } => requirement,
+        _ => return None,
+    };
+    advice(requirement)
+}
+
+/// What to do about code that needs uniform control flow for `requirement`, if it is one
+/// naga can fail.
+fn advice(requirement: UniformityRequirements) -> Option<String> {
+    if !requirement.contains(UniformityRequirements::WORK_GROUP_BARRIER) {
+        return None;
+    }
+    Some(
+        "a barrier has to be reached by every invocation of the workgroup at once, so it \
+         must be in uniform control flow, but this call may not run in every invocation: it \
+         follows a branch or loop on a value that differs between invocations, such as a \
+         builtin input, a storage variable or the result of sampling, or a `return` only \
+         some invocations take. Move it before that, or compute the condition from uniforms"
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+    #[test]
+    fn explains_barriers() {
+        // naga only checks the requirements of emitted expressions, which a barrier
+        // never is from WGSL, so its uniformity error can't be had from a snippet
+        assert!(advice(UniformityRequirements::WORK_GROUP_BARRIER).is_some());
+    }
+
+    #[test]
+    fn leaves_other_errors_alone() {
+        // an argument of the wrong type, which only the validator catches
+        let text = "fn f(a: f32) -> f32 {\n    return a;\n}\nfn main() {\n    let x = f(2u);\n}\n";
+        let module = naga::front::wgsl::parse_str(text).expect("the test shader parses");
+        let err = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .expect_err("the test shader doesn't validate");
+        assert!(explain(err.as_inner()).is_none());
+    }
+}
@@ -0,0 +1,19 @@
+//! The entry points of the WASM build, for editors running in the browser that have no
+//! transport to talk to a language server over.
+
+use wasm_bindgen::prelude::*;
+
+use crate::config::Config;
+use crate::diagnostics::check_source;
+use crate::document::Language;
+
+/// Diagnoses the WGSL `source` with `config`, a JSON object of the same settings as the
+/// server's `initializationOptions`, returning a JSON array of LSP diagnostics.
+///
+/// Positions count UTF-16 code units, like JavaScript strings do.
+#[wasm_bindgen]
+pub fn diagnostics(source: &str, config: &str) -> String {
+    let config = Config::from_value(serde_json::from_str(config).ok());
+    let diags = check_source(source, Language::Wgsl, &config);
+    serde_json::to_string(&diags).unwrap_or_default()
+}
@@ -0,0 +1,68 @@
+//! Checks on the workgroup sizes of compute shaders against the limits of the target.
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use naga::{Module, ShaderStage};
+
+use crate::config::WorkgroupLimits;
+use crate::position::byte_range_to_range;
+use crate::syntax::{DeclarationKind, SourceIndex};
+
+/// Warns about compute entry points whose workgroup size is zero or exceeds `limits`.
+pub fn check(
+    text: &str,
+    index: &SourceIndex,
+    module: &Module,
+    limits: &WorkgroupLimits,
+    source: &str,
+) -> Vec<Diagnostic> {
+    module
+        .entry_points
+        .iter()
+        .filter(|entry_point| entry_point.stage == ShaderStage::Compute)
+        .filter_map(|entry_point| {
+            let [x, y, z] = entry_point.workgroup_size;
+            let total = u64::from(x) * u64::from(y) * u64::from(z);
+            let size = format!("{} × {} × {} = {} invocations", x, y, z, total);
+            let message = if total == 0 {
+                format!("workgroup size {} must not be zero", size)
+            } else if x > limits.max_x || y > limits.max_y || z > limits.max_z {
+                format!(
+                    "workgroup size {} exceeds the limit of {} × {} × {}",
+                    size, limits.max_x, limits.max_y, limits.max_z
+                )
+            } else if total > u64::from(limits.max_invocations) {
+                format!(
+                    "workgroup size {} exceeds the limit of {} invocations",
+                    size, limits.max_invocations
+                )
+            } else {
+                return None;
+            };
+            let range = attribute_range(index, text, &entry_point.name)
+                .map_or_else(lsp_types::Range::default, |range| {
+                    byte_range_to_range(text, range)
+                });
+            Some(Diagnostic {
+                range,
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String("workgroup-size".to_string())),
+                source: Some(source.to_string()),
+                message,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// The span of the `@workgroup_size(..)` attribute of the function `name`, if the source
+/// is WGSL.
+fn attribute_range(index: &SourceIndex, text: &str, name: &str) -> Option<std::ops::Range<usize>> {
+    let full = index
+        .declarations
+        .iter()
+        .find(|decl| decl.kind == DeclarationKind::Function && decl.name == name)?
+        .full
+        .clone();
+    index.attribute(text, full, "workgroup_size")
+}
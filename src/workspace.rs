@@ -0,0 +1,192 @@
+//! The WGSL files of the workspace, indexed without being opened so that workspace
+//! symbols and references find what is declared in them.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use lsp_types::{InitializeParams, Url};
+
+use crate::config::Config;
+use crate::syntax::SourceIndex;
+
+/// A file of the workspace as last read from disk.
+pub struct WorkspaceFile {
+    pub text: String,
+    pub index: SourceIndex,
+}
+
+/// The indexed files, shared with the thread indexing them in the background.
+#[derive(Default)]
+pub struct Workspace {
+    roots: Vec<PathBuf>,
+    indexing: bool,
+    glob: String,
+    files: Arc<Mutex<HashMap<Url, WorkspaceFile>>>,
+}
+
+impl Workspace {
+    /// The workspace of the client's folders, or its root if it sent no folders.
+    #[allow(deprecated)]
+    pub fn new(params: &InitializeParams) -> Self {
+        let folders = params
+            .workspace_folders
+            .iter()
+            .flatten()
+            .map(|folder| &folder.uri);
+        let roots = folders
+            .chain(
+                params
+                    .root_uri
+                    .as_ref()
+                    .filter(|_| params.workspace_folders.is_none()),
+            )
+            .filter_map(|uri| uri.to_file_path().ok())
+            .collect();
+        Workspace {
+            roots,
+            ..Workspace::default()
+        }
+    }
+
+    /// Drops what was indexed and, if `workspaceIndex` is set, indexes every file matching
+    /// `workspaceGlob` again on another thread, so that big workspaces don't hold up
+    /// requests.
+    pub fn reindex(&mut self, config: &Config) {
+        self.indexing = config.workspace_index;
+        self.glob = config.workspace_glob.clone();
+        // a thread still indexing keeps filling the map it started with
+        self.files = Arc::default();
+        if !self.indexing {
+            return;
+        }
+        let roots = self.roots.clone();
+        let glob = self.glob.clone();
+        let files = Arc::clone(&self.files);
+        thread::spawn(move || {
+            for root in &roots {
+                let mut paths = Vec::new();
+                find_files(root, root, &glob, &mut paths);
+                log::info!("indexing {} files in {}", paths.len(), root.display());
+                for path in paths {
+                    if let Some((uri, file)) = read(&path) {
+                        files.lock().unwrap().insert(uri, file);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Whether there is a workspace to index and the user asked for it.
+    pub fn is_indexed(&self) -> bool {
+        self.indexing && !self.roots.is_empty()
+    }
+
+    /// The glob the indexed files match, relative to the workspace folders.
+    pub fn glob(&self) -> &str {
+        &self.glob
+    }
+
+    /// Reads a created or changed file again, if it is one we index.
+    pub fn update(&self, uri: &Url) {
+        if !self.indexing {
+            return;
+        }
+        let path = match uri.to_file_path() {
+            Ok(path) => path,
+            Err(()) => return,
+        };
+        let matched = self.roots.iter().any(|root| {
+            path.strip_prefix(root)
+                .is_ok_and(|relative| glob_matches(&self.glob, relative))
+        });
+        if !matched {
+            return;
+        }
+        match read(&path) {
+            Some((uri, file)) => {
+                self.files.lock().unwrap().insert(uri, file);
+            }
+            None => self.remove(uri),
+        }
+    }
+
+    pub fn remove(&self, uri: &Url) {
+        self.files.lock().unwrap().remove(uri);
+    }
+
+    /// Runs `f` on the files indexed so far.
+    pub fn with_files<T>(&self, f: impl FnOnce(&HashMap<Url, WorkspaceFile>) -> T) -> T {
+        f(&self.files.lock().unwrap())
+    }
+}
+
+fn read(path: &Path) -> Option<(Url, WorkspaceFile)> {
+    let uri = Url::from_file_path(path).ok()?;
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => {
+            log::warn!("failed to read {}: {}", path.display(), err);
+            return None;
+        }
+    };
+    let index = SourceIndex::new(&text);
+    Some((uri, WorkspaceFile { text, index }))
+}
+
+/// Collects the files under `dir` whose path relative to `root` matches `glob`, skipping
+/// hidden directories such as `.git`.
+fn find_files(root: &Path, dir: &Path, glob: &str, paths: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::warn!("failed to list {}: {}", dir.display(), err);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() && !hidden => find_files(root, &path, glob, paths),
+            Ok(file_type) if file_type.is_file() => {
+                let matched = path
+                    .strip_prefix(root)
+                    .is_ok_and(|relative| glob_matches(glob, relative));
+                if matched {
+                    paths.push(path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether a relative path matches a glob, in which `**` stands for any number of
+/// directories, `*` for any part of a name and `?` for any one character.
+fn glob_matches(glob: &str, path: &Path) -> bool {
+    fn segments(glob: &[&str], path: &[String]) -> bool {
+        match glob.split_first() {
+            None => path.is_empty(),
+            Some((&"**", rest)) => (0..=path.len()).any(|skipped| segments(rest, &path[skipped..])),
+            Some((pattern, rest)) => path.split_first().is_some_and(|(name, path)| {
+                wildcard(pattern.as_bytes(), name.as_bytes()) && segments(rest, path)
+            }),
+        }
+    }
+    fn wildcard(pattern: &[u8], name: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some((b'*', rest)) => (0..=name.len()).any(|taken| wildcard(rest, &name[taken..])),
+            Some((b'?', rest)) => !name.is_empty() && wildcard(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && wildcard(rest, &name[1..]),
+        }
+    }
+    let glob: Vec<&str> = glob.split('/').collect();
+    let path: Vec<String> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    segments(&glob, &path)
+}